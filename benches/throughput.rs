@@ -0,0 +1,212 @@
+//! Throughput and contention benchmarks for the bridge's hot paths. No network I/O.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, RwLock};
+use vecord::message::BridgeMessage;
+use vecord::metadata::{MetadataCache, UserMetadata};
+use vecord::nostr::{ShardedSubscriberList, SubscriberList};
+use vector_sdk::nostr::{Keys, PublicKey};
+
+const SENDERS: usize = 10;
+const MESSAGES_PER_SENDER: usize = 100;
+const READERS: usize = 100;
+
+fn random_pubkeys(count: usize) -> Vec<PublicKey> {
+    (0..count).map(|_| Keys::generate().public_key()).collect()
+}
+
+/// Ten concurrent senders feeding one receiver, as the Discord handler feeds the Nostr task
+fn bench_mpsc_round_trip(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    c.bench_function("mpsc_discord_message_10_senders", |b| {
+        b.to_async(&rt).iter(|| async {
+            let (tx, mut rx) = mpsc::channel::<BridgeMessage>(100);
+
+            for sender in 0..SENDERS {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    for i in 0..MESSAGES_PER_SENDER {
+                        let message = BridgeMessage::Discord {
+                            author: format!("sender-{}", sender),
+                            content: format!("message {}", i),
+                            image: None,
+                            image_url: None,
+                            federation_source: None,
+                            guild_name: None,
+                            guild_icon_url: None,
+                            author_id: None,
+                            message_id: None,
+                            span: tracing::Span::none(),
+                        };
+                        tx.send(message).await.unwrap();
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut received = 0;
+            while let Some(message) = rx.recv().await {
+                black_box(message);
+                received += 1;
+            }
+            assert_eq!(received, SENDERS * MESSAGES_PER_SENDER);
+        });
+    });
+}
+
+/// `MetadataCache::get` against 10 000 entries from 8 reader threads
+fn bench_metadata_cache_get(c: &mut Criterion) {
+    let cache = MetadataCache::new(None).unwrap();
+    let pubkeys = random_pubkeys(10_000);
+    for pubkey in &pubkeys {
+        cache.put(UserMetadata::new(pubkey).unwrap());
+    }
+
+    c.bench_function("metadata_cache_get_8_readers", |b| {
+        b.iter(|| {
+            std::thread::scope(|scope| {
+                for reader in 0..8 {
+                    let cache = &cache;
+                    let pubkeys = &pubkeys;
+                    scope.spawn(move || {
+                        for pubkey in pubkeys.iter().skip(reader).step_by(8).take(1_000) {
+                            black_box(cache.get(pubkey));
+                        }
+                    });
+                }
+            });
+        });
+    });
+}
+
+/// `SubscriberList::get_all` over 1000 subscribers while another task adds and removes
+fn bench_subscriber_get_all(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let subscribers = SubscriberList::new(None).unwrap();
+    rt.block_on(async {
+        for pubkey in random_pubkeys(1_000) {
+            subscribers.add(pubkey).await;
+        }
+    });
+
+    // Churn a separate set of keys for the duration of the benchmark
+    let churn = subscribers.clone();
+    let churn_keys = random_pubkeys(100);
+    let churn_task = rt.spawn(async move {
+        loop {
+            for pubkey in &churn_keys {
+                churn.add(*pubkey).await;
+            }
+            for pubkey in &churn_keys {
+                churn.remove(pubkey).await;
+            }
+            tokio::task::yield_now().await;
+        }
+    });
+
+    c.bench_function("subscriber_list_get_all_1000_with_churn", |b| {
+        b.to_async(&rt).iter(|| async {
+            black_box(subscribers.get_all().await);
+        });
+    });
+
+    churn_task.abort();
+}
+
+/// `contains` over 10 000 subscribers from 100 concurrent readers while a writer churns,
+/// sharded against the single lock `SubscriberList` used before
+fn bench_sharded_subscribers(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let pubkeys = Arc::new(random_pubkeys(10_000));
+    let churn_keys = Arc::new(random_pubkeys(100));
+    let sharded = ShardedSubscriberList::from_pubkeys(pubkeys.iter().copied());
+    let unsharded = Arc::new(RwLock::new(pubkeys.iter().copied().collect::<HashSet<_>>()));
+
+    let mut group = c.benchmark_group("subscriber_contains_100_readers_10000");
+
+    group.bench_function("sharded", |b| {
+        b.to_async(&rt).iter(|| async {
+            let writer = {
+                let (set, churn_keys) = (sharded.clone(), churn_keys.clone());
+                tokio::spawn(async move {
+                    for pubkey in churn_keys.iter() {
+                        set.add(*pubkey).await;
+                    }
+                    for pubkey in churn_keys.iter() {
+                        set.remove(pubkey).await;
+                    }
+                })
+            };
+            let readers: Vec<_> = (0..READERS).map(|reader| {
+                let (set, pubkeys) = (sharded.clone(), pubkeys.clone());
+                tokio::spawn(async move {
+                    for pubkey in pubkeys.iter().skip(reader).step_by(READERS) {
+                        black_box(set.contains(pubkey).await);
+                    }
+                })
+            }).collect();
+            for reader in readers {
+                reader.await.unwrap();
+            }
+            writer.await.unwrap();
+        });
+    });
+
+    group.bench_function("unsharded", |b| {
+        b.to_async(&rt).iter(|| async {
+            let writer = {
+                let (set, churn_keys) = (unsharded.clone(), churn_keys.clone());
+                tokio::spawn(async move {
+                    for pubkey in churn_keys.iter() {
+                        set.write().await.insert(*pubkey);
+                    }
+                    for pubkey in churn_keys.iter() {
+                        set.write().await.remove(pubkey);
+                    }
+                })
+            };
+            let readers: Vec<_> = (0..READERS).map(|reader| {
+                let (set, pubkeys) = (unsharded.clone(), pubkeys.clone());
+                tokio::spawn(async move {
+                    for pubkey in pubkeys.iter().skip(reader).step_by(READERS) {
+                        black_box(set.read().await.contains(pubkey));
+                    }
+                })
+            }).collect();
+            for reader in readers {
+                reader.await.unwrap();
+            }
+            writer.await.unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+/// `UserMetadata::get_best_name` on a pre-built struct, 100 000 times per iteration
+fn bench_get_best_name(c: &mut Criterion) {
+    let mut metadata = UserMetadata::new(&Keys::generate().public_key()).unwrap();
+    metadata.name = Some("alice".to_string());
+
+    c.bench_function("user_metadata_get_best_name_100k", |b| {
+        b.iter(|| {
+            for _ in 0..100_000 {
+                black_box(black_box(&metadata).get_best_name());
+            }
+        });
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_mpsc_round_trip,
+    bench_metadata_cache_get,
+    bench_subscriber_get_all,
+    bench_sharded_subscribers,
+    bench_get_best_name
+);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    // Shown by `!version`, so operators can tell exactly which build an instance runs
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", chrono::Utc::now().to_rfc3339());
+
+    let lock = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    let nostr_sdk_version = fs::read_to_string(lock).ok()
+        .and_then(|lock| locked_version(&lock, "nostr-sdk"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=NOSTR_SDK_VERSION={}", nostr_sdk_version);
+}
+
+/// The version of `package` pinned in a Cargo.lock
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name)?;
+    let version = lines.next()?.strip_prefix("version = \"")?.strip_suffix('"')?;
+    Some(version.to_string())
+}
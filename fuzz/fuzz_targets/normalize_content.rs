@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vecord::sanitize::{contains_bidi_control, normalize_content};
+
+// Normalized output must never contain a bidi control, whatever the input
+fuzz_target!(|data: &str| {
+    let normalized = normalize_content(data);
+    assert!(!contains_bidi_control(&normalized));
+});
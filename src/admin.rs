@@ -0,0 +1,482 @@
+use crate::archive::{ArchivedMessage, MessageArchive};
+use crate::message::{BridgeMessage, SystemEventType};
+use crate::metadata::MetadataCache;
+use crate::nostr::{pubkey_to_bech32_or_fail, relay_list_notice, RelayMonitor, SubscriberEvent, SubscriberList};
+use crate::pause::BridgePause;
+use crate::priority::PrioritySender;
+use anyhow::Result;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use base64::engine::{general_purpose, Engine};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{info, info_span, warn};
+use url::{Position, Url};
+use nostr_sdk::{Event, JsonUtil, Kind, TagKind, Timestamp};
+use vector_sdk::nostr::PublicKey;
+use vector_sdk::VectorBot;
+
+// Subscriber events queued for one admin session before it's considered gone
+const SESSION_EVENT_QUEUE: usize = 64;
+
+// How far a NIP-98 auth event may be from the server's clock, in either direction
+const AUTH_MAX_AGE_SECS: u64 = 60;
+
+// Results `/archive` returns by default, and at most
+const ARCHIVE_DEFAULT_LIMIT: usize = 50;
+const ARCHIVE_MAX_LIMIT: usize = 500;
+
+/// The parts of one running bridge the admin API reads from
+#[derive(Clone)]
+pub struct AdminBridge {
+    pub subscribers: SubscriberList,
+    pub metadata_cache: MetadataCache,
+    pub relay_monitor: RelayMonitor,
+    pub bridge_pause: BridgePause,
+    /// The bridge's current Nostr bot, for changes such as adding relays
+    pub bot: Option<Arc<RwLock<VectorBot>>>,
+    /// Sends bridge notices to the bridge's Nostr subscribers
+    pub notices: Option<PrioritySender<BridgeMessage>>,
+}
+
+/// Shared state of the admin API, covering every bridge in the process
+#[derive(Clone)]
+pub struct AdminState {
+    /// Keys whose NIP-98 signatures authorize requests
+    admin_pubkeys: Arc<Vec<PublicKey>>,
+    bridges: Arc<Vec<AdminBridge>>,
+    /// Searched by `/archive`, unset when archiving is disabled
+    archive: Option<MessageArchive>,
+}
+
+impl AdminState {
+    pub fn new(admin_pubkeys: Vec<PublicKey>, bridges: Vec<AdminBridge>) -> Self {
+        Self {
+            admin_pubkeys: Arc::new(admin_pubkeys),
+            bridges: Arc::new(bridges),
+            archive: None,
+        }
+    }
+
+    /// Serves searches of the message archive, when there is one
+    pub fn with_archive(mut self, archive: Option<MessageArchive>) -> Self {
+        self.archive = archive;
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct RelayHealth {
+    url: String,
+    connected: bool,
+    latency_ms: Option<u64>,
+    messages_sent: u64,
+    messages_failed: u64,
+}
+
+#[derive(Serialize)]
+struct SubscriberInfo {
+    pubkey: String,
+    name: Option<String>,
+    website: Option<String>,
+    joined_at: Option<u64>,
+    message_count: u64,
+}
+
+/// A failed request, answered with `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self(rejection.status(), rejection.body_text())
+    }
+}
+
+#[derive(Deserialize)]
+struct SubscriberRequest {
+    pubkey: String,
+}
+
+#[derive(Deserialize)]
+struct RelayRequest {
+    url: String,
+}
+
+/// Commands an admin session sends over `/admin/ws`
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum SessionCommand {
+    ListSubscribers,
+    RemoveSubscriber { pubkey: String },
+    Ban {
+        pubkey: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SessionAuth {
+    auth: Option<String>,
+}
+
+/// Builds the admin API, every route requiring a NIP-98 `Authorization: Nostr <base64 event>` from an admin key
+///
+/// Browsers can't set headers on a WebSocket upgrade, so `/admin/ws` takes the base64 event as `?auth=` instead,
+/// signed for the URL without the query.
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/admin/relays", get(relays))
+        .route("/admin/subscribers", get(subscribers))
+        .route("/subscribers", get(subscribers).post(add_subscriber))
+        .route("/subscribers/{pubkey}", delete(remove_subscriber))
+        .route("/relay", post(add_relay))
+        .route("/archive", get(search_archive))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        // Read-only and without subscriber details, so `vecord health-check` needs no key
+        .route("/health", get(health))
+        .route("/admin/ws", get(admin_ws))
+        .with_state(state)
+}
+
+/// Serves the admin API on `addr` until the process exits
+pub async fn serve(addr: &str, state: AdminState) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Admin API listening on {}", addr);
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+async fn require_auth(State(state): State<AdminState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let encoded = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Nostr "))
+        .ok_or_else(|| ApiError(StatusCode::UNAUTHORIZED, "Missing NIP-98 authorization".to_string()))?;
+    let path = request.uri().path_and_query().map_or("/", |path| path.as_str());
+    let url = request_url(request.headers(), path);
+    verify_http_auth(&state, encoded, request.method().as_str(), &url)
+        .map_err(|reason| ApiError(StatusCode::UNAUTHORIZED, reason.to_string()))?;
+    Ok(next.run(request).await)
+}
+
+/// The host and path a request was sent to, as the part of its URL after the scheme
+fn request_url(headers: &HeaderMap, path: &str) -> String {
+    let host = headers.get(header::HOST).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    format!("{}{}", host, path)
+}
+
+/// Checks a base64 NIP-98 event authorizes `method` on `url`, returning the admin key that signed it
+fn verify_http_auth(state: &AdminState, encoded: &str, method: &str, url: &str) -> Result<PublicKey, &'static str> {
+    let json = general_purpose::STANDARD.decode(encoded).map_err(|_| "Authorization is not valid base64")?;
+    let event = Event::from_json(json).map_err(|_| "Authorization is not a Nostr event")?;
+    event.verify().map_err(|_| "Authorization event has an invalid signature")?;
+    if event.kind != Kind::HttpAuth {
+        return Err("Authorization event is not a NIP-98 HTTP auth event");
+    }
+
+    // The scheme is ignored, as TLS is usually terminated by a proxy in front of the API
+    let authorized_url = event.tags.find(TagKind::u())
+        .and_then(|tag| tag.content())
+        .and_then(|u| Url::parse(u).ok());
+    if authorized_url.is_none_or(|authorized| &authorized[Position::BeforeHost..] != url) {
+        return Err("Authorization is for a different URL");
+    }
+
+    let authorized_method = event.tags.find(TagKind::Method).and_then(|tag| tag.content());
+    if !authorized_method.is_some_and(|authorized| authorized.eq_ignore_ascii_case(method)) {
+        return Err("Authorization is for a different method");
+    }
+
+    if Timestamp::now().as_u64().abs_diff(event.created_at.as_u64()) > AUTH_MAX_AGE_SECS {
+        return Err("Authorization event has expired");
+    }
+
+    if !state.admin_pubkeys.contains(&event.pubkey) {
+        return Err("Authorization is not signed by an admin key");
+    }
+    Ok(event.pubkey)
+}
+
+async fn relays(State(state): State<AdminState>) -> Json<Vec<RelayHealth>> {
+    let relays = state.bridges.iter()
+        .flat_map(|bridge| bridge.relay_monitor.snapshot())
+        .map(|(url, stats)| RelayHealth {
+            url,
+            connected: stats.connected,
+            latency_ms: stats.latency_ewma_ms.map(|ms| ms.round() as u64),
+            messages_sent: stats.messages_sent,
+            messages_failed: stats.messages_failed,
+        })
+        .collect();
+    Json(relays)
+}
+
+async fn subscribers(State(state): State<AdminState>) -> Json<Vec<SubscriberInfo>> {
+    Json(subscriber_infos(&state).await)
+}
+
+/// Every bridge's subscribers, named from the metadata cache where it has a profile
+async fn subscriber_infos(state: &AdminState) -> Vec<SubscriberInfo> {
+    let mut subscribers = Vec::new();
+    for bridge in state.bridges.iter() {
+        let infos = bridge.subscribers.get_all_with_info().await;
+        let pubkeys: Vec<PublicKey> = infos.iter().map(|(pubkey, _)| *pubkey).collect();
+        let profiles = bridge.metadata_cache.get_many(&pubkeys);
+
+        for ((pubkey, stats), profile) in infos.into_iter().zip(profiles) {
+            let npub = match pubkey_to_bech32_or_fail(&pubkey) {
+                Ok(npub) => npub,
+                Err(e) => {
+                    warn!("Leaving out a subscriber: {}", e);
+                    continue;
+                }
+            };
+            let profile = profile.as_ref();
+            let name = profile
+                .filter(|profile| profile.name.is_some() || profile.display_name.is_some())
+                .map(|profile| profile.get_best_name());
+            subscribers.push(SubscriberInfo {
+                pubkey: npub,
+                name,
+                website: profile.and_then(|profile| profile.website.clone()),
+                joined_at: stats.joined_at,
+                message_count: stats.message_count,
+            });
+        }
+    }
+    subscribers
+}
+
+fn parse_pubkey(pubkey: &str) -> Result<PublicKey, ApiError> {
+    PublicKey::parse(pubkey).map_err(|_| ApiError(StatusCode::BAD_REQUEST, format!("Invalid pubkey '{}'", pubkey)))
+}
+
+/// Subscribes a pubkey to every bridge, answering 201 if any bridge didn't have it yet
+async fn add_subscriber(
+    State(state): State<AdminState>,
+    body: Result<Json<SubscriberRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let Json(body) = body?;
+    let pubkey = parse_pubkey(&body.pubkey)?;
+
+    let mut added = false;
+    for bridge in state.bridges.iter() {
+        added |= bridge.subscribers.add(pubkey).await;
+    }
+    let status = if added { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(json!({ "ok": true, "added": added }))))
+}
+
+async fn remove_subscriber(State(state): State<AdminState>, Path(pubkey): Path<String>) -> Result<Json<Value>, ApiError> {
+    let pubkey = parse_pubkey(&pubkey)?;
+
+    let mut removed = false;
+    for bridge in state.bridges.iter() {
+        removed |= bridge.subscribers.remove(&pubkey).await;
+    }
+    if !removed {
+        return Err(ApiError(StatusCode::NOT_FOUND, "Not subscribed".to_string()));
+    }
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// Each bridge's subscriber count, pause state and relay connections, with the running version
+async fn health(State(state): State<AdminState>) -> Json<Value> {
+    let mut healthy = true;
+    let mut bridges = Vec::new();
+    for bridge in state.bridges.iter() {
+        let relays = bridge.relay_monitor.snapshot();
+        let connected = relays.iter().filter(|(_, stats)| stats.connected).count();
+        healthy &= connected > 0;
+        bridges.push(json!({
+            "subscribers": bridge.subscribers.count().await,
+            "paused": bridge.bridge_pause.is_paused(),
+            "relays_connected": connected,
+            "relays_total": relays.len(),
+        }));
+    }
+
+    let status = if healthy { "ok" } else { "degraded" };
+    Json(json!({ "status": status, "version": env!("CARGO_PKG_VERSION"), "bridges": bridges }))
+}
+
+/// Adds and connects a relay on every bridge until the next restart
+async fn add_relay(
+    State(state): State<AdminState>,
+    body: Result<Json<RelayRequest>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let Json(body) = body?;
+    if !body.url.starts_with("ws://") && !body.url.starts_with("wss://") {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Relay URL must start with ws:// or wss://".to_string()));
+    }
+
+    for bridge in state.bridges.iter() {
+        let Some(bot) = &bridge.bot else {
+            return Err(ApiError(StatusCode::SERVICE_UNAVAILABLE, "Nostr client is not running".to_string()));
+        };
+        let client = bot.read().await.client.clone();
+        client.add_relay(body.url.as_str()).await
+            .map_err(|e| ApiError(StatusCode::BAD_REQUEST, format!("Failed to add relay: {}", e)))?;
+        if let Err(e) = client.connect_relay(body.url.as_str()).await {
+            warn!("Failed to connect to relay {}: {}", body.url, e);
+        }
+    }
+    info!("Admin added relay {}", body.url);
+
+    // Subscribers whose clients cached the bridge's relays would otherwise miss the new one
+    for bridge in state.bridges.iter() {
+        let (Some(bot), Some(notices)) = (&bridge.bot, &bridge.notices) else {
+            continue;
+        };
+        let relays: Vec<String> = bot.read().await.client.relays().await
+            .into_keys()
+            .map(|url| url.to_string())
+            .collect();
+        let message = BridgeMessage::System {
+            event_type: SystemEventType::RelayListChanged,
+            message: relay_list_notice(&relays),
+            delivered: None,
+            span: info_span!("bridge_message", direction = "system", source_id = %body.url),
+        };
+        if let Err(e) = notices.send(message).await {
+            warn!("Failed to announce the relay list change: {}", e);
+        }
+    }
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// Full-text search of the archived messages, best matches first
+async fn search_archive(
+    State(state): State<AdminState>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Json<Vec<ArchivedMessage>>, ApiError> {
+    let archive = state.archive.as_ref()
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, "Message archiving is disabled".to_string()))?;
+    let limit = query.limit.unwrap_or(ARCHIVE_DEFAULT_LIMIT).min(ARCHIVE_MAX_LIMIT);
+    let messages = archive.search(&query.q, limit).await
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(messages))
+}
+
+async fn admin_ws(
+    State(state): State<AdminState>,
+    Query(auth): Query<SessionAuth>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let url = request_url(&headers, "/admin/ws");
+    let authorized = auth.auth.is_some_and(|encoded| verify_http_auth(&state, &encoded, "GET", &url).is_ok());
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    ws.on_upgrade(move |socket| admin_session(socket, state))
+}
+
+/// Answers one admin session's commands while pushing it every subscriber change
+async fn admin_session(socket: WebSocket, state: AdminState) {
+    let (mut sink, mut stream) = socket.split();
+
+    // Merge every bridge's subscriber changes into one queue for this session
+    let (event_sender, mut event_receiver) = mpsc::channel(SESSION_EVENT_QUEUE);
+    for bridge in state.bridges.iter() {
+        let mut events = bridge.subscribers.events();
+        let event_sender = event_sender.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let json = match event_json(&event) {
+                            Ok(json) => json,
+                            Err(e) => {
+                                warn!("Dropping a subscriber event: {}", e);
+                                continue;
+                            }
+                        };
+                        if event_sender.send(json).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => warn!("Admin session missed {} subscriber events", skipped),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    loop {
+        let reply = tokio::select! {
+            frame = stream.next() => match frame {
+                Some(Ok(Message::Text(text))) => handle_command(&state, text.as_str()).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            Some(event) = event_receiver.recv() => event,
+        };
+        if sink.send(Message::Text(reply.to_string().into())).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_command(state: &AdminState, text: &str) -> Value {
+    let command = match serde_json::from_str::<SessionCommand>(text) {
+        Ok(command) => command,
+        Err(e) => return json!({ "ok": false, "error": format!("Invalid command: {}", e) }),
+    };
+
+    match command {
+        SessionCommand::ListSubscribers => json!({ "subscribers": subscriber_infos(state).await }),
+        SessionCommand::RemoveSubscriber { pubkey } => {
+            let Ok(pubkey) = PublicKey::parse(&pubkey) else {
+                return json!({ "ok": false, "error": "Invalid pubkey" });
+            };
+            for bridge in state.bridges.iter() {
+                bridge.subscribers.remove(&pubkey).await;
+            }
+            json!({ "ok": true })
+        }
+        SessionCommand::Ban { pubkey, reason } => {
+            let Ok(pubkey) = PublicKey::parse(&pubkey) else {
+                return json!({ "ok": false, "error": "Invalid pubkey" });
+            };
+            let reason = reason.unwrap_or_else(|| "No reason given".to_string());
+            for bridge in state.bridges.iter() {
+                bridge.subscribers.ban(pubkey, &reason).await;
+            }
+            info!("Admin banned {}: {}", pubkey, reason);
+            json!({ "ok": true })
+        }
+    }
+}
+
+fn event_json(event: &SubscriberEvent) -> Result<Value> {
+    let (name, pubkey) = match event {
+        SubscriberEvent::Joined(pubkey) => ("subscriber_joined", pubkey),
+        SubscriberEvent::Left(pubkey) => ("subscriber_left", pubkey),
+    };
+    Ok(json!({ "event": name, "pubkey": pubkey_to_bech32_or_fail(pubkey)? }))
+}
@@ -0,0 +1,127 @@
+use crate::message::BridgeMessage;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        direction TEXT NOT NULL,
+        content TEXT NOT NULL,
+        author TEXT,
+        pubkey TEXT,
+        discord_message_id TEXT,
+        nostr_event_id TEXT,
+        created_at INTEGER,
+        bridged_at INTEGER NOT NULL
+    )",
+    // Indexes the text of `messages` without storing a second copy of it
+    "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(content, author, content='messages', content_rowid='id')",
+];
+
+/// One message from the archive
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ArchivedMessage {
+    pub id: i64,
+    /// `discord_to_nostr` or `nostr_to_discord`
+    pub direction: String,
+    pub content: String,
+    pub author: Option<String>,
+    /// The Nostr author's npub, for messages from Nostr
+    pub pubkey: Option<String>,
+    pub discord_message_id: Option<String>,
+    pub nostr_event_id: Option<String>,
+    /// When the author wrote it, as Unix seconds, where known
+    pub created_at: Option<i64>,
+    /// When the bridge delivered it, as Unix seconds
+    pub bridged_at: i64,
+}
+
+/// Every bridged message in a SQLite database, searchable through FTS5
+#[derive(Clone)]
+pub struct MessageArchive {
+    pool: SqlitePool,
+}
+
+impl MessageArchive {
+    /// Opens the archive at `path`, creating the database and its tables if needed
+    pub async fn open(path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await
+            .map_err(|e| anyhow!("Failed to open message archive {}: {}", path, e))?;
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        Ok(Self { pool })
+    }
+
+    /// Stores a message once it was delivered, with the ID of the Discord message posted for it if any
+    ///
+    /// Reactions and bridge notices aren't archived.
+    pub async fn insert(&self, message: &BridgeMessage, discord_message_id: Option<u64>) -> Result<()> {
+        let (direction, content, author, pubkey, nostr_event_id, created_at) = match message {
+            BridgeMessage::Discord { author, content, .. } => {
+                ("discord_to_nostr", content, Some(author.as_str()), None, None, None)
+            }
+            BridgeMessage::Nostr { content, metadata, .. } => (
+                "nostr_to_discord",
+                content,
+                Some(metadata.username.as_str()),
+                Some(metadata.pubkey.as_str()),
+                metadata.event_id.as_deref(),
+                metadata.event_created_at.map(|ts| ts as i64),
+            ),
+            BridgeMessage::DiscordReaction { .. }
+            | BridgeMessage::NostrReaction { .. }
+            | BridgeMessage::DeliveryReceipt { .. }
+            | BridgeMessage::Mention { .. }
+            | BridgeMessage::Redaction { .. }
+            | BridgeMessage::System { .. } => return Ok(()),
+        };
+        let bridged_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+
+        let mut transaction = self.pool.begin().await?;
+        let id = sqlx::query(
+            "INSERT INTO messages (direction, content, author, pubkey, discord_message_id, nostr_event_id, created_at, bridged_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+            .bind(direction)
+            .bind(content)
+            .bind(author)
+            .bind(pubkey)
+            .bind(discord_message_id.map(|id| id.to_string()))
+            .bind(nostr_event_id)
+            .bind(created_at)
+            .bind(bridged_at)
+            .execute(&mut *transaction)
+            .await?
+            .last_insert_rowid();
+        sqlx::query("INSERT INTO messages_fts (rowid, content, author) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(content)
+            .bind(author)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// The best matches for an FTS5 `query` over message text and authors, at most `limit` of them
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<ArchivedMessage>> {
+        let messages = sqlx::query_as::<_, ArchivedMessage>(
+            "SELECT messages.* FROM messages_fts
+             JOIN messages ON messages.id = messages_fts.rowid
+             WHERE messages_fts MATCH ?
+             ORDER BY messages_fts.rank
+             LIMIT ?",
+        )
+            .bind(query)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| anyhow!("Invalid search '{}': {}", query, e))?;
+        Ok(messages)
+    }
+}
@@ -1,61 +1,1249 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use dotenvy::dotenv;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+use vector_sdk::nostr::{FromBech32, PublicKey, SecretKey};
 
+use crate::crypto;
+use crate::filter::ContentFilter;
+use crate::nostr;
+use crate::time;
+
+const DEFAULT_SUBSCRIBE_MESSAGE: &str = "You are now subscribed to the Discord channel. You will receive all messages from the Discord channel. Send !unsubscribe to stop receiving messages.";
+const DEFAULT_UNSUBSCRIBE_MESSAGE: &str = "You have been unsubscribed from the Discord channel. You will no longer receive messages.";
+
+#[derive(Clone)]
 pub struct Config {
-    pub discord_token: String,
+    pub discord_token: Option<String>,
+    pub discord_webhook_url: Option<String>,
+    /// Post webhook messages under the Nostr sender's name and avatar rather than the webhook's own
+    pub webhook_use_sender_identity: bool,
+    pub discord_channel_id: u64,
+    /// Only messages from this guild are bridged, when set
+    pub discord_guild_id: Option<u64>,
+    /// Channel for bridge notices posted to Discord, the bridged channel when unset
+    pub discord_system_channel_id: Option<u64>,
+    pub bridge_membership_events: bool,
+    /// Carry reactions between Discord and Nostr for bridged Nostr messages
+    pub bridge_reactions: bool,
+    /// Post each Nostr sender's messages in their own thread of `discord_forum_channel_id`
+    pub discord_use_forum: bool,
+    pub discord_forum_channel_id: Option<u64>,
+    /// React to Discord messages with whether they reached every Nostr subscriber
+    pub delivery_receipt_reactions: bool,
+    /// Mention the Discord author of a bridged message when a subscriber replies to it
+    pub discord_mention_on_nostr_reply: bool,
+    /// Show Nostr senders' Lightning addresses on their bridged messages
+    pub show_lightning_addresses: bool,
+    /// Name the relay each Nostr message arrived from in its embed footer
+    pub show_relay_in_footer: bool,
+    /// Link Nostr senders' websites on their bridged messages
+    pub show_profile_website: bool,
+    pub fail_on_missing_permissions: bool,
+    /// Drop messages posted by any webhook, rather than only the bridge's own
+    pub discord_ignore_webhook_messages: bool,
+    /// The bridge's own Discord user, ignored even where Discord doesn't flag it as a bot
+    pub discord_bot_user_id: Option<u64>,
+    pub cache_avatars: bool,
+    pub avatar_cache_dir: String,
+    pub avatar_cache_ttl_secs: u64,
+    pub image_proxy_url: Option<String>,
+    pub circuit_breaker_threshold: u32,
+    pub circuit_breaker_buffer: usize,
+    pub circuit_breaker_timeout_secs: u64,
+    pub allowed_discord_role_ids: Vec<u64>,
+    pub nostr_private_key: String,
+    pub nostr_private_key_encrypted: Option<String>,
+    pub nostr_key_passphrase: Option<String>,
+    pub nostr_bunker_url: Option<String>,
+    pub nostr_bunker_secret: Option<String>,
+    pub nostr_bunker_pubkey: Option<String>,
+    /// Relays the bridge receives DMs from
+    pub nostr_read_relays: Vec<String>,
+    /// Relays the bridge publishes its events to
+    pub nostr_write_relays: Vec<String>,
+    /// Relays from `[[relays]]` or `NOSTR_RELAY_CONFIGS`, which replace the relay lists above when given
+    pub relay_configs: Vec<RelayConfig>,
+    /// Events per second shared by the write relays without a `max_msgs_per_sec` of their own
+    pub nostr_max_msgs_per_sec: Option<f64>,
+    /// Relays listed more than once, dropped while parsing and reported by `validate`
+    pub duplicate_relays: Vec<String>,
+    /// Fewest relays the bridge may run with
+    pub min_relay_count: usize,
+    /// Relays beyond which `validate` suggests pruning the list
+    pub max_relay_count: Option<usize>,
+    pub admin_pubkeys: Vec<String>,
+    /// Bot commands that may be run, without the `!` (empty enables every command)
+    pub enabled_commands: HashSet<String>,
+    pub silent_disabled_commands: bool,
+    pub allow_key_rotation: bool,
+    pub subscribers_file: Option<String>,
+    pub metadata_cache_file: Option<String>,
+    /// How often stale cached profiles are fetched again, 0 disables the refresh
+    pub metadata_refresh_interval_secs: u64,
+    pub subscriber_filters_file: Option<String>,
+    pub preferences_file: Option<String>,
+    /// Which forum thread belongs to each Nostr sender, kept across restarts
+    pub forum_thread_map_file: Option<String>,
+    /// SQLite database every bridged message is archived to, shared by all bridges
+    pub archive_file: Option<String>,
+    /// Discord usernames linked to Nostr users, shared by all bridges
+    pub username_map_file: Option<String>,
+    pub pause_buffer_size: usize,
+    pub allowed_nostr_event_kinds: Vec<u16>,
+    pub strict_giftwrap: bool,
+    pub support_nip04: bool,
+    pub min_pow_difficulty: Option<u8>,
+    pub nostr_subscriber_list_event_id: Option<String>,
+    pub sync_subscriber_list: bool,
+    pub spam_duplicate_threshold: u8,
+    pub spam_window_secs: u64,
+    pub spam_mute_secs: u64,
+    pub flood_threshold: usize,
+    pub flood_window_secs: u64,
+    pub flood_cooldown_secs: u64,
+    pub publish_profile: bool,
+    pub publish_relay_list: bool,
+    pub bot_name: String,
+    pub bot_description: String,
+    pub bot_picture_url: Option<String>,
+    pub bot_website: Option<String>,
+    pub log_file: Option<String>,
+    pub log_rotation: String,
+    /// IANA zone used when displaying times, such as "America/New_York"
+    pub timezone: String,
+    pub subscribe_message: String,
+    pub unsubscribe_message: String,
+    pub content_filter_patterns: Vec<String>,
+    pub notify_filtered_sender: bool,
+    /// Only Nostr messages containing this keyword, in any case, are bridged to Discord
+    pub nostr_content_must_contain: Option<String>,
+    pub federation_peers: Vec<String>,
+    pub federation_listen_addr: Option<String>,
+    /// Shared secret peers send as a bearer token, required by the federation listener
+    pub federation_token: Option<String>,
+    /// Where the admin HTTP API listens, disabled when unset
+    pub admin_listen_addr: Option<String>,
+    /// Port for the admin API on every interface, when no listen address is given
+    pub admin_api_port: Option<u16>,
+    /// Where the unauthenticated Prometheus metrics are served, disabled when unset
+    pub metrics_listen_addr: Option<String>,
+    /// Extra bridges from `[[bridges]]`, each overriding the fields above, see `bridge_configs`
+    pub bridges: Vec<BridgeConfig>,
+    /// This bridge's `[[bridges]]` name, which senders pass to `!subscribe-channel`
+    pub bridge_name: Option<String>,
+}
+
+/// One `[[relays]]` entry, a relay with its own publishing rate
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelayConfig {
+    pub url: String,
+    /// Events published to it per second, unlimited when unset
+    pub max_msgs_per_sec: Option<f64>,
+    /// Only read DMs from it, never publish to it
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// One `[[bridges]]` entry, a Discord channel bridged to its own Nostr identity
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeConfig {
+    /// Name senders pick the bridge by with `!subscribe-channel`
+    pub name: Option<String>,
     pub discord_channel_id: u64,
+    pub discord_webhook_url: Option<String>,
     pub nostr_private_key: String,
     pub nostr_relays: Vec<String>,
     pub subscribers_file: Option<String>,
     pub metadata_cache_file: Option<String>,
+    pub bot_name: Option<String>,
+    pub bot_description: Option<String>,
+    pub bot_picture_url: Option<String>,
+    pub bot_website: Option<String>,
+}
+
+/// Mirror of `Config` as read from a TOML file, every field is optional and
+/// falls back to its environment variable when missing
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    discord: DiscordSection,
+    nostr: NostrSection,
+    profile: ProfileSection,
+    filter: FilterSection,
+    federation: FederationSection,
+    admin: AdminSection,
+    metrics: MetricsSection,
+    subscribers_file: Option<String>,
+    metadata_cache_file: Option<String>,
+    metadata_refresh_interval_secs: Option<u64>,
+    log_file: Option<String>,
+    log_rotation: Option<String>,
+    timezone: Option<String>,
+    messages: MessagesSection,
+    subscriber_filters_file: Option<String>,
+    preferences_file: Option<String>,
+    forum_thread_map_file: Option<String>,
+    archive_file: Option<String>,
+    username_map_file: Option<String>,
+    pause_buffer_size: Option<usize>,
+    relays: Vec<RelayConfig>,
+    bridges: Vec<BridgeConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiscordSection {
+    token: Option<String>,
+    webhook_url: Option<String>,
+    webhook_use_sender_identity: Option<bool>,
+    channel_id: Option<u64>,
+    guild_id: Option<u64>,
+    system_channel_id: Option<u64>,
+    bridge_membership_events: Option<bool>,
+    bridge_reactions: Option<bool>,
+    use_forum: Option<bool>,
+    forum_channel_id: Option<u64>,
+    show_lightning_addresses: Option<bool>,
+    show_relay_in_footer: Option<bool>,
+    show_profile_website: Option<bool>,
+    mention_on_nostr_reply: Option<bool>,
+    delivery_receipt_reactions: Option<bool>,
+    fail_on_missing_permissions: Option<bool>,
+    ignore_webhook_messages: Option<bool>,
+    bot_user_id: Option<u64>,
+    allowed_role_ids: Option<Vec<u64>>,
+    cache_avatars: Option<bool>,
+    avatar_cache_dir: Option<String>,
+    avatar_cache_ttl_secs: Option<u64>,
+    image_proxy_url: Option<String>,
+    circuit_breaker_threshold: Option<u32>,
+    circuit_breaker_buffer: Option<usize>,
+    circuit_breaker_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NostrSection {
+    private_key: Option<String>,
+    private_key_encrypted: Option<String>,
+    key_passphrase: Option<String>,
+    bunker_url: Option<String>,
+    bunker_secret: Option<String>,
+    bunker_pubkey: Option<String>,
+    relays: Option<Vec<String>>,
+    read_relays: Option<Vec<String>>,
+    write_relays: Option<Vec<String>>,
+    max_msgs_per_sec: Option<f64>,
+    min_relay_count: Option<usize>,
+    max_relay_count: Option<usize>,
+    admin_pubkeys: Option<Vec<String>>,
+    enabled_commands: Option<Vec<String>>,
+    silent_disabled_commands: Option<bool>,
+    allow_key_rotation: Option<bool>,
+    allowed_kinds: Option<Vec<u16>>,
+    strict_giftwrap: Option<bool>,
+    support_nip04: Option<bool>,
+    min_pow_difficulty: Option<u8>,
+    subscriber_list_event_id: Option<String>,
+    sync_subscriber_list: Option<bool>,
+    spam_duplicate_threshold: Option<u8>,
+    spam_window_secs: Option<u64>,
+    spam_mute_secs: Option<u64>,
+    flood_threshold: Option<usize>,
+    flood_window_secs: Option<u64>,
+    flood_cooldown_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProfileSection {
+    publish: Option<bool>,
+    publish_relay_list: Option<bool>,
+    name: Option<String>,
+    description: Option<String>,
+    picture_url: Option<String>,
+    website: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MessagesSection {
+    subscribe: Option<String>,
+    unsubscribe: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FilterSection {
+    content_patterns: Option<Vec<String>>,
+    notify_sender: Option<bool>,
+    nostr_must_contain: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FederationSection {
+    peers: Option<Vec<String>>,
+    listen_addr: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AdminSection {
+    listen_addr: Option<String>,
+    api_port: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MetricsSection {
+    listen_addr: Option<String>,
+}
+
+// How long to wait for the remote config at `VECORD_CONFIG_URL`
+const CONFIG_URL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A remote config, keyed by the environment variables it stands in for
+///
+/// Values may be strings, numbers, booleans or arrays, which are joined into comma-separated lists.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+struct ConfigJson {
+    vars: HashMap<String, Value>,
+}
+
+impl ConfigJson {
+    /// The variables as the strings they would have in the environment
+    fn into_vars(self) -> HashMap<String, String> {
+        self.vars.into_iter()
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    Value::Null => return None,
+                    Value::String(value) => value,
+                    Value::Array(values) => values.iter()
+                        .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    value => value.to_string(),
+                };
+                Some((name, value))
+            })
+            .collect()
+    }
+}
+
+/// Where settings missing from the config file are looked up: the environment, then a remote config
+///
+/// Remote values are only held here, never written to the process environment where child processes
+/// and `/proc` would see the secrets they carry.
+#[derive(Debug, Default)]
+struct EnvVars {
+    remote: HashMap<String, String>,
+}
+
+impl EnvVars {
+    fn get(&self, name: &str) -> Option<String> {
+        env::var(name).ok().or_else(|| self.remote.get(name).cloned())
+    }
 }
 
 impl Config {
-    pub fn new() -> Result<Self> {
+    pub async fn new() -> Result<Self> {
         // Load environment variables from .env file
         dotenv().ok();
         
-        let discord_token = env::var("DISCORD_TOKEN")
-            .expect("Expected DISCORD_TOKEN in the environment");
+        // Fleets can share one remote config, still overridden by the local environment
+        if let Ok(url) = env::var("VECORD_CONFIG_URL") {
+            return Self::from_url(&url, CONFIG_URL_TIMEOUT).await;
+        }
         
-        let discord_channel_id = env::var("DISCORD_CHANNEL_ID")
-            .expect("Expected DISCORD_CHANNEL_ID in the environment")
-            .parse::<u64>()
-            .expect("DISCORD_CHANNEL_ID must be a valid u64");
+        Self::from_file(ConfigFile::default(), &EnvVars::default())
+    }
+
+    /// Builds a config from a JSON object of environment variables served at `url`
+    ///
+    /// Variables already set locally win over the remote ones. The JSON is only ever held in memory,
+    /// as it usually carries the bridge's secrets, so the server may require `VECORD_CONFIG_TOKEN` as a bearer token.
+    pub async fn from_url(url: &str, timeout: Duration) -> Result<Self> {
+        let token = env::var("VECORD_CONFIG_TOKEN").ok();
+        let client = reqwest::Client::new();
+        let fetch = || {
+            let request = client.get(url).timeout(timeout);
+            match &token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+            .send()
+        };
+        let response = match fetch().await {
+            Err(e) if e.is_timeout() => fetch().await,
+            response => response,
+        };
+        let remote: ConfigJson = response
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow!("Failed to fetch config from {}: {}", url, e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse config from {}: {}", url, e))?;
         
-        let nostr_private_key = env::var("NOSTR_PRIVATE_KEY")
-            .expect("Expected NOSTR_PRIVATE_KEY in the environment");
+        Self::from_file(ConfigFile::default(), &EnvVars { remote: remote.into_vars() })
+    }
+
+    /// Builds a config from a TOML file, filling missing fields from the environment
+    pub fn from_toml(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config file {}: {}", path.display(), e))?;
         
-        // Parse comma-separated list of relays
-        let nostr_relays_str = env::var("NOSTR_RELAYS")
-            .expect("Expected NOSTR_RELAYS in the environment");
+        Self::from_toml_str(&contents)
+    }
+
+    /// Builds a config from a TOML string, filling missing fields from the environment
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(toml)
+            .map_err(|e| anyhow!("Failed to parse TOML config: {}", e))?;
         
-        let nostr_relays = nostr_relays_str
-            .split(',')
-            .map(|s| s.trim().to_string())
+        Self::from_file(file, &EnvVars::default())
+    }
+
+    /// A config with safe dummy values for tests that don't touch the network
+    pub fn default_for_testing() -> Self {
+        Self {
+            discord_token: Some("test-token".to_string()),
+            discord_webhook_url: None,
+            webhook_use_sender_identity: true,
+            discord_channel_id: 1,
+            discord_guild_id: None,
+            discord_system_channel_id: None,
+            bridge_membership_events: false,
+            bridge_reactions: false,
+            discord_use_forum: false,
+            discord_forum_channel_id: None,
+            show_lightning_addresses: false,
+            show_relay_in_footer: false,
+            show_profile_website: false,
+            discord_mention_on_nostr_reply: false,
+            delivery_receipt_reactions: false,
+            fail_on_missing_permissions: false,
+            discord_ignore_webhook_messages: true,
+            discord_bot_user_id: None,
+            cache_avatars: false,
+            avatar_cache_dir: default_avatar_cache_dir(),
+            avatar_cache_ttl_secs: 86_400,
+            image_proxy_url: None,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_buffer: 50,
+            circuit_breaker_timeout_secs: 30,
+            allowed_discord_role_ids: Vec::new(),
+            nostr_private_key: "0000000000000000000000000000000000000000000000000000000000000001".to_string(),
+            nostr_private_key_encrypted: None,
+            nostr_key_passphrase: None,
+            nostr_bunker_url: None,
+            nostr_bunker_secret: None,
+            nostr_bunker_pubkey: None,
+            nostr_read_relays: vec!["ws://127.0.0.1:0".to_string()],
+            nostr_write_relays: vec!["ws://127.0.0.1:0".to_string()],
+            relay_configs: Vec::new(),
+            nostr_max_msgs_per_sec: None,
+            duplicate_relays: Vec::new(),
+            min_relay_count: 1,
+            max_relay_count: None,
+            admin_pubkeys: Vec::new(),
+            enabled_commands: HashSet::new(),
+            silent_disabled_commands: false,
+            allow_key_rotation: false,
+            subscribers_file: None,
+            metadata_cache_file: None,
+            metadata_refresh_interval_secs: 3600,
+            subscriber_filters_file: None,
+            preferences_file: None,
+            forum_thread_map_file: None,
+            archive_file: None,
+            username_map_file: None,
+            pause_buffer_size: 200,
+            allowed_nostr_event_kinds: vec![14],
+            strict_giftwrap: false,
+            support_nip04: false,
+            min_pow_difficulty: None,
+            nostr_subscriber_list_event_id: None,
+            sync_subscriber_list: false,
+            spam_duplicate_threshold: 3,
+            spam_window_secs: 60,
+            spam_mute_secs: 300,
+            flood_threshold: 10,
+            flood_window_secs: 10,
+            flood_cooldown_secs: 120,
+            publish_profile: false,
+            publish_relay_list: false,
+            bot_name: "Vecord".to_string(),
+            bot_description: String::new(),
+            bot_picture_url: None,
+            bot_website: None,
+            log_file: None,
+            log_rotation: "daily".to_string(),
+            timezone: "UTC".to_string(),
+            subscribe_message: DEFAULT_SUBSCRIBE_MESSAGE.to_string(),
+            unsubscribe_message: DEFAULT_UNSUBSCRIBE_MESSAGE.to_string(),
+            content_filter_patterns: Vec::new(),
+            notify_filtered_sender: false,
+            nostr_content_must_contain: None,
+            federation_peers: Vec::new(),
+            federation_listen_addr: None,
+            federation_token: None,
+            admin_listen_addr: None,
+            admin_api_port: None,
+            metrics_listen_addr: None,
+            bridges: Vec::new(),
+            bridge_name: None,
+        }
+    }
+
+    /// Every configured relay once, whether it is read from, written to or both
+    pub fn nostr_relays(&self) -> Vec<String> {
+        let mut relays = self.nostr_read_relays.clone();
+        for relay in &self.nostr_write_relays {
+            if !relays.contains(relay) {
+                relays.push(relay.clone());
+            }
+        }
+        relays
+    }
+
+    /// Where the admin API listens, preferring an explicit address over the bare port
+    pub fn admin_addr(&self) -> Option<String> {
+        self.admin_listen_addr.clone()
+            .or_else(|| self.admin_api_port.map(|port| format!("0.0.0.0:{}", port)))
+    }
+
+    /// The config of every bridge to run: one per `[[bridges]]` entry, or just this one without any
+    pub fn bridge_configs(&self) -> Vec<Config> {
+        if self.bridges.is_empty() {
+            return vec![self.clone()];
+        }
+
+        self.bridges.iter().map(|bridge| {
+            let mut config = self.clone();
+            config.bridges = Vec::new();
+            config.bridge_name = bridge.name.clone();
+            config.duplicate_relays = Vec::new();
+            config.relay_configs.retain(|relay| bridge.nostr_relays.contains(&relay.url));
+            config.discord_channel_id = bridge.discord_channel_id;
+            config.discord_webhook_url = bridge.discord_webhook_url.clone().or(config.discord_webhook_url);
+            config.nostr_private_key = bridge.nostr_private_key.clone();
+            config.nostr_read_relays = bridge.nostr_relays.clone();
+            config.nostr_write_relays = bridge.nostr_relays.clone();
+
+            // Each bridge keeps its state beside its own subscribers file
+            config.subscribers_file = bridge.subscribers_file.clone();
+            config.metadata_cache_file = bridge.metadata_cache_file.clone()
+                .or_else(|| sibling_file(&config.subscribers_file, "metadata_cache.json"));
+            config.subscriber_filters_file = sibling_file(&config.subscribers_file, "subscriber_filters.json");
+            config.preferences_file = sibling_file(&config.subscribers_file, "preferences.json");
+            config.forum_thread_map_file = sibling_file(&config.subscribers_file, "forum_threads.json");
+
+            if let Some(name) = &bridge.bot_name {
+                config.bot_name = name.clone();
+            }
+            if let Some(description) = &bridge.bot_description {
+                config.bot_description = description.clone();
+            }
+            config.bot_picture_url = bridge.bot_picture_url.clone().or(config.bot_picture_url);
+            config.bot_website = bridge.bot_website.clone().or(config.bot_website);
+            config
+        }).collect()
+    }
+
+    /// Checks the config for invalid values, returning any non-fatal warnings
+    pub fn validate(&self) -> Result<Vec<String>> {
+        let mut warnings: Vec<String> = self.duplicate_relays.iter()
+            .map(|relay| format!("Ignoring duplicate relay {}", relay))
+            .collect();
+        
+        if !self.bridges.is_empty() {
+            let mut channel_ids = std::collections::HashSet::new();
+            let mut names = std::collections::HashSet::new();
+            for bridge in self.bridge_configs() {
+                if !channel_ids.insert(bridge.discord_channel_id) {
+                    return Err(anyhow!("Discord channel {} is used by more than one bridge", bridge.discord_channel_id));
+                }
+                if let Some(name) = &bridge.bridge_name {
+                    if name.is_empty() || name.contains(char::is_whitespace) {
+                        return Err(anyhow!("Bridge name '{}' must be a single word", name));
+                    }
+                    if !names.insert(name.clone()) {
+                        return Err(anyhow!("Bridge name '{}' is used by more than one bridge", name));
+                    }
+                }
+                warnings.extend(bridge.validate()?);
+            }
+            return Ok(warnings);
+        }
+        
+        if self.discord_channel_id == 0 {
+            return Err(anyhow!("DISCORD_CHANNEL_ID must not be zero"));
+        }
+        
+        if self.discord_guild_id == Some(0) {
+            return Err(anyhow!("DISCORD_GUILD_ID must not be zero"));
+        }
+        
+        if self.discord_system_channel_id == Some(0) {
+            return Err(anyhow!("DISCORD_SYSTEM_CHANNEL_ID must not be zero"));
+        }
+        
+        if self.discord_forum_channel_id == Some(0) {
+            return Err(anyhow!("DISCORD_FORUM_CHANNEL_ID must not be zero"));
+        }
+        
+        if self.discord_bot_user_id == Some(0) {
+            return Err(anyhow!("DISCORD_BOT_USER_ID must not be zero"));
+        }
+        
+        if self.discord_use_forum {
+            if self.discord_forum_channel_id.is_none() {
+                return Err(anyhow!("DISCORD_FORUM_CHANNEL_ID is required when DISCORD_USE_FORUM is set"));
+            }
+            if self.discord_webhook_url.is_some() {
+                warnings.push("DISCORD_USE_FORUM is ignored while sending through DISCORD_WEBHOOK_URL".to_string());
+            }
+        }
+        
+        if self.discord_token.is_none() {
+            warnings.push("No DISCORD_TOKEN set, messages from Discord will not be bridged".to_string());
+        }
+        
+        if self.nostr_bunker_url.is_some() {
+            match &self.nostr_bunker_pubkey {
+                Some(pubkey) if PublicKey::parse(pubkey).is_ok() => {}
+                Some(pubkey) => return Err(anyhow!("NOSTR_BUNKER_PUBKEY {} is not a valid npub or hex pubkey", pubkey)),
+                None => return Err(anyhow!("NOSTR_BUNKER_PUBKEY is required when NOSTR_BUNKER_URL is set")),
+            }
+        }
+        
+        // With a bunker the private key is optional, it only authenticates the bridge to the bunker
+        let needs_private_key = self.nostr_bunker_url.is_none() || !self.nostr_private_key.is_empty();
+        if needs_private_key {
+            parse_secret_key(&self.nostr_private_key)
+                .map_err(|e| anyhow!("NOSTR_PRIVATE_KEY is not a valid hex or nsec key: {}", e))?;
+        }
+        
+        if self.nostr_read_relays.is_empty() {
+            return Err(anyhow!("NOSTR_READ_RELAYS (or NOSTR_RELAYS) must contain at least one relay"));
+        }
+        
+        if self.nostr_write_relays.is_empty() {
+            return Err(anyhow!("NOSTR_WRITE_RELAYS (or NOSTR_RELAYS) must contain at least one relay"));
+        }
+        
+        let relays = self.nostr_relays();
+        for relay in &relays {
+            if !relay.starts_with("ws://") && !relay.starts_with("wss://") {
+                return Err(anyhow!("Relay {} must be a ws:// or wss:// URL", relay));
+            }
+        }
+        
+        if relays.len() < self.min_relay_count {
+            return Err(ConfigError::InvalidValue {
+                name: "NOSTR_RELAYS".to_string(),
+                value: relays.join(","),
+                reason: format!("At least {} relay(s) required", self.min_relay_count),
+            }.into());
+        }
+        
+        if let Some(max) = self.max_relay_count.filter(|max| relays.len() > *max) {
+            warnings.push(format!("{} relays are configured, more than MAX_RELAY_COUNT ({}), consider pruning the list", relays.len(), max));
+        }
+        
+        for relay in &self.relay_configs {
+            if relay.max_msgs_per_sec.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+                return Err(anyhow!("max_msgs_per_sec of relay {} must be a positive number", relay.url));
+            }
+        }
+        
+        if self.nostr_max_msgs_per_sec.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+            return Err(anyhow!("NOSTR_MAX_MSGS_PER_SEC must be a positive number"));
+        }
+        
+        for admin in &self.admin_pubkeys {
+            if PublicKey::parse(admin).is_err() {
+                return Err(anyhow!("ADMIN_PUBKEYS entry {} is not a valid npub or hex pubkey", admin));
+            }
+        }
+        
+        for command in &self.enabled_commands {
+            if !nostr::is_command(command) {
+                warnings.push(format!("ENABLED_COMMANDS entry '{}' is not a bot command", command));
+            }
+        }
+        
+        if !matches!(self.log_rotation.as_str(), "daily" | "hourly" | "never") {
+            return Err(anyhow!("LOG_ROTATION must be daily, hourly or never, got '{}'", self.log_rotation));
+        }
+        
+        time::parse_timezone(&self.timezone)?;
+        
+        ContentFilter::new(&self.content_filter_patterns)?;
+        
+        for peer in &self.federation_peers {
+            if !peer.starts_with("ws://") && !peer.starts_with("wss://") {
+                return Err(anyhow!("Federation peer {} must be a ws:// or wss:// URL", peer));
+            }
+        }
+        
+        if (self.federation_listen_addr.is_some() || !self.federation_peers.is_empty()) && self.federation_token.is_none() {
+            return Err(anyhow!("FEDERATION_TOKEN is required for federation, peers authenticate to each other's listeners with it"));
+        }
+        
+        if self.admin_addr().is_some() && self.admin_pubkeys.is_empty() {
+            return Err(anyhow!("ADMIN_PUBKEYS is required when the admin API is enabled, its requests are signed by those keys"));
+        }
+        
+        if !self.allowed_nostr_event_kinds.contains(&14) {
+            warnings.push("NOSTR_ALLOWED_KINDS does not include 14, NIP-17 DMs and commands will be ignored".to_string());
+        }
+        
+        Ok(warnings)
+    }
+
+    fn from_file(file: ConfigFile, vars: &EnvVars) -> Result<Self> {
+        // A bot token is needed to listen on the gateway, a webhook can replace it for sending
+        let discord_token = string_var(vars, file.discord.token, "DISCORD_TOKEN");
+        let discord_webhook_url = string_var(vars, file.discord.webhook_url, "DISCORD_WEBHOOK_URL");
+        if discord_token.is_none() && discord_webhook_url.is_none() {
+            return Err(ConfigError::MissingRequired("DISCORD_TOKEN".to_string()).into());
+        }
+        
+        // Webhook messages appear to come from the Nostr sender themselves
+        let webhook_use_sender_identity = bool_var(vars, file.discord.webhook_use_sender_identity, "WEBHOOK_USE_SENDER_IDENTITY")?.unwrap_or(true);
+        
+        // With `[[bridges]]` the top-level bridge settings may be left out, so borrow the first entry's
+        let first_bridge = file.bridges.first();
+        
+        let discord_channel_id = parse_var(vars, file.discord.channel_id, "DISCORD_CHANNEL_ID")?
+            .or(first_bridge.map(|b| b.discord_channel_id))
+            .ok_or_else(|| ConfigError::MissingRequired("DISCORD_CHANNEL_ID".to_string()))?;
+        
+        // Guards against a channel ID that is reused or guessed in another server
+        let discord_guild_id = parse_var(vars, file.discord.guild_id, "DISCORD_GUILD_ID")?;
+        let discord_system_channel_id = parse_var(vars, file.discord.system_channel_id, "DISCORD_SYSTEM_CHANNEL_ID")?;
+        
+        // Tell Nostr subscribers when members join or leave (needs the privileged Server Members intent)
+        let bridge_membership_events = bool_var(vars, file.discord.bridge_membership_events, "BRIDGE_MEMBERSHIP_EVENTS")?.unwrap_or(false);
+        
+        // Send Discord reactions on bridged Nostr messages to their authors as NIP-25 reactions
+        let bridge_reactions = bool_var(vars, file.discord.bridge_reactions, "BRIDGE_REACTIONS")?.unwrap_or(false);
+        
+        // Give every Nostr sender a thread of their own in a forum channel
+        let discord_use_forum = bool_var(vars, file.discord.use_forum, "DISCORD_USE_FORUM")?.unwrap_or(false);
+        let discord_forum_channel_id = parse_var(vars, file.discord.forum_channel_id, "DISCORD_FORUM_CHANNEL_ID")?;
+        
+        // Show Discord authors whether their message reached the Nostr subscribers
+        let delivery_receipt_reactions = bool_var(vars, file.discord.delivery_receipt_reactions, "DELIVERY_RECEIPT_REACTIONS")?.unwrap_or(false);
+        
+        // Notify Discord authors when a Nostr subscriber replies to their message
+        let discord_mention_on_nostr_reply = bool_var(vars, file.discord.mention_on_nostr_reply, "DISCORD_MENTION_ON_NOSTR_REPLY")?.unwrap_or(false);
+        
+        // Invite tips by showing the sender's Lightning address under their message
+        let show_lightning_addresses = bool_var(vars, file.discord.show_lightning_addresses, "SHOW_LIGHTNING_ADDRESSES")?.unwrap_or(false);
+        
+        // Credit the relay a message came through, for provenance, at the cost of a longer footer
+        let show_relay_in_footer = bool_var(vars, file.discord.show_relay_in_footer, "SHOW_RELAY_IN_FOOTER")?.unwrap_or(false);
+        
+        // Link the sender's website from their profile under their message
+        let show_profile_website = bool_var(vars, file.discord.show_profile_website, "SHOW_PROFILE_WEBSITE")?.unwrap_or(false);
+        
+        // Stop calling Discord after repeated failures, buffering messages until it recovers
+        let circuit_breaker_threshold = parse_var(vars, file.discord.circuit_breaker_threshold, "CIRCUIT_BREAKER_THRESHOLD")?.unwrap_or(5);
+        let circuit_breaker_buffer = parse_var(vars, file.discord.circuit_breaker_buffer, "CIRCUIT_BREAKER_BUFFER")?.unwrap_or(50);
+        let circuit_breaker_timeout_secs = parse_var(vars, file.discord.circuit_breaker_timeout_secs, "CIRCUIT_BREAKER_TIMEOUT_SECS")?.unwrap_or(30);
+        
+        // Exit on startup if the bot can't view, post or embed in the channel
+        let fail_on_missing_permissions = bool_var(vars, file.discord.fail_on_missing_permissions, "FAIL_ON_MISSING_PERMISSIONS")?.unwrap_or(false);
+        
+        // Webhook posts may be the bridge's own Nostr messages, so they aren't bridged unless allowed
+        let discord_ignore_webhook_messages = bool_var(vars, file.discord.ignore_webhook_messages, "DISCORD_IGNORE_WEBHOOK_MESSAGES")?.unwrap_or(true);
+        
+        // Guards against loops from Discord apps whose messages aren't flagged as a bot's
+        let discord_bot_user_id = parse_var(vars, file.discord.bot_user_id, "DISCORD_BOT_USER_ID")?;
+        
+        // Serve Nostr avatars to Discord from a local cache instead of their original URLs
+        let cache_avatars = bool_var(vars, file.discord.cache_avatars, "CACHE_AVATARS")?.unwrap_or(false);
+        let avatar_cache_dir = string_var(vars, file.discord.avatar_cache_dir, "AVATAR_CACHE_DIR")
+            .unwrap_or_else(default_avatar_cache_dir);
+        let avatar_cache_ttl_secs = parse_var(vars, file.discord.avatar_cache_ttl_secs, "AVATAR_CACHE_TTL_SECS")?.unwrap_or(86_400);
+        
+        // Prefix for Nostr image URLs so Discord fetches them through an operator-controlled proxy
+        let image_proxy_url = string_var(vars, file.discord.image_proxy_url, "IMAGE_PROXY_URL")
+            .filter(|url| !url.trim().is_empty());
+        
+        // Only members holding one of these roles are bridged (empty allows everyone)
+        let allowed_discord_role_ids = list_var(vars, file.discord.allowed_role_ids, "ALLOWED_DISCORD_ROLE_IDS")?
+            .unwrap_or_default();
+        
+        // Sign through a NIP-46 bunker, the bunker pubkey is the bridge's identity
+        let nostr_bunker_url = string_var(vars, file.nostr.bunker_url, "NOSTR_BUNKER_URL");
+        let nostr_bunker_secret = string_var(vars, file.nostr.bunker_secret, "NOSTR_BUNKER_SECRET");
+        let nostr_bunker_pubkey = string_var(vars, file.nostr.bunker_pubkey, "NOSTR_BUNKER_PUBKEY");
+        
+        // An encrypted key (from `vecord encrypt-key`) replaces the plain one and is only decrypted in memory
+        let nostr_private_key_encrypted = string_var(vars, file.nostr.private_key_encrypted, "NOSTR_PRIVATE_KEY_ENCRYPTED");
+        let nostr_key_passphrase = string_var(vars, file.nostr.key_passphrase, "NOSTR_KEY_PASSPHRASE");
+        let nostr_private_key_plain = string_var(vars, file.nostr.private_key, "NOSTR_PRIVATE_KEY");
+        
+        let nostr_private_key = match &nostr_private_key_encrypted {
+            Some(_) if nostr_private_key_plain.is_some() => {
+                return Err(ConfigError::Incompatible("NOSTR_PRIVATE_KEY".into(), "NOSTR_PRIVATE_KEY_ENCRYPTED".into()).into());
+            }
+            Some(encrypted) => {
+                let passphrase = nostr_key_passphrase.as_deref()
+                    .ok_or_else(|| ConfigError::MissingRequired("NOSTR_KEY_PASSPHRASE".to_string()))?;
+                crypto::decrypt_private_key(encrypted, passphrase)?
+            }
+            None => nostr_private_key_plain
+                .or(first_bridge.map(|b| b.nostr_private_key.clone()))
+                .or_else(|| nostr_bunker_url.as_ref().map(|_| String::new()))
+                .ok_or_else(|| ConfigError::MissingRequired("NOSTR_PRIVATE_KEY".to_string()))?,
+        };
+        // Catch a malformed key here rather than when the Nostr client starts, the bunker setup may leave it empty
+        if !nostr_private_key.is_empty() {
+            parse_secret_key(&nostr_private_key)?;
+        }
+        
+        // Relays with their own rate limits, from a TOML array or a JSON string
+        let mut relay_configs = if !file.relays.is_empty() {
+            file.relays
+        } else if let Some(json) = vars.get("NOSTR_RELAY_CONFIGS") {
+            serde_json::from_str::<Vec<RelayConfig>>(&json)
+                .map_err(|e| ConfigError::ParseError {
+                    field: "NOSTR_RELAY_CONFIGS".to_string(),
+                    value: json.clone(),
+                    reason: format!("not a valid JSON array of relays: {}", e),
+                })?
+        } else {
+            Vec::new()
+        };
+        for relay in &mut relay_configs {
+            relay.url = normalize_relay("NOSTR_RELAY_CONFIGS", &relay.url)?;
+        }
+        
+        // Parse comma-separated lists of relays, separate read and write lists override the shared one
+        let (nostr_read_relays, nostr_write_relays) = if relay_configs.is_empty() {
+            let nostr_relays = list_var(vars, file.nostr.relays, "NOSTR_RELAYS")?
+                .or(first_bridge.map(|b| b.nostr_relays.clone()));
+            let nostr_read_relays = list_var(vars, file.nostr.read_relays, "NOSTR_READ_RELAYS")?
+                .or(nostr_relays.clone())
+                .ok_or_else(|| ConfigError::MissingRequired("NOSTR_RELAYS".to_string()))?;
+            let nostr_write_relays = list_var(vars, file.nostr.write_relays, "NOSTR_WRITE_RELAYS")?
+                .or(nostr_relays)
+                .ok_or_else(|| ConfigError::MissingRequired("NOSTR_RELAYS".to_string()))?;
+            (nostr_read_relays, nostr_write_relays)
+        } else {
+            // DMs are read from every configured relay, but only published to those that aren't read-only
+            let nostr_write_relays = relay_configs.iter()
+                .filter(|relay| !relay.read_only)
+                .map(|relay| relay.url.clone())
+                .collect();
+            (relay_configs.iter().map(|relay| relay.url.clone()).collect(), nostr_write_relays)
+        };
+        // Relays without a rate of their own are paced together
+        let nostr_max_msgs_per_sec = parse_var(vars, file.nostr.max_msgs_per_sec, "NOSTR_MAX_MSGS_PER_SEC")?;
+        
+        // The same relay listed twice would deliver every event twice
+        let (nostr_read_relays, mut duplicate_relays) = normalize_relays("NOSTR_READ_RELAYS", nostr_read_relays)?;
+        let (nostr_write_relays, duplicates) = normalize_relays("NOSTR_WRITE_RELAYS", nostr_write_relays)?;
+        duplicate_relays.extend(duplicates);
+        
+        // Guard against running with too few relays, or far more than needed
+        let min_relay_count = parse_var(vars, file.nostr.min_relay_count, "MIN_RELAY_COUNT")?.unwrap_or(1);
+        let max_relay_count = parse_var(vars, file.nostr.max_relay_count, "MAX_RELAY_COUNT")?;
+        
+        // Nostr users allowed to run admin commands
+        let admin_pubkeys = list_var(vars, file.nostr.admin_pubkeys, "ADMIN_PUBKEYS")?.unwrap_or_default();
+        
+        // Restrict the bot to these commands, replying to (or with SILENT_DISABLED_COMMANDS, ignoring) the rest
+        let enabled_commands = list_var(vars, file.nostr.enabled_commands, "ENABLED_COMMANDS")?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name: String| name.trim_start_matches('!').to_lowercase())
             .collect();
+        let silent_disabled_commands = bool_var(vars, file.nostr.silent_disabled_commands, "SILENT_DISABLED_COMMANDS")?.unwrap_or(false);
+        
+        // Admins may swap the bridge's Nostr identity at runtime only when explicitly enabled
+        let allow_key_rotation = bool_var(vars, file.nostr.allow_key_rotation, "ALLOW_KEY_ROTATION")?.unwrap_or(false);
         
         // Optional file to persist subscribers
-        let subscribers_file = env::var("SUBSCRIBERS_FILE").ok();
+        let subscribers_file = string_var(vars, file.subscribers_file, "SUBSCRIBERS_FILE");
         
         // Optional file to cache user metadata
-        let metadata_cache_file = env::var("METADATA_CACHE_FILE").ok().or_else(|| {
-            // Default to a file in the same directory as subscribers if it exists
-            subscribers_file.as_ref().map(|s| {
-                let path = std::path::Path::new(s);
-                let dir = path.parent().unwrap_or_else(|| std::path::Path::new("."));
-                dir.join("metadata_cache.json").to_string_lossy().to_string()
-            })
+        let metadata_cache_file = string_var(vars, file.metadata_cache_file, "METADATA_CACHE_FILE")
+            .or_else(|| sibling_file(&subscribers_file, "metadata_cache.json"));
+        
+        // Refetch cached profiles past their lifetime in the background
+        let metadata_refresh_interval_secs = parse_var(vars, file.metadata_refresh_interval_secs, "METADATA_REFRESH_INTERVAL_SECS")?.unwrap_or(3600);
+        
+        // Optional file to persist subscribers' keyword filters
+        let subscriber_filters_file = string_var(vars, file.subscriber_filters_file, "SUBSCRIBER_FILTERS_FILE")
+            .or_else(|| sibling_file(&subscribers_file, "subscriber_filters.json"));
+        
+        // Optional file to persist subscribers' message format preferences
+        let preferences_file = string_var(vars, file.preferences_file, "PREFERENCES_FILE")
+            .or_else(|| sibling_file(&subscribers_file, "preferences.json"));
+        
+        // Optional file to persist the forum thread of each Nostr sender
+        let forum_thread_map_file = string_var(vars, file.forum_thread_map_file, "FORUM_THREAD_MAP_FILE")
+            .or_else(|| sibling_file(&subscribers_file, "forum_threads.json"));
+        
+        // Optional SQLite database archiving every bridged message for search
+        let archive_file = string_var(vars, file.archive_file, "ARCHIVE_FILE");
+        
+        // Optional file to persist the Discord usernames Nostr users registered
+        let username_map_file = string_var(vars, file.username_map_file, "USERNAME_MAP_FILE")
+            .or_else(|| sibling_file(&subscribers_file, "username_map.json"));
+        
+        // Messages held while an admin has paused the bridge
+        let pause_buffer_size = parse_var(vars, file.pause_buffer_size, "PAUSE_BUFFER_SIZE")?.unwrap_or(200);
+        
+        // Event kinds the bridge is willing to process (defaults to NIP-17 DMs)
+        let allowed_nostr_event_kinds = list_var(vars, file.nostr.allowed_kinds, "NOSTR_ALLOWED_KINDS")?
+            .unwrap_or_else(|| vec![14]);
+        
+        // Drop giftwraps whose seal sender doesn't match the rumor author
+        let strict_giftwrap = bool_var(vars, file.nostr.strict_giftwrap, "STRICT_GIFTWRAP")?.unwrap_or(false);
+        
+        // Also accept legacy NIP-04 (kind 4) DMs from older clients
+        let support_nip04 = bool_var(vars, file.nostr.support_nip04, "SUPPORT_NIP04")?.unwrap_or(false);
+        
+        // Minimum NIP-13 proof-of-work on incoming events, checked before decrypting
+        let min_pow_difficulty = parse_var(vars, file.nostr.min_pow_difficulty, "MIN_POW_DIFFICULTY")?;
+        
+        // Optional NIP-51 people list to import subscribers from, and whether to publish changes back
+        let nostr_subscriber_list_event_id = string_var(vars, file.nostr.subscriber_list_event_id, "NOSTR_SUBSCRIBER_LIST_EVENT_ID");
+        let sync_subscriber_list = bool_var(vars, file.nostr.sync_subscriber_list, "SYNC_SUBSCRIBER_LIST")?.unwrap_or(false);
+        
+        // Mute senders repeating the same message more than the threshold within the window
+        let spam_duplicate_threshold = parse_var(vars, file.nostr.spam_duplicate_threshold, "SPAM_DUPLICATE_THRESHOLD")?.unwrap_or(3);
+        let spam_window_secs = parse_var(vars, file.nostr.spam_window_secs, "SPAM_WINDOW_SECS")?.unwrap_or(60);
+        let spam_mute_secs = parse_var(vars, file.nostr.spam_mute_secs, "SPAM_MUTE_SECS")?.unwrap_or(300);
+        
+        // Cool down senders bursting more than the threshold of messages of any content within the window
+        let flood_threshold = parse_var(vars, file.nostr.flood_threshold, "FLOOD_THRESHOLD")?.unwrap_or(10);
+        let flood_window_secs = parse_var(vars, file.nostr.flood_window_secs, "FLOOD_WINDOW_SECS")?.unwrap_or(10);
+        let flood_cooldown_secs = parse_var(vars, file.nostr.flood_cooldown_secs, "FLOOD_COOLDOWN_SECS")?.unwrap_or(120);
+        
+        // Public profile of the bridge's Nostr identity
+        let publish_profile = bool_var(vars, file.profile.publish, "PUBLISH_PROFILE")?.unwrap_or(false);
+        // NIP-65 list of the configured relays, republished only when it changes
+        let publish_relay_list = bool_var(vars, file.profile.publish_relay_list, "PUBLISH_RELAY_LIST")?.unwrap_or(false);
+        let bot_name = string_var(vars, file.profile.name, "BOT_NAME").unwrap_or_else(|| "Vecord".to_string());
+        let bot_description = string_var(vars, file.profile.description, "BOT_DESCRIPTION").unwrap_or_else(|| {
+            "The Vecord Bridge - Bringing the anonymity of Vector to the Discord realm.".to_string()
         });
+        let bot_picture_url = string_var(vars, file.profile.picture_url, "BOT_PICTURE_URL");
+        let bot_website = string_var(vars, file.profile.website, "BOT_WEBSITE");
+        
+        // Optional rolling log file alongside stdout
+        let log_file = string_var(vars, file.log_file, "LOG_FILE");
+        let log_rotation = string_var(vars, file.log_rotation, "LOG_ROTATION")
+            .map(|r| r.trim().to_lowercase())
+            .unwrap_or_else(|| "daily".to_string());
+
+        // Zone for log lines and displayed message times, timestamps stay Unix seconds internally
+        let timezone = string_var(vars, file.timezone, "TIMEZONE").unwrap_or_else(|| "UTC".to_string());
+        
+        // Replies to !subscribe and !unsubscribe, with {pubkey}, {subscriber_count} and {relay_count} placeholders
+        let subscribe_message = string_var(vars, file.messages.subscribe, "SUBSCRIBE_MESSAGE")
+            .unwrap_or_else(|| DEFAULT_SUBSCRIBE_MESSAGE.to_string());
+        let unsubscribe_message = string_var(vars, file.messages.unsubscribe, "UNSUBSCRIBE_MESSAGE")
+            .unwrap_or_else(|| DEFAULT_UNSUBSCRIBE_MESSAGE.to_string());
+        
+        // Regex patterns for content that must not be bridged, one per line
+        let content_filter_patterns = file.filter.content_patterns.unwrap_or_else(|| {
+            vars.get("CONTENT_FILTER_PATTERNS")
+                .map(|v| v.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+                .unwrap_or_default()
+        });
+        let notify_filtered_sender = bool_var(vars, file.filter.notify_sender, "NOTIFY_FILTERED_SENDER")?.unwrap_or(false);
+        
+        // Keyword every Nostr message must contain to reach a topic-specific Discord channel
+        let nostr_content_must_contain = string_var(vars, file.filter.nostr_must_contain, "NOSTR_CONTENT_MUST_CONTAIN")
+            .filter(|keyword| !keyword.trim().is_empty());
+        
+        // Peer Vecord instances to share Discord messages with, and where to accept theirs
+        let federation_peers = list_var(vars, file.federation.peers, "FEDERATION_PEERS")?.unwrap_or_default();
+        let federation_listen_addr = string_var(vars, file.federation.listen_addr, "FEDERATION_LISTEN_ADDR");
+        let federation_token = string_var(vars, file.federation.token, "FEDERATION_TOKEN");
+        
+        // HTTP API for dashboards, every request must carry a NIP-98 event signed by an admin key
+        let admin_listen_addr = string_var(vars, file.admin.listen_addr, "ADMIN_LISTEN_ADDR");
+        let admin_api_port = parse_var(vars, file.admin.api_port, "ADMIN_API_PORT")?;
+        
+        // Prometheus metrics on their own listener, so scraping doesn't depend on the admin API
+        let metrics_listen_addr = string_var(vars, file.metrics.listen_addr, "METRICS_LISTEN_ADDR");
+        
+        // Each `[[bridges]]` entry's relays are cleaned up like the top-level ones
+        let mut bridges = file.bridges;
+        for bridge in &mut bridges {
+            let (relays, duplicates) = normalize_relays("[[bridges]] nostr_relays", std::mem::take(&mut bridge.nostr_relays))?;
+            bridge.nostr_relays = relays;
+            duplicate_relays.extend(duplicates);
+        }
         
         Ok(Self {
             discord_token,
+            discord_webhook_url,
+            webhook_use_sender_identity,
             discord_channel_id,
+            discord_guild_id,
+            discord_system_channel_id,
+            bridge_membership_events,
+            bridge_reactions,
+            discord_use_forum,
+            discord_forum_channel_id,
+            show_lightning_addresses,
+            show_relay_in_footer,
+            show_profile_website,
+            discord_mention_on_nostr_reply,
+            delivery_receipt_reactions,
+            fail_on_missing_permissions,
+            discord_ignore_webhook_messages,
+            discord_bot_user_id,
+            cache_avatars,
+            avatar_cache_dir,
+            avatar_cache_ttl_secs,
+            image_proxy_url,
+            circuit_breaker_threshold,
+            circuit_breaker_buffer,
+            circuit_breaker_timeout_secs,
+            allowed_discord_role_ids,
             nostr_private_key,
-            nostr_relays,
+            nostr_private_key_encrypted,
+            nostr_key_passphrase,
+            nostr_bunker_url,
+            nostr_bunker_secret,
+            nostr_bunker_pubkey,
+            nostr_read_relays,
+            nostr_write_relays,
+            relay_configs,
+            nostr_max_msgs_per_sec,
+            duplicate_relays,
+            min_relay_count,
+            max_relay_count,
+            admin_pubkeys,
+            enabled_commands,
+            silent_disabled_commands,
+            allow_key_rotation,
             subscribers_file,
             metadata_cache_file,
+            metadata_refresh_interval_secs,
+            subscriber_filters_file,
+            preferences_file,
+            forum_thread_map_file,
+            archive_file,
+            username_map_file,
+            pause_buffer_size,
+            allowed_nostr_event_kinds,
+            strict_giftwrap,
+            support_nip04,
+            min_pow_difficulty,
+            nostr_subscriber_list_event_id,
+            sync_subscriber_list,
+            spam_duplicate_threshold,
+            spam_window_secs,
+            spam_mute_secs,
+            flood_threshold,
+            flood_window_secs,
+            flood_cooldown_secs,
+            publish_profile,
+            publish_relay_list,
+            bot_name,
+            bot_description,
+            bot_picture_url,
+            bot_website,
+            log_file,
+            log_rotation,
+            timezone,
+            subscribe_message,
+            unsubscribe_message,
+            content_filter_patterns,
+            notify_filtered_sender,
+            nostr_content_must_contain,
+            federation_peers,
+            federation_listen_addr,
+            federation_token,
+            admin_listen_addr,
+            admin_api_port,
+            metrics_listen_addr,
+            bridges,
+            bridge_name: None,
         })
     }
 }
+
+/// A config value that is missing or can't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A setting the bridge can't run without
+    MissingRequired(String),
+    /// A value that doesn't parse as the setting's type
+    ParseError { field: String, value: String, reason: String },
+    /// A value that parses but is out of range
+    InvalidValue { name: String, value: String, reason: String },
+    /// Two settings that can't be used together
+    Incompatible(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingRequired(name) => write!(f, "Expected {} in the environment", name),
+            ConfigError::ParseError { field, value, reason } => write!(f, "{} could not parse '{}': {}", field, value, reason),
+            ConfigError::InvalidValue { name, value, reason } => write!(f, "{} has an invalid value '{}': {}", name, value, reason),
+            ConfigError::Incompatible(first, second) => write!(f, "{} can't be used together with {}", first, second),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Trims, parses and sorts the relays of `name`, dropping trailing slashes and duplicates
+///
+/// Returns the relays along with every duplicate removed.
+pub fn normalize_relays(name: &str, relays: Vec<String>) -> Result<(Vec<String>, Vec<String>), ConfigError> {
+    let mut normalized = relays.iter()
+        .map(|relay| normalize_relay(name, relay))
+        .collect::<Result<Vec<_>, _>>()?;
+    if normalized.is_empty() {
+        return Err(ConfigError::InvalidValue {
+            name: name.to_string(),
+            value: String::new(),
+            reason: "at least one relay is required".to_string(),
+        });
+    }
+
+    normalized.sort();
+    let mut duplicates = Vec::new();
+    normalized.dedup_by(|relay, kept| {
+        let duplicate = relay == kept;
+        if duplicate {
+            duplicates.push(relay.clone());
+        }
+        duplicate
+    });
+    Ok((normalized, duplicates))
+}
+
+/// Trims and parses a single relay of `name`, dropping its trailing slash
+pub fn normalize_relay(name: &str, relay: &str) -> Result<String, ConfigError> {
+    let relay = relay.trim();
+    let invalid = |reason: String| ConfigError::InvalidValue {
+        name: name.to_string(),
+        value: relay.to_string(),
+        reason,
+    };
+
+    let url = Url::parse(relay).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(url.scheme(), "ws" | "wss") {
+        return Err(invalid("relays must be ws:// or wss:// URLs".to_string()));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Parses a Nostr secret key given as either an `nsec` bech32 string or hex
+pub fn parse_secret_key(s: &str) -> Result<SecretKey> {
+    if s.starts_with("nsec") {
+        SecretKey::from_bech32(s).map_err(|e| anyhow!("Invalid nsec key: {}", e))
+    } else {
+        SecretKey::from_str(s).map_err(|e| anyhow!("Invalid hex key: {}", e))
+    }
+}
+
+/// A file named `file_name` in the same directory as the subscribers file, if there is one
+fn sibling_file(subscribers_file: &Option<String>, file_name: &str) -> Option<String> {
+    subscribers_file.as_ref().map(|s| {
+        let path = Path::new(s);
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        dir.join(file_name).to_string_lossy().to_string()
+    })
+}
+
+/// `$XDG_CACHE_HOME/vecord/avatars`, falling back to `~/.cache` and then the working directory
+fn default_avatar_cache_dir() -> String {
+    let cache_home = env::var("XDG_CACHE_HOME").ok()
+        .or_else(|| env::var("HOME").ok().map(|home| format!("{}/.cache", home)))
+        .unwrap_or_else(|| ".cache".to_string());
+    
+    Path::new(&cache_home).join("vecord").join("avatars").to_string_lossy().to_string()
+}
+
+/// Returns the file value if present, otherwise the environment variable
+fn string_var(vars: &EnvVars, file: Option<String>, name: &str) -> Option<String> {
+    file.or_else(|| vars.get(name))
+}
+
+/// Returns the file value if present, otherwise parses the environment variable
+fn parse_var<T>(vars: &EnvVars, file: Option<T>, name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    if file.is_some() {
+        return Ok(file);
+    }
+    
+    match vars.get(name) {
+        Some(value) => value.trim().parse::<T>()
+            .map(Some)
+            .map_err(|e| ConfigError::ParseError { field: name.to_string(), value: value.clone(), reason: e.to_string() }.into()),
+        None => Ok(None),
+    }
+}
+
+/// Returns the file list if present, otherwise parses a comma-separated environment variable
+fn list_var<T>(vars: &EnvVars, file: Option<Vec<T>>, name: &str) -> Result<Option<Vec<T>>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    if file.is_some() {
+        return Ok(file);
+    }
+    
+    match vars.get(name) {
+        Some(value) => value
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<T>().map_err(|e| ConfigError::ParseError { field: name.to_string(), value: s.to_string(), reason: e.to_string() }.into()))
+            .collect::<Result<Vec<T>>>()
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Returns the file flag if present, otherwise parses a `true`/`false` (or `1`/`0`) environment variable
+fn bool_var(vars: &EnvVars, file: Option<bool>, name: &str) -> Result<Option<bool>> {
+    if file.is_some() {
+        return Ok(file);
+    }
+    
+    match vars.get(name) {
+        Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(Some(true)),
+            "false" | "0" => Ok(Some(false)),
+            _ => Err(ConfigError::ParseError {
+                field: name.to_string(),
+                value: value.clone(),
+                reason: "expected true or false".to_string(),
+            }.into()),
+        },
+        None => Ok(None),
+    }
+}
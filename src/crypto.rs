@@ -0,0 +1,62 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Result, anyhow};
+use argon2::Argon2;
+use nostr_sdk::util::hex;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// 32 symbols so each random byte maps evenly, leaving out the easily confused 0, O, 1 and I
+const TOKEN_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Derives the 256-bit AES key for `passphrase` with Argon2id
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key from passphrase: {}", e))?;
+    Ok(key)
+}
+
+/// A random alphanumeric token of `len` characters, for confirming sensitive commands
+pub fn confirmation_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| TOKEN_ALPHABET[*b as usize % TOKEN_ALPHABET.len()] as char).collect()
+}
+
+/// Encrypts a private key with `passphrase`, returning hex-encoded `salt||nonce||ciphertext`
+pub fn encrypt_private_key(private_key: &str, passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher.encrypt(&nonce, private_key.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt private key"))?;
+
+    let mut blob = salt.to_vec();
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&ciphertext);
+    Ok(hex::encode(blob))
+}
+
+/// Decrypts a blob produced by `encrypt_private_key`, keeping the cleartext key in memory only
+pub fn decrypt_private_key(encrypted: &str, passphrase: &str) -> Result<String> {
+    let corrupted = || anyhow!("Wrong passphrase or corrupted key");
+
+    let blob = hex::decode(encrypted.trim()).map_err(|_| corrupted())?;
+    if blob.len() <= SALT_LEN + NONCE_LEN {
+        return Err(corrupted());
+    }
+    let (salt, rest) = blob.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, salt)?);
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| corrupted())?;
+
+    String::from_utf8(plaintext).map_err(|_| corrupted())
+}
@@ -0,0 +1,78 @@
+use crate::persist;
+use serenity::all::ChannelId;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+/// The forum thread each Nostr sender's messages are posted in, persisted as JSON keyed by npub
+#[derive(Clone, Default)]
+pub struct ForumThreads {
+    threads: Arc<Mutex<HashMap<String, ChannelId>>>,
+    file_path: Option<String>,
+}
+
+impl ForumThreads {
+    pub fn new(file_path: Option<String>) -> Self {
+        let mut threads = HashMap::new();
+
+        // Try to load the threads from the file if it exists
+        if let Some(path) = &file_path {
+            if let Ok(contents) = fs::read_to_string(path) {
+                match serde_json::from_str::<HashMap<String, ChannelId>>(&contents) {
+                    Ok(loaded) => {
+                        threads = loaded;
+                        info!("Loaded forum threads for {} Nostr users", threads.len());
+                    }
+                    Err(e) => warn!("Failed to parse forum thread file: {}", e),
+                }
+            }
+        }
+
+        Self {
+            threads: Arc::new(Mutex::new(threads)),
+            file_path,
+        }
+    }
+
+    pub fn get(&self, npub: &str) -> Option<ChannelId> {
+        self.threads.lock().unwrap().get(npub).copied()
+    }
+
+    /// Remembers the thread created for a sender and saves the map
+    pub async fn insert(&self, npub: String, thread: ChannelId) {
+        self.threads.lock().unwrap().insert(npub, thread);
+        self.save_to_file().await;
+    }
+
+    /// Forgets a sender's thread, such as one deleted on Discord, and saves the map
+    pub async fn remove(&self, npub: &str) {
+        self.threads.lock().unwrap().remove(npub);
+        self.save_to_file().await;
+    }
+
+    async fn save_to_file(&self) {
+        if let Some(path) = &self.file_path {
+            // Snapshot the threads so the lock isn't held during file I/O
+            let json_result = {
+                let threads = self.threads.lock().unwrap();
+                serde_json::to_vec(&*threads)
+            };
+
+            match json_result {
+                Ok(json) => {
+                    // A lost map would open a second thread for every sender
+                    let path = path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        persist::atomic_write(&path, |file| file.write_all(&json))
+                    }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    if let Err(e) = result {
+                        error!("Failed to write forum thread file: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to serialize forum threads: {}", e),
+            }
+        }
+    }
+}
@@ -1,37 +1,375 @@
-use crate::message::{BridgeMessage, ImageAttachment};
+use crate::discord::SharedContext;
+use crate::filter::{ContentFilter, FILTERED_NOTICE};
+use crate::message::{BridgeMessage, ImageAttachment, SystemEventType};
+use crate::metrics;
+use crate::priority::PrioritySender;
+use crate::sanitize::normalize_content;
+use crate::usernames::UsernameMap;
 use serenity::all::{
-    ChannelId, Context, EventHandler, Message, MessageType, Ready,
+    ChannelId, Context, CreateMessage, EventHandler, GuildChannel, GuildId, Member, Message, MessageId, MessageType, Permissions,
+    Reaction, ReactionType, Ready, StickerFormatType, User, UserId, WebhookId,
 };
-use tokio::sync::mpsc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 pub struct Handler {
-    channel_id: ChannelId,
-    message_sender: mpsc::Sender<BridgeMessage>,
+    /// Each bridged channel and the sender for its Nostr side
+    routes: HashMap<ChannelId, PrioritySender<BridgeMessage>>,
+    /// Set while the bot can't view or post in a bridged channel
+    channel_unavailable: HashMap<ChannelId, Arc<AtomicBool>>,
+    /// Guild the bridged channels must belong to, when configured
+    guild_id: Option<GuildId>,
+    /// Guild of each bridged channel, learned while checking its permissions
+    channel_guilds: Mutex<HashMap<ChannelId, GuildId>>,
+    bridge_membership_events: bool,
+    allowed_role_ids: Arc<Vec<u64>>,
+    content_filter: ContentFilter,
+    notify_filtered_sender: bool,
+    fail_on_missing_permissions: bool,
+    /// Fired once the first `ready` has checked permissions
+    ready_notifier: Mutex<Option<oneshot::Sender<()>>>,
+    bridge_reactions: bool,
+    /// The bot's own user, whose reactions came from Nostr and must not be bridged back
+    bot_user_id: Mutex<Option<UserId>>,
+    /// When a reaction on each message was last bridged
+    reaction_times: Mutex<HashMap<MessageId, Instant>>,
+    /// Shared with the Discord bots, which add Nostr reactions through it
+    context: SharedContext,
+    /// Drop every webhook message, not just those of `own_webhook_id`
+    ignore_webhook_messages: bool,
+    /// The webhook the bridge posts Nostr messages through, if any
+    own_webhook_id: Option<WebhookId>,
+    /// The bridge's own user as configured, for apps whose messages lack the bot flag
+    configured_user_id: Option<UserId>,
+    /// Discord usernames linked to Nostr users, whose mentions are DMed to them
+    usernames: UsernameMap,
+    /// Name and icon of each guild messages came from, fetched once since the gateway cache is disabled
+    guilds: Mutex<HashMap<GuildId, (String, Option<String>)>>,
 }
 
+// Reactions on a message after the first are dropped for this long, to keep emoji spam off Nostr
+const REACTION_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Permissions the bridge needs in its channel to post Nostr messages
+const REQUIRED_PERMISSIONS: &[(Permissions, &str)] = &[
+    (Permissions::VIEW_CHANNEL, "VIEW_CHANNEL"),
+    (Permissions::SEND_MESSAGES, "SEND_MESSAGES"),
+    (Permissions::EMBED_LINKS, "EMBED_LINKS"),
+];
+
+// Losing either of these makes the channel unusable for the bridge
+const ACCESS_PERMISSIONS: &[&str] = &["VIEW_CHANNEL", "SEND_MESSAGES"];
+
+const CHANNEL_UNAVAILABLE_NOTICE: &str = "The bridged Discord channel has become unavailable, messages will not be bridged until access is restored.";
+const CHANNEL_RESTORED_NOTICE: &str = "Bridge channel is back online.";
+
 impl Handler {
-    pub fn new(channel_id: ChannelId, message_sender: mpsc::Sender<BridgeMessage>) -> Self {
+    pub fn new(
+        routes: HashMap<ChannelId, PrioritySender<BridgeMessage>>,
+        guild_id: Option<GuildId>,
+        bridge_membership_events: bool,
+        allowed_role_ids: Arc<Vec<u64>>,
+        content_filter: ContentFilter,
+        notify_filtered_sender: bool,
+        fail_on_missing_permissions: bool,
+    ) -> Self {
+        let channel_unavailable = routes.keys()
+            .map(|id| (*id, Arc::new(AtomicBool::new(false))))
+            .collect();
+
+        Self {
+            routes,
+            channel_unavailable,
+            guild_id,
+            channel_guilds: Mutex::new(HashMap::new()),
+            bridge_membership_events,
+            allowed_role_ids,
+            content_filter,
+            notify_filtered_sender,
+            fail_on_missing_permissions,
+            ready_notifier: Mutex::new(None),
+            bridge_reactions: false,
+            bot_user_id: Mutex::new(None),
+            reaction_times: Mutex::new(HashMap::new()),
+            context: Arc::new(Mutex::new(None)),
+            ignore_webhook_messages: true,
+            own_webhook_id: None,
+            configured_user_id: None,
+            usernames: UsernameMap::default(),
+            guilds: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fires `notifier` once Discord is connected and the bridged channels have been checked
+    pub fn with_ready_notifier(self, notifier: Option<oneshot::Sender<()>>) -> Self {
+        Self {
+            ready_notifier: Mutex::new(notifier),
+            ..self
+        }
+    }
+
+    /// Bridges reactions on messages from Nostr back to their authors when `enabled`
+    pub fn with_bridge_reactions(self, enabled: bool) -> Self {
+        Self {
+            bridge_reactions: enabled,
+            ..self
+        }
+    }
+
+    /// Chooses which webhook and bot messages are dropped to prevent bridging loops
+    pub fn with_loop_protection(self, ignore_webhook_messages: bool, own_webhook_id: Option<WebhookId>, configured_user_id: Option<UserId>) -> Self {
         Self {
-            channel_id,
-            message_sender,
+            ignore_webhook_messages,
+            own_webhook_id,
+            configured_user_id,
+            ..self
+        }
+    }
+
+    /// Stores the gateway context in `context` once connected
+    pub fn with_context(self, context: SharedContext) -> Self {
+        Self { context, ..self }
+    }
+
+    /// DMs mentions of registered Discord usernames to their Nostr users
+    pub fn with_usernames(self, usernames: UsernameMap) -> Self {
+        Self { usernames, ..self }
+    }
+
+    /// The name and icon of a guild, fetched from Discord the first time it is seen
+    async fn guild_info(&self, ctx: &Context, guild_id: GuildId) -> (Option<String>, Option<String>) {
+        if let Some((name, icon_url)) = self.guilds.lock().unwrap().get(&guild_id) {
+            return (Some(name.clone()), icon_url.clone());
+        }
+        match ctx.http.get_guild(guild_id).await {
+            Ok(guild) => {
+                let icon_url = guild.icon_url();
+                self.guilds.lock().unwrap().insert(guild_id, (guild.name.clone(), icon_url.clone()));
+                (Some(guild.name), icon_url)
+            }
+            Err(e) => {
+                warn!("Failed to fetch guild {}: {}", guild_id, e);
+                (None, None)
+            }
+        }
+    }
+
+    /// Whether a reaction on `message_id` may be bridged now, starting its cooldown if so
+    fn take_reaction_slot(&self, message_id: MessageId) -> bool {
+        let now = Instant::now();
+        let mut reaction_times = self.reaction_times.lock().unwrap();
+        reaction_times.retain(|_, at| now.duration_since(*at) < REACTION_COOLDOWN);
+        if reaction_times.contains_key(&message_id) {
+            return false;
+        }
+        reaction_times.insert(message_id, now);
+        true
+    }
+
+    /// Returns the names of required permissions the bot lacks in a bridged channel, and the channel's name
+    async fn missing_permissions(&self, ctx: &Context, channel_id: ChannelId, bot_id: UserId) -> serenity::Result<(String, Vec<&'static str>)> {
+        let channel = channel_id.to_channel(&ctx.http).await?;
+        let Some(channel) = channel.guild() else {
+            // Not a guild channel, so there are no permission overwrites to check
+            return Ok((channel_id.to_string(), Vec::new()));
+        };
+        self.channel_guilds.lock().unwrap().insert(channel_id, channel.guild_id);
+
+        // The cache has no guilds yet when `ready` fires, so compute permissions over HTTP
+        let guild = ctx.http.get_guild(channel.guild_id).await?;
+        let member = ctx.http.get_member(channel.guild_id, bot_id).await?;
+        let permissions = guild.user_permissions_in(&channel, &member);
+
+        let missing = REQUIRED_PERMISSIONS.iter()
+            .filter(|(permission, _)| !permissions.contains(*permission))
+            .map(|(_, name)| *name)
+            .collect();
+
+        Ok((channel.name, missing))
+    }
+
+    /// Flips a channel's availability, telling its subscribers when it actually changes
+    async fn set_channel_unavailable(&self, channel_id: ChannelId, unavailable: bool) {
+        let (Some(flag), Some(sender)) = (self.channel_unavailable.get(&channel_id), self.routes.get(&channel_id)) else {
+            return;
+        };
+        if flag.swap(unavailable, Ordering::Relaxed) == unavailable {
+            return;
+        }
+
+        let (event_type, notice) = if unavailable {
+            warn!("Discord channel {} became unavailable", channel_id);
+            (SystemEventType::ChannelUnavailable, CHANNEL_UNAVAILABLE_NOTICE)
+        } else {
+            info!("Discord channel {} is available again", channel_id);
+            (SystemEventType::ChannelRestored, CHANNEL_RESTORED_NOTICE)
+        };
+        let message = BridgeMessage::System {
+            event_type,
+            message: notice.to_string(),
+            delivered: None,
+            span: info_span!("bridge_message", direction = "system", source_id = %channel_id),
+        };
+        if let Err(e) = sender.send(message).await {
+            error!("Error sending channel notice to Nostr: {}", e);
+        }
+    }
+
+    /// Announces a membership change to the subscribers of every bridge in `guild_id`
+    async fn send_membership_notice(&self, guild_id: GuildId, event_type: SystemEventType, notice: String) {
+        if !self.bridge_membership_events {
+            return;
+        }
+
+        let channels: Vec<ChannelId> = self.channel_guilds.lock().unwrap().iter()
+            .filter(|(_, guild)| **guild == guild_id)
+            .map(|(channel_id, _)| *channel_id)
+            .collect();
+        for channel_id in channels {
+            let Some(sender) = self.routes.get(&channel_id) else {
+                continue;
+            };
+            let message = BridgeMessage::System {
+                event_type,
+                message: notice.clone(),
+                delivered: None,
+                span: info_span!("bridge_message", direction = "system", source_id = %guild_id),
+            };
+            if let Err(e) = sender.send(message).await {
+                error!("Error sending membership notice to Nostr: {}", e);
+            }
         }
     }
 }
 
 #[serenity::async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         println!("Connected to Discord as {}", ready.user.name);
+        *self.bot_user_id.lock().unwrap() = Some(ready.user.id);
+        *self.context.lock().unwrap() = Some(ctx.clone());
+
+        // Surface missing permissions now instead of as cryptic errors on the first send
+        for channel_id in self.routes.keys() {
+            match self.missing_permissions(&ctx, *channel_id, ready.user.id).await {
+                Ok((_, missing)) if missing.is_empty() => {}
+                Ok((channel_name, missing)) => {
+                    for permission in &missing {
+                        error!("Bot is missing the {} permission in #{}", permission, channel_name);
+                    }
+                    if self.fail_on_missing_permissions {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => error!("Failed to check permissions for channel {}: {}", channel_id, e),
+            }
+        }
+
+        if let Some(notifier) = self.ready_notifier.lock().unwrap().take() {
+            let _ = notifier.send(());
+        }
     }
 
-    async fn message(&self, _ctx: Context, msg: Message) {
-        // Only process messages from the specified channel
-        if msg.channel_id != self.channel_id {
+    async fn channel_update(&self, ctx: Context, _old: Option<GuildChannel>, new: GuildChannel) {
+        if !self.routes.contains_key(&new.id) {
             return;
         }
 
-        // Ignore bot messages to prevent loops
-        if msg.author.bot {
+        // Overwrite changes arrive as channel updates, so re-check the bot's access
+        let Some(bot_id) = *self.bot_user_id.lock().unwrap() else {
+            return;
+        };
+        match self.missing_permissions(&ctx, new.id, bot_id).await {
+            Ok((_, missing)) => {
+                let unavailable = missing.iter().any(|p| ACCESS_PERMISSIONS.contains(p));
+                self.set_channel_unavailable(new.id, unavailable).await;
+            }
+            // Failing to even fetch the channel means the bot can no longer see it
+            Err(e) => {
+                error!("Failed to check permissions for channel {}: {}", new.id, e);
+                self.set_channel_unavailable(new.id, true).await;
+            }
+        }
+    }
+
+    async fn channel_delete(&self, _ctx: Context, channel: GuildChannel, _messages: Option<Vec<Message>>) {
+        self.set_channel_unavailable(channel.id, true).await;
+    }
+
+    async fn guild_member_addition(&self, _ctx: Context, new_member: Member) {
+        let notice = format!("[Discord] {} joined the server", new_member.user.name);
+        self.send_membership_notice(new_member.guild_id, SystemEventType::MemberJoin, notice).await;
+    }
+
+    async fn guild_member_removal(&self, _ctx: Context, guild_id: GuildId, user: User, _member: Option<Member>) {
+        let notice = format!("[Discord] {} left the server", user.name);
+        self.send_membership_notice(guild_id, SystemEventType::MemberLeave, notice).await;
+    }
+
+    async fn reaction_add(&self, _ctx: Context, reaction: Reaction) {
+        if !self.bridge_reactions {
+            return;
+        }
+        let Some(message_sender) = self.routes.get(&reaction.channel_id) else {
+            return;
+        };
+
+        // The bot's own reactions were bridged from Nostr in the first place
+        if reaction.user_id.is_some() && reaction.user_id == *self.bot_user_id.lock().unwrap() {
+            return;
+        }
+
+        // Custom server emoji mean nothing outside Discord
+        let ReactionType::Unicode(emoji) = &reaction.emoji else {
+            return;
+        };
+
+        if !self.take_reaction_slot(reaction.message_id) {
+            debug!("Dropping reaction on {} within its cooldown", reaction.message_id);
+            return;
+        }
+
+        let message = BridgeMessage::DiscordReaction {
+            discord_message_id: reaction.message_id.get(),
+            emoji: emoji.clone(),
+            span: info_span!("bridge_message", direction = "discord_to_nostr", source_id = %reaction.message_id),
+        };
+        if let Err(e) = message_sender.send(message).await {
+            error!("Error sending reaction to Nostr: {}", e);
+        }
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        // Only process messages from a bridged channel, routed to that channel's bridge
+        let Some(message_sender) = self.routes.get(&msg.channel_id) else {
+            return;
+        };
+
+        // A matching channel ID from another guild (or a DM) is never bridged
+        if self.guild_id.is_some_and(|guild_id| msg.guild_id != Some(guild_id)) {
+            return;
+        }
+
+        // Ignore bot messages to prevent loops, webhook posts are bot messages too unless allowed
+        if let Some(webhook_id) = msg.webhook_id {
+            if self.ignore_webhook_messages {
+                return;
+            }
+            if self.own_webhook_id == Some(webhook_id) {
+                warn!("Ignoring a message from the bridge's own webhook {}, this would loop", webhook_id);
+                return;
+            }
+        } else if msg.author.bot {
+            return;
+        }
+        let is_own_user = self.configured_user_id == Some(msg.author.id)
+            || *self.bot_user_id.lock().unwrap() == Some(msg.author.id);
+        if is_own_user {
+            warn!("Ignoring a message from the bridge's own Discord user {} that isn't flagged as a bot, this would loop", msg.author.id);
             return;
         }
 
@@ -40,6 +378,29 @@ impl EventHandler for Handler {
             return;
         }
 
+        // Silently skip authors without an allowed role so roles can't be probed
+        if !self.allowed_role_ids.is_empty() {
+            let has_allowed_role = msg.member.as_ref()
+                .is_some_and(|m| m.roles.iter().any(|r| self.allowed_role_ids.contains(&r.get())));
+            if !has_allowed_role {
+                return;
+            }
+        }
+
+        // Strip invisible characters first so they can't hide content from the filter
+        let content = normalize_content(&msg.content);
+
+        // Drop messages matching the operator's content filter
+        if self.content_filter.is_blocked(&content) {
+            if self.notify_filtered_sender {
+                let notice = CreateMessage::new().content(FILTERED_NOTICE);
+                if let Err(e) = msg.author.direct_message(&ctx, notice).await {
+                    warn!("Error notifying filtered sender: {}", e);
+                }
+            }
+            return;
+        }
+
         // Optional first image attachment (we currently support a single image)
         let mut image: Option<ImageAttachment> = None;
 
@@ -80,18 +441,73 @@ impl EventHandler for Handler {
             }
         }
 
+        // Stickers are forwarded as a CDN link instead of being downloaded
+        let image_url = msg.sticker_items.first().map(|sticker| {
+            let extension = match sticker.format_type {
+                StickerFormatType::Lottie | StickerFormatType::Gif => "gif",
+                _ => "png",
+            };
+            format!("https://media.discordapp.net/stickers/{}.{}", sticker.id, extension)
+        });
+
         // Create a BridgeMessage for Nostr
+        metrics::DISCORD_TO_NOSTR_CONTENT_LENGTH.observe(&content);
+        let span = info_span!("bridge_message", direction = "discord_to_nostr", source_id = %msg.id);
         let author_name = msg.author.name.clone();
-        let content = msg.content.clone();
+        let (guild_name, guild_icon_url) = match msg.guild_id {
+            Some(guild_id) => self.guild_info(&ctx, guild_id).await,
+            None => (None, None),
+        };
         let bridge_message = BridgeMessage::Discord {
             author: author_name,
             content,
             image,
+            image_url,
+            federation_source: None,
+            guild_name,
+            guild_icon_url,
+            author_id: Some(msg.author.id.get()),
+            message_id: Some(msg.id.get()),
+            span: span.clone(),
         };
 
+        // Mentioned users who registered their Discord username also get the message directly
+        let mut mentioned = HashSet::new();
+        let mentions: Vec<BridgeMessage> = msg.mentions.iter()
+            .filter(|user| user.id != msg.author.id && !user.bot)
+            .filter_map(|user| self.usernames.get(&user.name))
+            .filter(|pubkey| mentioned.insert(*pubkey))
+            .map(|pubkey| BridgeMessage::Mention {
+                pubkey: pubkey.to_hex(),
+                author: msg.author.name.clone(),
+                content: resolve_mentions(&msg.content, &msg.mentions),
+                span: span.clone(),
+            })
+            .collect();
+
         // Send the message to be bridged to Nostr
-        if let Err(e) = self.message_sender.send(bridge_message).await {
-            eprintln!("Error sending message to Nostr: {}", e);
+        async {
+            debug!("Received Discord message from {}", msg.author.name);
+            if let Err(e) = message_sender.send(bridge_message).await {
+                error!("Error sending message to Nostr: {}", e);
+            }
+            for mention in mentions {
+                if let Err(e) = message_sender.send(mention).await {
+                    error!("Error sending mention to Nostr: {}", e);
+                }
+            }
         }
+        .instrument(span)
+        .await;
     }
 }
+
+/// Replaces the `<@id>` mentions in `content` with the mentioned users' `@name`
+fn resolve_mentions(content: &str, mentions: &[User]) -> String {
+    mentions.iter().fold(content.to_string(), |content, user| {
+        let name = format!("@{}", user.name);
+        content
+            .replace(&format!("<@{}>", user.id), &name)
+            .replace(&format!("<@!{}>", user.id), &name)
+    })
+}
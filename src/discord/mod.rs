@@ -1,46 +1,305 @@
+mod forum;
 mod handler;
 
 use crate::config::Config;
-use crate::message::BridgeMessage;
-use anyhow::Result;
+use crate::filter::ContentFilter;
+use crate::mapping::{MessageIdMapping, NostrMessageRef};
+use crate::message::{BridgeMessage, NostrMessageMetadata};
+use crate::metadata::short_pubkey;
+use crate::priority::PrioritySender;
+use crate::time;
+use crate::usernames::UsernameMap;
+use anyhow::{Result, anyhow};
+use chrono_tz::Tz;
 use serenity::all::{
-    ChannelId, Client, Colour, CreateEmbed, CreateEmbedAuthor, CreateMessage, GatewayIntents, Http
+    ChannelId, Client, Colour, Context, CreateAttachment, CreateEmbed, CreateEmbedAuthor, CreateForumPost, CreateMessage,
+    GatewayIntents, GuildId, Http, HttpError, MessageId, ReactionType, StatusCode, UserId, WebhookId,
 };
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+use url::Url;
+use nostr_sdk::EventId;
+use vector_sdk::nostr::{PublicKey, ToBech32};
 
+pub use forum::ForumThreads;
 pub use handler::Handler;
 
+/// The listening bot's gateway context, unset until Discord is ready
+pub type SharedContext = Arc<Mutex<Option<Context>>>;
+
+// Discord rejects webhook usernames longer than this
+const WEBHOOK_USERNAME_MAX_CHARS: usize = 80;
+
+// Discord rejects thread names longer than this
+const FORUM_POST_TITLE_MAX_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open { opened_at: Instant },
+    /// One trial message is allowed through to probe for recovery
+    HalfOpen,
+}
+
+/// Stops calling Discord during sustained failures, buffering messages until it recovers
+pub struct CircuitBreaker {
+    state: Mutex<CircuitState>,
+    consecutive_failures: Arc<AtomicU32>,
+    buffer: Mutex<VecDeque<BridgeMessage>>,
+    threshold: u32,
+    buffer_size: usize,
+    timeout: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, buffer_size: usize, timeout: Duration) -> Self {
+        Self {
+            state: Mutex::new(CircuitState::Closed),
+            consecutive_failures: Arc::new(AtomicU32::new(0)),
+            buffer: Mutex::new(VecDeque::new()),
+            threshold,
+            buffer_size,
+            timeout,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Returns true if a call may be attempted, moving an expired `Open` to `HalfOpen`
+    pub fn allow_request(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            CircuitState::Closed => true,
+            CircuitState::Open { opened_at } if opened_at.elapsed() >= self.timeout => {
+                info!("Discord circuit breaker half-open, sending a trial message");
+                *state = CircuitState::HalfOpen;
+                true
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen => false,
+        }
+    }
+
+    /// Closes the circuit, returning any messages buffered while it was open
+    pub fn record_success(&self) -> Vec<BridgeMessage> {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+
+        let mut state = self.state.lock().unwrap();
+        if *state != CircuitState::Closed {
+            info!("Discord circuit breaker closed");
+            *state = CircuitState::Closed;
+        }
+
+        self.buffer.lock().unwrap().drain(..).collect()
+    }
+
+    /// Counts a failed call, returning true if it opened the circuit
+    pub fn record_failure(&self) -> bool {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut state = self.state.lock().unwrap();
+        let reopen = *state == CircuitState::HalfOpen;
+        if reopen || (*state == CircuitState::Closed && failures >= self.threshold) {
+            warn!("Discord circuit breaker opened after {} consecutive failures", failures);
+            *state = CircuitState::Open { opened_at: Instant::now() };
+            return true;
+        }
+        false
+    }
+
+    /// Holds a message until the circuit closes, dropping it if the buffer is full
+    pub fn buffer(&self, message: BridgeMessage) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() >= self.buffer_size {
+            warn!("Discord circuit breaker buffer full, dropping message");
+            return;
+        }
+        buffer.push_back(message);
+    }
+
+    /// Puts messages back at the front of the buffer after a failed flush
+    fn requeue(&self, messages: Vec<BridgeMessage>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        for message in messages.into_iter().rev() {
+            buffer.push_front(message);
+        }
+        buffer.truncate(self.buffer_size);
+    }
+}
+
+/// What became of a message handed to `DiscordBot::send_message`
+#[derive(Debug)]
+pub enum Delivery {
+    /// Posted to Discord, along with the buffered messages that were flushed after it
+    Sent { flushed: Vec<BridgeMessage> },
+    /// Held by the open circuit breaker, it may still be dropped if the buffer overflows
+    Buffered,
+}
+
 #[derive(Clone)]
 pub struct DiscordBot {
-    token: String,
+    token: Option<String>,
     channel_id: ChannelId,
-    http: Arc<Http>,
+    guild_id: Option<GuildId>,
+    system_channel_id: Option<ChannelId>,
+    bridge_membership_events: bool,
+    bridge_reactions: bool,
+    allowed_role_ids: Arc<Vec<u64>>,
+    ignore_webhook_messages: bool,
+    bot_user_id: Option<UserId>,
+    /// Bot HTTP client for sending, unset when sending through a webhook
+    http: Option<Arc<Http>>,
+    webhook_url: Option<String>,
+    webhook_use_sender_identity: bool,
+    webhook_client: reqwest::Client,
+    circuit_breaker: Arc<CircuitBreaker>,
+    cache_avatars: bool,
+    avatar_cache_dir: PathBuf,
+    avatar_cache_ttl: Duration,
+    image_proxy_url: Option<String>,
+    content_filter_patterns: Vec<String>,
+    notify_filtered_sender: bool,
+    fail_on_missing_permissions: bool,
+    /// Zone the message time is shown in, alongside Discord's own timestamp
+    timezone: Tz,
+    /// Discord messages posted for Nostr messages, shared with the Nostr client for reactions
+    message_ids: MessageIdMapping,
+    /// Gateway context of the listening bot, set once it is ready and used to add Nostr reactions
+    context: SharedContext,
+    /// Discord usernames linked to Nostr users, whose mentions are DMed to them
+    usernames: UsernameMap,
+    /// Forum channel each Nostr sender gets a thread in, unset to post everything in the bridged channel
+    forum_channel_id: Option<ChannelId>,
+    forum_threads: ForumThreads,
+    show_lightning_addresses: bool,
+    show_relay_in_footer: bool,
+    show_profile_website: bool,
 }
 
 impl DiscordBot {
     pub fn new(config: &Config) -> Self {
+        // Webhooks replace the bot token for the send path only
+        let http = match (&config.discord_webhook_url, &config.discord_token) {
+            (None, Some(token)) => Some(Arc::new(Http::new(token))),
+            _ => None,
+        };
+
         Self {
             token: config.discord_token.clone(),
             channel_id: ChannelId::new(config.discord_channel_id),
-            http: Arc::new(Http::new(&config.discord_token)),
+            guild_id: config.discord_guild_id.map(GuildId::new),
+            system_channel_id: config.discord_system_channel_id.map(ChannelId::new),
+            bridge_membership_events: config.bridge_membership_events,
+            bridge_reactions: config.bridge_reactions,
+            allowed_role_ids: Arc::new(config.allowed_discord_role_ids.clone()),
+            ignore_webhook_messages: config.discord_ignore_webhook_messages,
+            bot_user_id: config.discord_bot_user_id.map(UserId::new),
+            http,
+            webhook_url: config.discord_webhook_url.clone(),
+            webhook_use_sender_identity: config.webhook_use_sender_identity,
+            webhook_client: reqwest::Client::new(),
+            circuit_breaker: Arc::new(CircuitBreaker::new(
+                config.circuit_breaker_threshold,
+                config.circuit_breaker_buffer,
+                Duration::from_secs(config.circuit_breaker_timeout_secs),
+            )),
+            cache_avatars: config.cache_avatars,
+            avatar_cache_dir: PathBuf::from(&config.avatar_cache_dir),
+            avatar_cache_ttl: Duration::from_secs(config.avatar_cache_ttl_secs),
+            image_proxy_url: config.image_proxy_url.clone(),
+            content_filter_patterns: config.content_filter_patterns.clone(),
+            notify_filtered_sender: config.notify_filtered_sender,
+            fail_on_missing_permissions: config.fail_on_missing_permissions,
+            // Validated with the rest of the config, so this only falls back for unchecked configs
+            timezone: time::parse_timezone(&config.timezone).unwrap_or(Tz::UTC),
+            message_ids: MessageIdMapping::default(),
+            context: Arc::new(Mutex::new(None)),
+            usernames: UsernameMap::default(),
+            forum_channel_id: config.discord_forum_channel_id
+                .filter(|_| config.discord_use_forum)
+                .map(ChannelId::new),
+            forum_threads: ForumThreads::new(config.forum_thread_map_file.clone()),
+            show_lightning_addresses: config.show_lightning_addresses,
+            show_relay_in_footer: config.show_relay_in_footer,
+            show_profile_website: config.show_profile_website,
         }
     }
 
+    /// Shares one gateway context between bridges, since only the first bridge's bot listens
+    pub fn with_context(mut self, context: SharedContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Shares the Discord usernames Nostr users registered through any bridge
+    pub fn with_usernames(mut self, usernames: UsernameMap) -> Self {
+        self.usernames = usernames;
+        self
+    }
+
+    pub fn message_ids(&self) -> MessageIdMapping {
+        self.message_ids.clone()
+    }
+
     pub async fn start(
         &self,
-        message_sender: mpsc::Sender<BridgeMessage>,
+        message_sender: PrioritySender<BridgeMessage>,
     ) -> Result<()> {
+        self.start_with_routes(HashMap::from([(self.channel_id.get(), message_sender)]), None).await
+    }
+
+    /// Listens on a single gateway connection for every bridged channel, keyed by channel ID
+    ///
+    /// `ready_notifier` fires once the gateway is connected and the channels have been checked.
+    pub async fn start_with_routes(
+        &self,
+        routes: HashMap<u64, PrioritySender<BridgeMessage>>,
+        ready_notifier: Option<oneshot::Sender<()>>,
+    ) -> Result<()> {
+        // The gateway listener always requires a bot token
+        let token = self.token.as_ref()
+            .ok_or_else(|| anyhow!("A DISCORD_TOKEN is required to listen for Discord messages"))?;
+
+        let content_filter = ContentFilter::new(&self.content_filter_patterns)?;
+
         // Configure intents to receive message events
-        let intents = GatewayIntents::GUILD_MESSAGES 
-            | GatewayIntents::MESSAGE_CONTENT;
+        let mut intents = GatewayIntents::GUILD_MESSAGES 
+            | GatewayIntents::MESSAGE_CONTENT
+            | GatewayIntents::GUILDS;
+        // Privileged, so only requested when membership events are bridged
+        if self.bridge_membership_events {
+            intents |= GatewayIntents::GUILD_MEMBERS;
+        }
+        if self.bridge_reactions {
+            intents |= GatewayIntents::GUILD_MESSAGE_REACTIONS;
+        }
 
         // Create a new Client
-        let mut client = Client::builder(&self.token, intents)
+        let mut client = Client::builder(token, intents)
             .event_handler(Handler::new(
-                self.channel_id,
-                message_sender,
-            ))
+                routes.into_iter().map(|(id, sender)| (ChannelId::new(id), sender)).collect(),
+                self.guild_id,
+                self.bridge_membership_events,
+                self.allowed_role_ids.clone(),
+                content_filter,
+                self.notify_filtered_sender,
+                self.fail_on_missing_permissions,
+            )
+                .with_ready_notifier(ready_notifier)
+                .with_bridge_reactions(self.bridge_reactions)
+                .with_loop_protection(
+                    self.ignore_webhook_messages,
+                    self.webhook_url.as_deref().and_then(webhook_id),
+                    self.bot_user_id,
+                )
+                .with_context(self.context.clone())
+                .with_usernames(self.usernames.clone()))
             .await?;
 
         // Start client, this is a blocking operation
@@ -49,39 +308,368 @@ impl DiscordBot {
         Ok(())
     }
 
-    pub async fn send_message(&self, message: &BridgeMessage) -> Result<()> {
+    /// Sends a message through the circuit breaker, buffering it while Discord is failing
+    pub async fn send_message(&self, message: &BridgeMessage) -> Result<Delivery> {
+        if !self.circuit_breaker.allow_request() {
+            self.circuit_breaker.buffer(message.clone());
+            return Ok(Delivery::Buffered);
+        }
+
+        if let Err(e) = self.deliver(message).await {
+            // The failure that opens the circuit is held back with the rest rather than lost
+            if self.circuit_breaker.record_failure() {
+                warn!("Buffering message until Discord recovers: {}", e);
+                self.circuit_breaker.buffer(message.clone());
+                return Ok(Delivery::Buffered);
+            }
+            return Err(e);
+        }
+
+        // Discord is reachable again, flush anything held back while the circuit was open
+        let mut buffered = self.circuit_breaker.record_success();
+        let mut flushed = Vec::new();
+        while !buffered.is_empty() {
+            let next = buffered.remove(0);
+            if let Err(e) = self.deliver(&next).await {
+                warn!("Failed to flush buffered messages to Discord: {}", e);
+                self.circuit_breaker.record_failure();
+                buffered.insert(0, next);
+                self.circuit_breaker.requeue(buffered);
+                break;
+            }
+            flushed.push(next);
+        }
+
+        Ok(Delivery::Sent { flushed })
+    }
+
+    async fn deliver(&self, message: &BridgeMessage) -> Result<()> {
+        // The bot reacts even to messages a webhook posted
+        if let BridgeMessage::NostrReaction { discord_message_id, emoji, .. }
+            | BridgeMessage::DeliveryReceipt { discord_message_id, emoji, .. } = message
+        {
+            return self.add_reaction(*discord_message_id, emoji).await;
+        }
+        if let BridgeMessage::Redaction { discord_message_id, .. } = message {
+            return self.delete_message(*discord_message_id).await;
+        }
+
+        // Prefer the webhook when one is configured
+        if let Some(webhook_url) = &self.webhook_url {
+            return self.send_webhook_message(webhook_url, message).await;
+        }
+
+        let http = self.http.as_ref()
+            .ok_or_else(|| anyhow!("No Discord bot token or webhook configured for sending"))?;
+
         match message {
-            BridgeMessage::Nostr { content, metadata } => {
+            BridgeMessage::Nostr { content, metadata, .. } => {
                 // Create a message builder
-                let msg = CreateMessage::new();
+                let mut msg = CreateMessage::new();
+
+                // Mentions inside embeds don't notify, so the replied-to author is mentioned in the content
+                if let Some(user_id) = metadata.reply_to_discord_user_id {
+                    msg = msg.content(format!("<@{}>", user_id));
+                }
                 
                 // Create a rich embed
                 let mut embed = CreateEmbed::new();
                 embed = embed.description(content);
-                // Create a footer text without using the closure
-                embed = embed.footer(serenity::all::CreateEmbedFooter::new(metadata.pubkey.clone()));
+                // Create a footer text without using the closure, with the local time for non-UTC zones
+                let relay_host = metadata.relay_url.as_deref()
+                    .filter(|_| self.show_relay_in_footer)
+                    .and_then(|relay_url| Url::parse(relay_url).ok())
+                    .and_then(|url| url.host_str().map(str::to_string));
+                let author = match relay_host {
+                    Some(host) => format!("{} via {}", short_pubkey(&metadata.pubkey), host),
+                    None => metadata.pubkey.clone(),
+                };
+                let footer = match metadata.event_created_at {
+                    Some(created_at) if self.timezone != Tz::UTC => {
+                        format!("{} • {}", author, time::format_local(created_at, self.timezone))
+                    }
+                    _ => author,
+                };
+                embed = embed.footer(serenity::all::CreateEmbedFooter::new(footer));
                 embed = embed.color(Colour::from_rgb(89, 252, 179));
+
+                if let Some(lud16) = metadata.lud16.as_ref().filter(|_| self.show_lightning_addresses) {
+                    embed = embed.field("⚡ Tip", lud16, false);
+                }
+                if let Some(website) = metadata.website.as_ref().filter(|_| self.show_profile_website) {
+                    embed = embed.field("🌐 Website", website, false);
+                }
+
+                // Show when the message was written on Nostr rather than when it was delivered
+                if let Some(created_at) = metadata.event_created_at {
+                    embed = embed.timestamp(serenity::model::Timestamp::from_unix_timestamp(created_at as i64).unwrap_or_default());
+                }
+                
+                // Nostr content images are linked in the text, so the embed image is free for the banner
+                if let Some(banner_url) = &metadata.banner_url {
+                    embed = embed.image(self.proxy_image_url(banner_url));
+                }
                 
                 // Add thumbnail if avatar is available
                 if let Some(avatar_url) = &metadata.avatar_url {
-                    embed = embed.author(CreateEmbedAuthor::new(metadata.username.clone()).icon_url(avatar_url));
+                    let mut author = CreateEmbedAuthor::new(metadata.username.clone());
+                    if !self.cache_avatars {
+                        author = author.icon_url(self.proxy_image_url(avatar_url));
+                    } else {
+                        // Attach the cached copy so Discord never fetches the original URL
+                        match self.download_avatar(avatar_url, &metadata.pubkey).await {
+                            Ok(path) => {
+                                let attachment = CreateAttachment::path(&path).await?;
+                                author = author.icon_url(format!("attachment://{}", attachment.filename));
+                                msg = msg.add_file(attachment);
+                            }
+                            Err(e) => warn!("Failed to cache avatar for {}: {}", metadata.pubkey, e),
+                        }
+                    }
+                    embed = embed.author(author);
                 }
                 
                 // Send with rich embed
-                self.channel_id
-                    .send_message(&self.http, msg.embed(embed))
-                    .await?;
+                let msg = msg.embed(embed);
+                let sent_id = match self.forum_channel_id {
+                    Some(forum_channel_id) => self.send_forum_message(http, forum_channel_id, metadata, msg).await?,
+                    None => self.channel_id.send_message(http, msg).await?.id,
+                };
+                self.record_message_id(sent_id.get(), metadata);
             },
             
-            BridgeMessage::Discord { author, content, .. } => {
-                // This shouldn't happen, but handle it gracefully
-                self.channel_id
-                    .send_message(&self.http, CreateMessage::new()
-                        .content(format!("[Discord] {}: {}", author, content)))
+            BridgeMessage::Discord { author, content, federation_source, guild_name, guild_icon_url, .. } => {
+                // Messages federated from a peer instance are labelled with their origin
+                let origin = federation_source.as_deref().unwrap_or("Discord");
+                let text = format!("[{}] {}: {}", origin, author, content);
+                // An embed shows the icon of the server the message came from
+                let msg = match guild_icon_url {
+                    Some(icon_url) => CreateMessage::new().embed(CreateEmbed::new()
+                        .description(text)
+                        .thumbnail(icon_url)
+                        .footer(serenity::all::CreateEmbedFooter::new(guild_name.as_deref().unwrap_or(origin)))),
+                    None => CreateMessage::new().content(text),
+                };
+                self.channel_id.send_message(http, msg).await?;
+            }
+
+            BridgeMessage::System { message, .. } => {
+                self.system_channel_id.unwrap_or(self.channel_id)
+                    .send_message(http, CreateMessage::new().content(message))
                     .await?;
             }
+
+            // Reactions from Discord only ever travel to Nostr, and reactions for Discord are added above
+            BridgeMessage::DiscordReaction { .. }
+            | BridgeMessage::NostrReaction { .. }
+            | BridgeMessage::DeliveryReceipt { .. }
+            | BridgeMessage::Mention { .. }
+            | BridgeMessage::Redaction { .. } => {}
         }
         
         Ok(())
     }
+
+    /// Posts in the sender's forum thread, opening a post titled with their name for their first message
+    async fn send_forum_message(
+        &self,
+        http: &Http,
+        forum_channel_id: ChannelId,
+        metadata: &NostrMessageMetadata,
+        msg: CreateMessage,
+    ) -> Result<MessageId> {
+        if let Some(thread_id) = self.forum_threads.get(&metadata.pubkey) {
+            match thread_id.send_message(http, msg.clone()).await {
+                Ok(sent) => return Ok(sent.id),
+                // The thread was deleted on Discord, so the sender gets a new one
+                Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                    if response.status_code == StatusCode::NOT_FOUND =>
+                {
+                    warn!("Forum thread {} for {} no longer exists, opening a new one", thread_id, metadata.pubkey);
+                    self.forum_threads.remove(&metadata.pubkey).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let title: String = metadata.username.chars().take(FORUM_POST_TITLE_MAX_CHARS).collect();
+        let thread = forum_channel_id
+            .create_forum_post(http, CreateForumPost::new(title, msg))
+            .await?;
+        self.forum_threads.insert(metadata.pubkey.clone(), thread.id).await;
+
+        // A forum post's starter message shares its thread's ID
+        Ok(MessageId::new(thread.id.get()))
+    }
+
+    /// Routes an image URL through the configured proxy, prepending the proxy base verbatim
+    fn proxy_image_url(&self, url: &str) -> String {
+        match &self.image_proxy_url {
+            Some(proxy) => {
+                let encoded: String = url::form_urlencoded::byte_serialize(url.as_bytes()).collect();
+                format!("{}{}", proxy, encoded)
+            }
+            None => url.to_string(),
+        }
+    }
+
+    /// Fetches an avatar into `{cache_dir}/{pubkey_hex}.{ext}`, reusing a copy younger than the TTL
+    async fn download_avatar(&self, url: &str, pubkey: &str) -> Result<PathBuf> {
+        let pubkey_hex = PublicKey::parse(pubkey).map(|pk| pk.to_hex()).unwrap_or_else(|_| pubkey.to_string());
+        let extension = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_lowercase())
+            .filter(|ext| matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "webp"))
+            .unwrap_or_else(|| "png".to_string());
+        let path = self.avatar_cache_dir.join(format!("{}.{}", pubkey_hex, extension));
+
+        // Check the cache first
+        if let Ok(modified) = tokio::fs::metadata(&path).await.and_then(|m| m.modified()) {
+            let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+            if age < self.avatar_cache_ttl {
+                return Ok(path);
+            }
+        }
+
+        let bytes = self.webhook_client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        tokio::fs::create_dir_all(&self.avatar_cache_dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+
+        Ok(path)
+    }
+
+    async fn send_webhook_message(&self, webhook_url: &str, message: &BridgeMessage) -> Result<()> {
+        let payload = match message {
+            BridgeMessage::Nostr { content, metadata, .. } if !self.webhook_use_sender_identity => serde_json::json!({
+                "content": format!("{}**{}**: {}", reply_mention(metadata), metadata.username, content),
+            }),
+
+            BridgeMessage::Nostr { content, metadata, .. } => serde_json::json!({
+                "username": webhook_username(metadata),
+                // Webhook avatars must be URLs Discord fetches itself, so cached avatars can't be used here
+                "avatar_url": if self.cache_avatars {
+                    None
+                } else {
+                    metadata.avatar_url.as_deref().map(|url| self.proxy_image_url(url))
+                },
+                "content": format!("{}{}", reply_mention(metadata), content),
+            }),
+
+            BridgeMessage::Discord { author, content, federation_source, .. } => serde_json::json!({
+                "content": format!("[{}] {}: {}", federation_source.as_deref().unwrap_or("Discord"), author, content),
+            }),
+
+            BridgeMessage::System { message, .. } => serde_json::json!({
+                "content": message,
+            }),
+
+            BridgeMessage::DiscordReaction { .. }
+            | BridgeMessage::NostrReaction { .. }
+            | BridgeMessage::DeliveryReceipt { .. }
+            | BridgeMessage::Mention { .. }
+            | BridgeMessage::Redaction { .. } => return Ok(()),
+        };
+
+        // `wait` makes Discord answer with the created message, whose ID reactions are matched by
+        let response = self.webhook_client
+            .post(webhook_url)
+            .query(&[("wait", "true")])
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        if let BridgeMessage::Nostr { metadata, .. } = message {
+            let sent = response.json::<serde_json::Value>().await.ok();
+            if let Some(id) = sent.and_then(|sent| sent["id"].as_str().and_then(|id| id.parse().ok())) {
+                self.record_message_id(id, metadata);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a reaction to a Discord message, such as a Nostr subscriber's on a message posted for them
+    async fn add_reaction(&self, discord_message_id: u64, emoji: &str) -> Result<()> {
+        let Some(ctx) = self.context.lock().unwrap().clone() else {
+            warn!("Dropping a Nostr reaction, the Discord gateway is not connected yet");
+            return Ok(());
+        };
+
+        ctx.http.create_reaction(
+            self.message_channel(discord_message_id),
+            MessageId::new(discord_message_id),
+            &ReactionType::Unicode(emoji.to_string()),
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Deletes a message bridged from Nostr, which the bot may do even if a webhook posted it
+    async fn delete_message(&self, discord_message_id: u64) -> Result<()> {
+        let Some(ctx) = self.context.lock().unwrap().clone() else {
+            return Err(anyhow!("Cannot delete Discord message {}, the gateway is not connected yet", discord_message_id));
+        };
+
+        ctx.http.delete_message(
+            self.message_channel(discord_message_id),
+            MessageId::new(discord_message_id),
+            Some("Redacted by a bridge admin"),
+        ).await?;
+        info!("Deleted redacted Discord message {}", discord_message_id);
+
+        Ok(())
+    }
+
+    /// The channel a message bridged from Nostr was posted in, which in a forum is its sender's thread
+    fn message_channel(&self, discord_message_id: u64) -> ChannelId {
+        self.forum_channel_id
+            .and_then(|_| self.message_ids.get_nostr(discord_message_id))
+            .and_then(|nostr| nostr.author.to_bech32().ok())
+            .and_then(|npub| self.forum_threads.get(&npub))
+            .unwrap_or(self.channel_id)
+    }
+
+    /// Remembers which Nostr message a Discord message was posted for
+    fn record_message_id(&self, discord_message_id: u64, metadata: &NostrMessageMetadata) {
+        let event_id = metadata.event_id.as_deref().and_then(|id| EventId::from_hex(id).ok());
+        if let (Some(event_id), Ok(author)) = (event_id, PublicKey::parse(&metadata.pubkey)) {
+            self.message_ids.insert(discord_message_id, NostrMessageRef { event_id, author });
+        }
+    }
+}
+
+/// The ID in a webhook URL such as `https://discord.com/api/webhooks/<id>/<token>`
+fn webhook_id(webhook_url: &str) -> Option<WebhookId> {
+    let url = Url::parse(webhook_url).ok()?;
+    let mut segments = url.path_segments()?.skip_while(|segment| *segment != "webhooks").skip(1);
+    segments.next()?.parse().ok().map(WebhookId::new)
+}
+
+/// Mentions the Discord author a Nostr message replies to, ahead of the message text
+fn reply_mention(metadata: &NostrMessageMetadata) -> String {
+    metadata.reply_to_discord_user_id
+        .map(|user_id| format!("<@{}> ", user_id))
+        .unwrap_or_default()
+}
+
+/// The sender's name as a webhook username, marking a bare npub as a Nostr identity
+fn webhook_username(metadata: &NostrMessageMetadata) -> String {
+    let is_npub = metadata.username.starts_with("npub")
+        && (metadata.username.ends_with("...") || metadata.username == metadata.pubkey);
+    let username = if is_npub {
+        format!("Nostr: {}", metadata.username)
+    } else {
+        metadata.username.clone()
+    };
+    username.chars().take(WEBHOOK_USERNAME_MAX_CHARS).collect()
 }
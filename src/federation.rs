@@ -0,0 +1,187 @@
+use crate::message::BridgeMessage;
+use crate::priority::PrioritySender;
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, info_span, warn, Span};
+
+// Delay before reconnecting to a peer that dropped or refused the connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Messages held per peer while it is unreachable
+const PEER_QUEUE_SIZE: usize = 100;
+
+/// Forwards locally bridged Discord messages to peer Vecord instances over WebSockets
+#[derive(Clone)]
+pub struct FederationClient {
+    /// Names this instance in the messages it federates
+    source: String,
+    peers: Vec<(String, mpsc::Sender<String>)>,
+}
+
+impl FederationClient {
+    /// Spawns a connection task for each peer URL, reconnecting whenever a peer drops
+    ///
+    /// Every connection authenticates with `token`, the `FEDERATION_TOKEN` shared by all peers.
+    pub fn start(peer_urls: &[String], source: &str, token: String) -> Self {
+        let peers = peer_urls.iter().map(|url| {
+            let (sender, receiver) = mpsc::channel(PEER_QUEUE_SIZE);
+            tokio::spawn(run_peer(url.clone(), token.clone(), receiver));
+            (url.clone(), sender)
+        }).collect();
+
+        Self {
+            source: source.to_string(),
+            peers,
+        }
+    }
+
+    /// Sends a Discord message to every peer, unless it already came from another instance
+    pub fn broadcast(&self, message: &BridgeMessage) {
+        // Messages with a federation source were federated to us, re-broadcasting them would loop
+        let BridgeMessage::Discord { author, content, image, image_url, federation_source: None, guild_name, guild_icon_url, .. } = message else {
+            return;
+        };
+
+        let federated = BridgeMessage::Discord {
+            author: author.clone(),
+            content: content.clone(),
+            image: image.clone(),
+            image_url: image_url.clone(),
+            federation_source: Some(self.source.clone()),
+            guild_name: guild_name.clone(),
+            guild_icon_url: guild_icon_url.clone(),
+            // Discord users of another server can't be mentioned here
+            author_id: None,
+            message_id: None,
+            span: Span::none(),
+        };
+        let json = match serde_json::to_string(&federated) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize federated message: {}", e);
+                return;
+            }
+        };
+
+        for (url, sender) in &self.peers {
+            if sender.try_send(json.clone()).is_err() {
+                warn!("Federation queue for {} is full, dropping message", url);
+            }
+        }
+    }
+}
+
+/// Keeps a connection to one peer open, sending it every queued message
+async fn run_peer(url: String, token: String, mut outbound: mpsc::Receiver<String>) {
+    let mut request = match url.as_str().into_client_request() {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid federation peer URL {}: {}", url, e);
+            return;
+        }
+    };
+    match HeaderValue::from_str(&format!("Bearer {}", token)) {
+        Ok(value) => {
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+        Err(e) => {
+            warn!("FEDERATION_TOKEN can't be sent as a header: {}", e);
+            return;
+        }
+    }
+
+    loop {
+        match tokio_tungstenite::connect_async(request.clone()).await {
+            Ok((mut stream, _)) => {
+                info!("Connected to federation peer {}", url);
+                loop {
+                    let Some(json) = outbound.recv().await else {
+                        // The client was dropped, so nothing will be federated anymore
+                        return;
+                    };
+                    if let Err(e) = stream.send(Message::Text(json.into())).await {
+                        warn!("Lost connection to federation peer {}: {}", url, e);
+                        break;
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to connect to federation peer {}: {}", url, e),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Accepts peer connections on `addr`, queueing every federated message for delivery to Discord
+///
+/// Peers must present `token` as `Authorization: Bearer <token>` on the WebSocket upgrade, others get a 401.
+pub async fn listen(addr: &str, token: String, discord_senders: Vec<PrioritySender<BridgeMessage>>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Listening for federation peers on {}", addr);
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        let discord_senders = discord_senders.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_peer(stream, &token, &discord_senders).await {
+                warn!("Federation peer {} disconnected: {}", peer_addr, e);
+            }
+        });
+    }
+}
+
+async fn handle_peer(stream: TcpStream, token: &str, discord_senders: &[PrioritySender<BridgeMessage>]) -> Result<()> {
+    let expected = format!("Bearer {}", token);
+    // The error type is set by tungstenite's handshake callback
+    #[allow(clippy::result_large_err)]
+    let authorize = |request: &Request, response: Response| {
+        let authorized = request.headers()
+            .get(header::AUTHORIZATION)
+            .is_some_and(|value| value.as_bytes() == expected.as_bytes());
+        if authorized {
+            Ok(response)
+        } else {
+            let mut rejection = ErrorResponse::new(Some("Missing or wrong federation token".to_string()));
+            *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+            Err(rejection)
+        }
+    };
+    let mut stream = tokio_tungstenite::accept_hdr_async(stream, authorize).await?;
+
+    while let Some(frame) = stream.next().await {
+        let Message::Text(text) = frame? else {
+            continue;
+        };
+
+        let mut message: BridgeMessage = match serde_json::from_str(text.as_str()) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Ignoring malformed federated message: {}", e);
+                continue;
+            }
+        };
+
+        // Only Discord messages tagged with their origin are accepted from peers
+        let BridgeMessage::Discord { federation_source: Some(source), span, .. } = &mut message else {
+            warn!("Ignoring federated message without a federation source");
+            continue;
+        };
+        *span = info_span!("bridge_message", direction = "federation_to_discord", source = %source);
+
+        for sender in discord_senders {
+            if let Err(e) = sender.send(message.clone()).await {
+                warn!("Error queueing federated message for Discord: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use tracing::info;
+
+/// Sent to the author of a filtered message when `NOTIFY_FILTERED_SENDER` is set
+pub const FILTERED_NOTICE: &str = "Your message was not forwarded due to content restrictions.";
+
+/// Operator-defined patterns for content that must not cross the bridge
+#[derive(Clone, Default)]
+pub struct ContentFilter {
+    patterns: Vec<Regex>,
+}
+
+impl ContentFilter {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| anyhow!("Invalid content filter pattern '{}': {}", p, e)))
+            .collect::<Result<Vec<Regex>>>()?;
+
+        Ok(Self { patterns })
+    }
+
+    /// Returns true if the content matches any of the filter patterns
+    pub fn is_blocked(&self, content: &str) -> bool {
+        match self.patterns.iter().find(|p| p.is_match(content)) {
+            Some(pattern) => {
+                info!("Blocked message matching content filter '{}'", pattern.as_str());
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod admin;
+pub mod archive;
+pub mod config;
+pub mod crypto;
+pub mod discord;
+pub mod federation;
+pub mod filter;
+pub mod mapping;
+pub mod message;
+pub mod metadata;
+pub mod metrics;
+pub mod nostr;
+pub mod pause;
+pub mod persist;
+pub mod priority;
+pub mod sanitize;
+pub mod time;
+pub mod usernames;
@@ -1,48 +1,436 @@
-mod config;
-mod discord;
-mod message;
-mod metadata;
-mod nostr;
+use vecord::message::BridgeMessage;
 
-use message::BridgeMessage;
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand};
+use vecord::admin::{self, AdminBridge, AdminState};
+use vecord::archive::MessageArchive;
+use vecord::config::{Config, ConfigError};
+use vecord::crypto;
+use vecord::discord::{Delivery, DiscordBot, SharedContext};
+use vecord::federation::{self, FederationClient};
+use vecord::metadata::MetadataCache;
+use vecord::metrics;
+use vecord::nostr::{BridgeChannels, NostrClient, SubscriberList};
+use vecord::priority::{priority_channel, PrioritySender};
+use vecord::time::{self, LocalTimer};
+use vecord::usernames::UsernameMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use sd_notify::NotifyState;
+use std::time::Duration;
+use url::Url;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, warn, Instrument, Level};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use vector_sdk::nostr::{FromBech32, PublicKey, SecretKey};
+use nostr_sdk::EventId;
 
-use anyhow::Result;
-use config::Config;
-use discord::DiscordBot;
-use nostr::NostrClient;
-use tokio::sync::mpsc;
-use tracing::{error, info};
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to a TOML config file (missing fields fall back to the environment)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Log level (error, warn, info, debug, trace), overrides LOG_LEVEL
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Validate the configuration and connectivity, then exit without bridging (or DRY_RUN=true)
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Seed the metadata cache from a JSON export of user profiles, then exit
+    #[arg(long, value_name = "PATH")]
+    import_metadata: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encrypt a Nostr private key for NOSTR_PRIVATE_KEY_ENCRYPTED
+    EncryptKey {
+        /// The private key to encrypt (nsec or hex)
+        nsec: String,
+
+        /// Passphrase to set later as NOSTR_KEY_PASSPHRASE
+        #[arg(long)]
+        passphrase: String,
+    },
+
+    /// Exit 0 if the bridge is healthy and 1 otherwise, for Docker's HEALTHCHECK
+    ///
+    /// Asks the admin API's /health on HEALTH_PORT when set, otherwise checks that a configured relay is reachable.
+    HealthCheck,
+}
+
+// How long the health check waits for each relay to accept a TCP connection
+const HEALTH_CHECK_RELAY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reports whether the running bridge (or at least one of its relays) is healthy
+///
+/// Relays come from the same config the bridge loads, `config_path` or the environment and `VECORD_CONFIG_URL`,
+/// falling back to the `NOSTR_*RELAYS` variables when that config can't be loaded.
+async fn health_check(config_path: Option<&Path>) -> bool {
+    if let Ok(port) = std::env::var("HEALTH_PORT") {
+        let url = format!("http://localhost:{}/health", port.trim());
+        let health = match reqwest::Client::new().get(&url).send().await {
+            Ok(response) => response.json::<serde_json::Value>().await.ok(),
+            Err(_) => None,
+        };
+        return health.is_some_and(|health| health["status"] == "ok");
+    }
+
+    let config = match config_path {
+        Some(path) => Config::from_toml(path),
+        None => Config::new().await,
+    };
+    let relays: Vec<String> = match config {
+        Ok(config) => config.nostr_read_relays.iter()
+            .chain(&config.nostr_write_relays)
+            .chain(config.relay_configs.iter().map(|relay| &relay.url))
+            .chain(config.bridges.iter().flat_map(|bridge| &bridge.nostr_relays))
+            .cloned()
+            .collect(),
+        Err(_) => ["NOSTR_RELAYS", "NOSTR_READ_RELAYS", "NOSTR_WRITE_RELAYS"]
+            .iter()
+            .flat_map(|name| std::env::var(name).unwrap_or_default().split(',').map(str::to_string).collect::<Vec<_>>())
+            .collect(),
+    };
+    for relay in relays.iter().map(|r| r.trim()).filter(|r| !r.is_empty()) {
+        let Some(addr) = Url::parse(relay).ok().and_then(|url| {
+            Some(format!("{}:{}", url.host_str()?, url.port_or_known_default()?))
+        }) else {
+            continue;
+        };
+        let connect = tokio::net::TcpStream::connect(addr);
+        if matches!(tokio::time::timeout(HEALTH_CHECK_RELAY_TIMEOUT, connect).await, Ok(Ok(_))) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Creates the rolling file appender for `LOG_FILE`, rotated per `LOG_ROTATION`
+fn file_appender(log_file: &str, rotation: &str) -> Result<tracing_appender::rolling::RollingFileAppender> {
+    let path = Path::new(log_file);
+    let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let prefix = path.file_name()
+        .ok_or_else(|| anyhow!("LOG_FILE {} has no file name", log_file))?;
+
+    Ok(match rotation {
+        "daily" => tracing_appender::rolling::daily(directory, prefix),
+        "hourly" => tracing_appender::rolling::hourly(directory, prefix),
+        "never" => tracing_appender::rolling::never(directory, prefix),
+        other => return Err(anyhow!("LOG_ROTATION must be daily, hourly or never, got '{}'", other)),
+    })
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Utility subcommands run without loading the bridge configuration
+    if let Some(Command::EncryptKey { nsec, passphrase }) = &cli.command {
+        if SecretKey::from_bech32(nsec).is_err() && SecretKey::from_hex(nsec).is_err() {
+            return Err(anyhow!("The key to encrypt is neither a valid nsec nor hex key"));
+        }
+        println!("{}", crypto::encrypt_private_key(nsec, passphrase)?);
+        return Ok(());
+    }
+
+    // Load environment variables early so LOG_LEVEL can come from the .env file
+    dotenvy::dotenv().ok();
+
+    if let Some(Command::HealthCheck) = &cli.command {
+        std::process::exit(if health_check(cli.config.as_deref()).await { 0 } else { 1 });
+    }
+
+    // Load configuration first, since it decides where logs go
+    let config = match &cli.config {
+        Some(path) => Config::from_toml(path),
+        None => Config::new().await,
+    };
+    let config = match config {
+        Ok(config) => config,
+        // Logging isn't set up yet, so config errors are explained on stderr
+        Err(e) => match e.downcast_ref::<ConfigError>() {
+            Some(config_error) => {
+                eprintln!("{}\n{}", config_error, config_guidance(config_error));
+                std::process::exit(1);
+            }
+            None => return Err(e),
+        },
+    };
+
     // Initialize logging
-    tracing_subscriber::fmt::init();
+    let log_level = match cli.log_level.or_else(|| std::env::var("LOG_LEVEL").ok()) {
+        Some(level) => level.parse::<Level>()
+            .map_err(|_| anyhow!("Invalid log level '{}'", level))?,
+        None => Level::INFO,
+    };
+    // Log lines are stamped in the configured zone
+    let timezone = time::parse_timezone(&config.timezone)?;
+    // The guard flushes buffered file logs on drop, so it must live until main returns
+    let (file_layer, _log_guard) = match &config.log_file {
+        Some(log_file) => {
+            let (writer, guard) = tracing_appender::non_blocking(file_appender(log_file, &config.log_rotation)?);
+            (Some(tracing_subscriber::fmt::layer().with_ansi(false).with_timer(LocalTimer(timezone)).with_writer(writer)), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(LevelFilter::from_level(log_level))
+        .with(tracing_subscriber::fmt::layer().with_timer(LocalTimer(timezone)))
+        .with(file_layer)
+        .init();
     info!("Starting Vecord - Vector <-> Discord bridge");
-
-    // Load configuration
-    let config = Config::new()?;
     info!("Configuration loaded");
 
+    // Validate-only mode: check everything and exit without bridging
+    let dry_run = cli.dry_run || std::env::var("DRY_RUN").is_ok_and(|v| v.eq_ignore_ascii_case("true"));
+    if dry_run {
+        let passed = DryRunChecker::new(&config).run().await;
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    for warning in config.validate()? {
+        warn!("{}", warning);
+    }
+
+    // One-time migration: merge exported profiles into the cache and exit
+    if let Some(path) = &cli.import_metadata {
+        let cache = MetadataCache::new(config.metadata_cache_file.clone())?;
+        let imported = cache.import_from_json(path).await?;
+        // Write once more so the process can't exit before the background saves land
+        cache.save_to_file_async().await;
+        info!("Imported {} profiles into the metadata cache", imported);
+        return Ok(());
+    }
+
+    // Share locally bridged Discord messages with peer instances
+    let federation = (!config.federation_peers.is_empty())
+        .then(|| FederationClient::start(&config.federation_peers, &config.bot_name, config.federation_token.clone().unwrap_or_default()));
+
+    // Start every bridge's Nostr side, collecting the Discord channels they listen on
+    let mut discord_routes = HashMap::new();
+    let mut bridges = Vec::new();
+    let discord_context = SharedContext::default();
+    let archive = match &config.archive_file {
+        Some(path) => Some(MessageArchive::open(path).await?),
+        None => None,
+    };
+    let channels = BridgeChannels::default();
+    let usernames = UsernameMap::new(config.username_map_file.clone());
+    for bridge_config in config.bridge_configs() {
+        let bridge = start_bridge(
+            &bridge_config,
+            federation.clone(),
+            discord_context.clone(),
+            archive.clone(),
+            channels.clone(),
+            usernames.clone(),
+        ).await?;
+        discord_routes.insert(bridge_config.discord_channel_id, bridge.discord_to_nostr_tx.clone());
+        bridges.push(bridge);
+    }
+    info!("Started {} bridge(s)", bridges.len());
+
+    // Messages federated from peers are posted to every local bridge's Discord channel
+    if let Some(addr) = config.federation_listen_addr.clone() {
+        let discord_senders = bridges.iter().map(|b| b.nostr_to_discord_tx.clone()).collect();
+        let token = config.federation_token.clone().unwrap_or_default();
+        tokio::spawn(async move {
+            if let Err(e) = federation::listen(&addr, token, discord_senders).await {
+                error!("Federation listener stopped: {}", e);
+            }
+        });
+    }
+
+    // Prometheus scrapes the counters without authorization, whether or not the admin API runs
+    if let Some(addr) = config.metrics_listen_addr.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&addr).await {
+                error!("Metrics listener stopped: {}", e);
+            }
+        });
+    }
+
+    // Dashboards read every bridge's relays and subscribers through the admin API
+    if let Some(addr) = config.admin_addr() {
+        // Only the configured admins may sign requests, the bridge's own key is not one of them
+        let admin_pubkeys = config.admin_pubkeys.iter()
+            .filter_map(|pubkey| PublicKey::parse(pubkey).ok())
+            .collect();
+        let state = AdminState::new(admin_pubkeys, bridges.iter().map(|b| b.admin.clone()).collect())
+            .with_archive(archive.clone());
+        tokio::spawn(async move {
+            if let Err(e) = admin::serve(&addr, state).await {
+                error!("Admin API stopped: {}", e);
+            }
+        });
+    }
+
+    // Tell systemd we're up once Discord is ready, then keep its status line current
+    let (ready_tx, ready_rx) = oneshot::channel();
+    tokio::spawn(notify_systemd(ready_rx, bridges.iter().map(|b| b.admin.subscribers.clone()).collect()));
+
+    let bridging = async {
+        if config.discord_token.is_some() {
+            // One gateway connection serves every bridge, routing messages by channel
+            info!("Starting Discord bot");
+            bridges[0].discord_bot.start_with_routes(discord_routes, Some(ready_tx)).await
+        } else {
+            // Webhook-only mode: Nostr -> Discord keeps running, but nothing is read from Discord
+            info!("No DISCORD_TOKEN configured, running in webhook-only mode");
+            let _ = ready_tx.send(());
+            std::future::pending().await
+        }
+    };
+
+    // Start Discord bot (this is a blocking call) until it fails or we're asked to stop
+    let result = tokio::select! {
+        result = bridging => result,
+        _ = shutdown_signal() => {
+            info!("Shutting down");
+            Ok(())
+        }
+    };
+    let _ = sd_notify::notify(false, &[NotifyState::Stopping]);
+    result
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM from a service manager
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Sends `READY=1` once the bridge is up, then refreshes the status whenever subscribers change
+///
+/// Outside a `Type=notify` service there is no notify socket and every call is a no-op.
+async fn notify_systemd(ready: oneshot::Receiver<()>, subscriber_lists: Vec<SubscriberList>) {
+    if ready.await.is_err() {
+        // Discord never became ready, so main is already returning an error
+        return;
+    }
+
+    // Merge every bridge's subscriber changes so any of them refreshes the status
+    let (changed_tx, mut changed_rx) = mpsc::channel(1);
+    for subscribers in &subscriber_lists {
+        let mut events = subscribers.events();
+        let changed_tx = changed_tx.clone();
+        tokio::spawn(async move {
+            while !matches!(events.recv().await, Err(RecvError::Closed)) {
+                let _ = changed_tx.try_send(());
+            }
+        });
+    }
+
+    let mut first = true;
+    loop {
+        let mut count = 0;
+        for subscribers in &subscriber_lists {
+            count += subscribers.count().await;
+        }
+        let status = format!("Bridging {} subscribers", count);
+        let states = [NotifyState::Ready, NotifyState::Status(&status)];
+        // READY=1 is only sent the first time, later updates just change the status
+        let states = if first { &states[..] } else { &states[1..] };
+        if let Err(e) = sd_notify::notify(false, states) {
+            warn!("Failed to notify systemd: {}", e);
+        }
+
+        first = false;
+        if changed_rx.recv().await.is_none() {
+            return;
+        }
+    }
+}
+
+/// One running bridge between a Discord channel and a Nostr identity
+struct Bridge {
+    discord_bot: DiscordBot,
+    /// Kept alive for as long as the bridge runs
+    _nostr_client: NostrClient,
+    discord_to_nostr_tx: PrioritySender<BridgeMessage>,
+    nostr_to_discord_tx: PrioritySender<BridgeMessage>,
+    admin: AdminBridge,
+}
+
+/// Starts one bridge's Nostr client and forwarding tasks
+///
+/// `discord_context` is filled in by the first bridge's gateway and shared by every bridge's Discord bot,
+/// as is the `archive` delivered messages are stored in. Named bridges register in `channels`,
+/// and Discord usernames registered through any bridge are kept in `usernames`.
+async fn start_bridge(
+    config: &Config,
+    federation: Option<FederationClient>,
+    discord_context: SharedContext,
+    archive: Option<MessageArchive>,
+    channels: BridgeChannels,
+    usernames: UsernameMap,
+) -> Result<Bridge> {
     // Create bi-directional channels for message passing
-    let (discord_to_nostr_tx, mut discord_to_nostr_rx) = mpsc::channel::<BridgeMessage>(100);
-    let (nostr_to_discord_tx, mut nostr_to_discord_rx) = mpsc::channel::<BridgeMessage>(100);
+    let (discord_to_nostr_tx, mut discord_to_nostr_rx) = priority_channel::<BridgeMessage>(100);
+    let (nostr_to_discord_tx, mut nostr_to_discord_rx) = priority_channel::<BridgeMessage>(100);
 
     // Initialize Discord bot
-    let discord_bot = DiscordBot::new(&config);
+    let discord_bot = DiscordBot::new(config)
+        .with_context(discord_context)
+        .with_usernames(usernames.clone());
     
     // Clone discord_bot for the receiver task
     let discord_bot_clone = discord_bot.clone();
 
     // Initialize Nostr client
-    let mut nostr_client = NostrClient::new(&config)?;
+    let mut nostr_client = NostrClient::new(config)?
+        .with_message_ids(discord_bot.message_ids())
+        .with_channels(channels)
+        .with_usernames(usernames)
+        .with_archive(archive.clone())
+        .with_discord_to_nostr(discord_to_nostr_tx.clone());
     
     // Start Nostr client and get sender channel
-    let nostr_sender = nostr_client.start(nostr_to_discord_tx).await?;
-    info!("Nostr client initialized");
+    let nostr_sender = nostr_client.start(nostr_to_discord_tx.clone()).await?;
+    info!("Nostr client initialized for Discord channel {}", config.discord_channel_id);
+
+    // Discord deliveries are counted here, the Nostr client counts the rest
+    let stats = nostr_client.stats();
+
+    // Admins can pause bridging, which holds messages in both directions
+    let bridge_pause = nostr_client.bridge_pause();
+    let bridge_pause_clone = bridge_pause.clone();
+    let archive_clone = archive.clone();
+    let message_ids = discord_bot.message_ids();
 
     // Spawn a task to forward messages from Discord to Nostr
     tokio::spawn(async move {
         while let Some(message) = discord_to_nostr_rx.recv().await {
+            if bridge_pause.is_paused() {
+                bridge_pause.hold(message);
+                continue;
+            }
+            if let Some(federation) = &federation {
+                federation.broadcast(&message);
+            }
             if let Err(e) = nostr_sender.send(message).await {
                 error!("Error forwarding message to Nostr: {}", e);
             }
@@ -52,15 +440,206 @@ async fn main() -> Result<()> {
     // Spawn a task to forward messages from Nostr to Discord
     tokio::spawn(async move {
         while let Some(message) = nostr_to_discord_rx.recv().await {
-            if let Err(e) = discord_bot_clone.send_message(&message).await {
-                error!("Error forwarding message to Discord: {}", e);
+            if bridge_pause_clone.is_paused() {
+                bridge_pause_clone.hold(message);
+                continue;
             }
+            let span = message.span().clone();
+            async {
+                match discord_bot_clone.send_message(&message).await {
+                    // Only messages that reached Discord are counted and archived, flushed ones included
+                    Ok(Delivery::Sent { flushed }) => {
+                        for delivered in std::iter::once(&message).chain(&flushed) {
+                            if matches!(delivered, BridgeMessage::Nostr { .. }) {
+                                stats.nostr_to_discord_total.inc();
+                            }
+                            info!("Delivered message to Discord");
+
+                            if let Some(archive) = &archive_clone {
+                                let discord_message_id = match delivered {
+                                    BridgeMessage::Nostr { metadata, .. } => metadata.event_id.as_deref()
+                                        .and_then(|id| EventId::from_hex(id).ok())
+                                        .and_then(|id| message_ids.get_discord(&id)),
+                                    _ => None,
+                                };
+                                if let Err(e) = archive.insert(delivered, discord_message_id).await {
+                                    warn!("Failed to archive message: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    Ok(Delivery::Buffered) => debug!("Buffered message while Discord is unavailable"),
+                    Err(e) => {
+                        stats.failed_sends_total.inc();
+                        error!("Error forwarding message to Discord: {}", e);
+                    }
+                }
+            }
+            .instrument(span)
+            .await;
         }
     });
 
-    // Start Discord bot (this is a blocking call)
-    info!("Starting Discord bot");
-    discord_bot.start(discord_to_nostr_tx).await?;
+    let admin = AdminBridge {
+        subscribers: nostr_client.subscribers(),
+        metadata_cache: nostr_client.metadata_cache(),
+        relay_monitor: nostr_client.relay_monitor(),
+        bridge_pause: nostr_client.bridge_pause(),
+        bot: nostr_client.current_bot(),
+        notices: Some(discord_to_nostr_tx.clone()),
+    };
+
+    Ok(Bridge {
+        admin,
+        discord_bot,
+        _nostr_client: nostr_client,
+        discord_to_nostr_tx,
+        nostr_to_discord_tx,
+    })
+}
+
+/// How to fix a config error, for operators setting up the bridge
+fn config_guidance(error: &ConfigError) -> String {
+    match error {
+        ConfigError::MissingRequired(name) => match name.as_str() {
+            "DISCORD_TOKEN" => "Set DISCORD_TOKEN to your bot's token from the Discord developer portal, \
+                or DISCORD_WEBHOOK_URL to only send through a webhook.".to_string(),
+            "DISCORD_CHANNEL_ID" => "Set DISCORD_CHANNEL_ID to the channel to bridge, copied from Discord \
+                with Developer Mode enabled.".to_string(),
+            "NOSTR_PRIVATE_KEY" => "Set NOSTR_PRIVATE_KEY to the bridge's nsec or hex key, \
+                or NOSTR_BUNKER_URL to sign through a NIP-46 bunker.".to_string(),
+            "NOSTR_KEY_PASSPHRASE" => "Set NOSTR_KEY_PASSPHRASE to the passphrase NOSTR_PRIVATE_KEY_ENCRYPTED \
+                was encrypted with.".to_string(),
+            "NOSTR_RELAYS" => "Set NOSTR_RELAYS to a comma-separated list of relays, \
+                such as wss://jskitty.cat/nostr,wss://relay.damus.io.".to_string(),
+            name => format!("Set {} in the environment or the config file.", name),
+        },
+        ConfigError::ParseError { field, .. } | ConfigError::InvalidValue { name: field, .. } => {
+            format!("Correct {}, see .env.example for its format.", field)
+        }
+        ConfigError::Incompatible(first, second) => format!("Unset either {} or {}.", first, second),
+    }
+}
+
+/// Outcome of a single dry-run check
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Validates the configuration and connectivity without starting the bridge
+struct DryRunChecker<'a> {
+    config: &'a Config,
+    results: Vec<(CheckStatus, String, String)>,
+}
+
+impl<'a> DryRunChecker<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            results: Vec::new(),
+        }
+    }
+
+    /// Runs every check and prints the report, returning true if all required checks passed
+    async fn run(mut self) -> bool {
+        self.check_config();
+        self.check_private_key();
+        self.check_discord_token().await;
+        self.check_relays().await;
+        self.print_report()
+    }
+
+    fn record(&mut self, status: CheckStatus, name: impl Into<String>, details: impl Into<String>) {
+        self.results.push((status, name.into(), details.into()));
+    }
+
+    fn check_config(&mut self) {
+        match self.config.validate() {
+            Ok(warnings) => {
+                self.record(CheckStatus::Pass, "Configuration", "valid");
+                for warning in warnings {
+                    self.record(CheckStatus::Warn, "Configuration", warning);
+                }
+            }
+            Err(e) => self.record(CheckStatus::Fail, "Configuration", e.to_string()),
+        }
+    }
+
+    fn check_private_key(&mut self) {
+        let key = &self.config.nostr_private_key;
+        if key.is_empty() && self.config.nostr_bunker_url.is_some() {
+            self.record(CheckStatus::Pass, "Nostr private key", "not needed, signing through NOSTR_BUNKER_URL");
+        } else if SecretKey::from_bech32(key).is_ok() {
+            self.record(CheckStatus::Pass, "Nostr private key", "valid nsec");
+        } else if SecretKey::from_hex(key).is_ok() {
+            self.record(CheckStatus::Pass, "Nostr private key", "valid hex");
+        } else {
+            self.record(CheckStatus::Fail, "Nostr private key", "neither a valid nsec nor hex key");
+        }
+    }
+
+    async fn check_discord_token(&mut self) {
+        let Some(token) = &self.config.discord_token else {
+            self.record(CheckStatus::Warn, "Discord token", "not set, skipped");
+            return;
+        };
+
+        // Bot tokens use the `Bot` authorization scheme rather than `Bearer`
+        let result = reqwest::Client::new()
+            .get("https://discord.com/api/v10/users/@me")
+            .header(reqwest::header::AUTHORIZATION, format!("Bot {}", token))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await;
+
+        match result {
+            Ok(resp) if resp.status().is_success() => {
+                let name = resp.json::<serde_json::Value>().await.ok()
+                    .and_then(|user| user["username"].as_str().map(String::from))
+                    .unwrap_or_default();
+                self.record(CheckStatus::Pass, "Discord token", format!("logged in as {}", name));
+            }
+            Ok(resp) => self.record(CheckStatus::Fail, "Discord token", format!("rejected with {}", resp.status())),
+            Err(e) => self.record(CheckStatus::Fail, "Discord token", e.to_string()),
+        }
+    }
+
+    async fn check_relays(&mut self) {
+        for relay in &self.config.nostr_relays() {
+            let name = format!("Relay {}", relay);
+            let handshake = tokio::time::timeout(Duration::from_secs(5), tokio_tungstenite::connect_async(relay.as_str())).await;
+
+            match handshake {
+                Ok(Ok((mut stream, _))) => {
+                    let _ = stream.close(None).await;
+                    self.record(CheckStatus::Pass, name, "WebSocket handshake succeeded");
+                }
+                Ok(Err(e)) => self.record(CheckStatus::Fail, name, e.to_string()),
+                Err(_) => self.record(CheckStatus::Fail, name, "timed out after 5s"),
+            }
+        }
+    }
+
+    fn print_report(&self) -> bool {
+        println!();
+        println!("{:<6} {:<48} DETAILS", "RESULT", "CHECK");
+
+        let mut all_passed = true;
+        for (status, name, details) in &self.results {
+            let label = match status {
+                CheckStatus::Pass => "\x1b[32mPASS\x1b[0m",
+                CheckStatus::Warn => "\x1b[33mWARN\x1b[0m",
+                CheckStatus::Fail => {
+                    all_passed = false;
+                    "\x1b[31mFAIL\x1b[0m"
+                }
+            };
+            println!("{}   {:<48} {}", label, name, details);
+        }
+        println!();
 
-    Ok(())
+        all_passed
+    }
 }
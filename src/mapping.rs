@@ -0,0 +1,72 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use nostr_sdk::EventId;
+use vector_sdk::nostr::PublicKey;
+
+// Bridged messages remembered for reactions, the oldest are forgotten first
+const MESSAGE_ID_MAPPING_CAPACITY: usize = 10_000;
+
+/// The Nostr message a Discord message was bridged from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NostrMessageRef {
+    pub event_id: EventId,
+    pub author: PublicKey,
+}
+
+#[derive(Default)]
+struct Mappings {
+    to_nostr: HashMap<u64, NostrMessageRef>,
+    to_discord: HashMap<EventId, u64>,
+    /// Discord message IDs in the order they were recorded, for eviction
+    order: VecDeque<u64>,
+    /// Discord authors of the DMs subscribers received, keyed by rumor ID
+    to_discord_author: HashMap<EventId, u64>,
+    /// Rumor IDs in the order they were recorded, for eviction
+    author_order: VecDeque<EventId>,
+}
+
+/// Links bridged Nostr messages to the Discord messages they were posted as, so reactions can follow them
+#[derive(Clone, Default)]
+pub struct MessageIdMapping {
+    mappings: Arc<Mutex<Mappings>>,
+}
+
+impl MessageIdMapping {
+    pub fn insert(&self, discord_message_id: u64, nostr: NostrMessageRef) {
+        let mut mappings = self.mappings.lock().unwrap();
+        if mappings.order.len() >= MESSAGE_ID_MAPPING_CAPACITY {
+            if let Some(oldest) = mappings.order.pop_front() {
+                if let Some(evicted) = mappings.to_nostr.remove(&oldest) {
+                    mappings.to_discord.remove(&evicted.event_id);
+                }
+            }
+        }
+        mappings.to_nostr.insert(discord_message_id, nostr);
+        mappings.to_discord.insert(nostr.event_id, discord_message_id);
+        mappings.order.push_back(discord_message_id);
+    }
+
+    pub fn get_nostr(&self, discord_message_id: u64) -> Option<NostrMessageRef> {
+        self.mappings.lock().unwrap().to_nostr.get(&discord_message_id).copied()
+    }
+
+    pub fn get_discord(&self, event_id: &EventId) -> Option<u64> {
+        self.mappings.lock().unwrap().to_discord.get(event_id).copied()
+    }
+
+    /// Remembers the Discord author of a message a subscriber received as rumor `event_id`
+    pub fn insert_discord_author(&self, event_id: EventId, user_id: u64) {
+        let mut mappings = self.mappings.lock().unwrap();
+        if mappings.author_order.len() >= MESSAGE_ID_MAPPING_CAPACITY {
+            if let Some(oldest) = mappings.author_order.pop_front() {
+                mappings.to_discord_author.remove(&oldest);
+            }
+        }
+        mappings.to_discord_author.insert(event_id, user_id);
+        mappings.author_order.push_back(event_id);
+    }
+
+    pub fn get_discord_author(&self, event_id: &EventId) -> Option<u64> {
+        self.mappings.lock().unwrap().to_discord_author.get(event_id).copied()
+    }
+}
@@ -1,10 +1,34 @@
 use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::Span;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NostrMessageMetadata {
     pub username: String,
     pub pubkey: String,
     pub avatar_url: Option<String>,
+    /// The sender's profile banner, shown as the embed image
+    #[serde(default)]
+    pub banner_url: Option<String>,
+    /// When the sender wrote the message, as Unix seconds
+    #[serde(default)]
+    pub event_created_at: Option<u64>,
+    /// Hex ID of the message's rumor (or NIP-04 event), which reactions reference
+    #[serde(default)]
+    pub event_id: Option<String>,
+    /// The sender's Lightning address, for tips
+    #[serde(default)]
+    pub lud16: Option<String>,
+    /// The sender's personal site
+    #[serde(default)]
+    pub website: Option<String>,
+    /// Discord user whose bridged message this replies to, mentioned so they are notified
+    #[serde(default)]
+    pub reply_to_discord_user_id: Option<u64>,
+    /// Relay the message's event arrived from
+    #[serde(default)]
+    pub relay_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +37,31 @@ pub struct ImageAttachment {
     pub extension: String,
 }
 
+/// Bridge events announced to subscribers rather than relayed from a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SystemEventType {
+    /// The bot can no longer read or post in the bridged Discord channel
+    ChannelUnavailable,
+    /// The bot regained access to the bridged Discord channel
+    ChannelRestored,
+    /// A member joined the bridged Discord server
+    MemberJoin,
+    /// A member left the bridged Discord server
+    MemberLeave,
+    /// The bridge's Nostr notification stream closed and was re-established
+    RelayReconnected,
+    /// An admin changed the relays the bridge uses
+    RelayListChanged,
+    /// An admin removed a bridged message with `!redact`
+    MessageRedacted,
+    /// An admin paused the bridge with `!pause`
+    BridgePaused,
+    /// An admin resumed the bridge with `!resume`
+    BridgeResumed,
+    /// An admin moved the bridge to a new key with `!rotate-key`
+    KeyRotated,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BridgeMessage {
     /// From Discord to Nostr
@@ -21,11 +70,103 @@ pub enum BridgeMessage {
         content: String,
         /// Optional first image attachment (bytes + file extension such as "png", "jpg")
         image: Option<ImageAttachment>,
+        /// Image forwarded by URL rather than downloaded, such as a sticker
+        #[serde(default)]
+        image_url: Option<String>,
+        /// Name of the peer instance this message was federated from, unset for local messages
+        #[serde(default)]
+        federation_source: Option<String>,
+        /// Name of the Discord server the message was sent in, telling apart bridges of several servers
+        #[serde(default)]
+        guild_name: Option<String>,
+        /// Icon of that server, shown when the message is posted back to Discord
+        #[serde(default)]
+        guild_icon_url: Option<String>,
+        /// Discord user ID of the author, unset for federated messages
+        #[serde(default)]
+        author_id: Option<u64>,
+        /// ID of the Discord message itself, unset for federated messages
+        #[serde(default)]
+        message_id: Option<u64>,
+        /// Tracks the message from the Discord handler until delivery to Nostr
+        #[serde(skip, default = "Span::none")]
+        span: Span,
     },
     
     /// From Nostr to Discord
     Nostr {
         content: String,
         metadata: NostrMessageMetadata,
+        /// Tracks the message from the notification handler until delivery to Discord
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+
+    /// From a Discord user reacting to a message that was bridged from Nostr
+    DiscordReaction {
+        discord_message_id: u64,
+        emoji: String,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+
+    /// From a Nostr subscriber reacting to a message that was bridged from Nostr
+    NostrReaction {
+        discord_message_id: u64,
+        emoji: String,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+
+    /// From the bridge, marking a Discord message with how its delivery to subscribers went
+    DeliveryReceipt {
+        discord_message_id: u64,
+        emoji: String,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+
+    /// From Discord, a message mentioning a user registered with `!register-discord`, DMed only to them
+    Mention {
+        /// Hex pubkey of the mentioned user
+        pubkey: String,
+        author: String,
+        content: String,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+
+    /// From an admin's `!redact`, deleting the Discord message a Nostr message was posted as
+    Redaction {
+        discord_message_id: u64,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
     },
+
+    /// From the bridge itself, delivered to every Nostr subscriber
+    System {
+        event_type: SystemEventType,
+        message: String,
+        /// Notified once every subscriber has been sent the notice
+        #[serde(skip)]
+        delivered: Option<Arc<Notify>>,
+        #[serde(skip, default = "Span::none")]
+        span: Span,
+    },
+}
+
+impl BridgeMessage {
+    /// The `bridge_message` span this message's journey is recorded under
+    pub fn span(&self) -> &Span {
+        match self {
+            BridgeMessage::Discord { span, .. }
+            | BridgeMessage::Nostr { span, .. }
+            | BridgeMessage::DiscordReaction { span, .. }
+            | BridgeMessage::NostrReaction { span, .. }
+            | BridgeMessage::DeliveryReceipt { span, .. }
+            | BridgeMessage::Mention { span, .. }
+            | BridgeMessage::Redaction { span, .. }
+            | BridgeMessage::System { span, .. } => span,
+        }
+    }
 }
@@ -1,7 +1,11 @@
+use crate::nostr::pubkey_to_bech32_or_fail;
+use crate::persist;
 use anyhow::{Result, anyhow};
-use vector_sdk::nostr::{Client, PublicKey, Metadata, Event, ToBech32};
+use futures_util::stream::{self, StreamExt};
+use vector_sdk::nostr::{Client, PublicKey, Metadata, Event};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
@@ -11,50 +15,118 @@ use serde::{Deserialize, Serialize};
 // How long to cache metadata before refreshing (1 day)
 const CACHE_LIFETIME: Duration = Duration::from_secs(60 * 60 * 24);
 
+// Profiles fetched at once by `refresh_stale`
+const REFRESH_CONCURRENCY: usize = 10;
+
+// Schema of the cache file, bumped whenever `UserMetadata` changes, see `migrate_entry`
+const METADATA_CACHE_VERSION: u8 = 2;
+
+/// The metadata cache as persisted, version 1 files are a bare map of the entries
+#[derive(Serialize)]
+struct MetadataCacheFile<'a> {
+    version: u8,
+    entries: &'a HashMap<String, UserMetadata>,
+}
+
+/// Upgrades a cached profile written by schema `version` to the next version
+fn migrate_entry(version: u8, entry: &mut serde_json::Map<String, serde_json::Value>) {
+    if version == 1 {
+        // Version 2 added the banner, Lightning and website fields
+        for field in ["banner_url", "lud16", "lud06", "website"] {
+            entry.entry(field).or_insert(serde_json::Value::Null);
+        }
+    }
+}
+
+/// Parses a cache file of any version, migrating its entries to the current one
+fn parse_cache_file(contents: &str) -> Result<HashMap<String, UserMetadata>> {
+    let mut value: serde_json::Value = serde_json::from_str(contents)?;
+    let version = match value.get("version").and_then(|version| version.as_u64()) {
+        Some(version) => u8::try_from(version).map_err(|_| anyhow!("Unknown metadata cache version {}", version))?,
+        None => 1,
+    };
+    if version > METADATA_CACHE_VERSION {
+        return Err(anyhow!("Metadata cache version {} is newer than this bridge supports", version));
+    }
+
+    let mut entries = if version == 1 { value } else { value["entries"].take() };
+    if version < METADATA_CACHE_VERSION {
+        if let Some(entries) = entries.as_object_mut() {
+            for entry in entries.values_mut().filter_map(|entry| entry.as_object_mut()) {
+                for from in version..METADATA_CACHE_VERSION {
+                    migrate_entry(from, entry);
+                }
+            }
+        }
+        info!("Migrated metadata cache from v{} to v{}.", version, METADATA_CACHE_VERSION);
+    }
+    Ok(serde_json::from_value(entries)?)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserMetadata {
     pub pubkey: String,
     pub name: Option<String>,
     pub display_name: Option<String>,
     pub picture: Option<String>,
+    /// Wide cover image from the profile's `banner` field
+    #[serde(default)]
+    pub banner_url: Option<String>,
     pub nip05: Option<String>,
     pub about: Option<String>,
+    /// Lightning address (`user@domain`) for tips
+    #[serde(default)]
+    pub lud16: Option<String>,
+    /// LNURL for tips, from profiles without a Lightning address
+    #[serde(default)]
+    pub lud06: Option<String>,
+    /// The user's personal site
+    #[serde(default)]
+    pub website: Option<String>,
     pub last_updated: u64,
 }
 
 impl UserMetadata {
-    pub fn new(pubkey: &PublicKey) -> Self {
-        Self {
-            pubkey: pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_string()),
+    pub fn new(pubkey: &PublicKey) -> Result<Self> {
+        Ok(Self {
+            pubkey: pubkey_to_bech32_or_fail(pubkey)?,
             name: None,
             display_name: None,
             picture: None,
+            banner_url: None,
             nip05: None,
             about: None,
+            lud16: None,
+            lud06: None,
+            website: None,
             last_updated: 0,
-        }
+        })
     }
 
-    pub fn from_metadata(pubkey: &PublicKey, metadata: Metadata) -> Self {
-        Self {
-            pubkey: pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_string()),
+    pub fn from_metadata(pubkey: &PublicKey, metadata: Metadata) -> Result<Self> {
+        Ok(Self {
+            pubkey: pubkey_to_bech32_or_fail(pubkey)?,
             name: metadata.name,
             display_name: metadata.display_name,
             picture: metadata.picture,
+            banner_url: metadata.banner,
             nip05: metadata.nip05,
             about: metadata.about,
+            lud16: metadata.lud16,
+            lud06: metadata.lud06,
+            website: metadata.website,
             last_updated: SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
-        }
+        })
     }
 
     pub fn from_event(pubkey: &PublicKey, event: &Event) -> Result<Self> {
         let metadata = serde_json::from_str::<Metadata>(&event.content)
             .map_err(|e| anyhow!("Failed to parse metadata: {}", e))?;
         
-        Ok(Self::from_metadata(pubkey, metadata))
+        Self::from_metadata(pubkey, metadata)
     }
 
     pub fn get_best_name(&self) -> String {
@@ -77,11 +149,7 @@ impl UserMetadata {
         }
 
         // If no name is available, use the pubkey (shortened)
-        if self.pubkey.starts_with("npub") && self.pubkey.len() > 12 {
-            format!("{}...", &self.pubkey[0..12])
-        } else {
-            self.pubkey.clone()
-        }
+        short_pubkey(&self.pubkey)
     }
 
     pub fn needs_refresh(&self) -> bool {
@@ -100,10 +168,21 @@ impl UserMetadata {
     }
 }
 
+/// An npub cut down to its first 12 characters, other pubkeys are returned as they are
+pub fn short_pubkey(pubkey: &str) -> String {
+    if pubkey.starts_with("npub") && pubkey.len() > 12 {
+        format!("{}...", &pubkey[0..12])
+    } else {
+        pubkey.to_string()
+    }
+}
+
 #[derive(Clone)]
 pub struct MetadataCache {
     cache: Arc<Mutex<HashMap<String, UserMetadata>>>,
     file_path: Option<String>,
+    /// Serializes background saves so concurrent writes can't interleave
+    save_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl MetadataCache {
@@ -114,7 +193,7 @@ impl MetadataCache {
         if let Some(path) = &file_path {
             if Path::new(path).exists() {
                 if let Ok(file_content) = fs::read_to_string(path) {
-                    match serde_json::from_str::<HashMap<String, UserMetadata>>(&file_content) {
+                    match parse_cache_file(&file_content) {
                         Ok(loaded_cache) => {
                             info!("Loaded metadata cache with {} entries", loaded_cache.len());
                             cache = loaded_cache;
@@ -130,11 +209,12 @@ impl MetadataCache {
         Ok(Self {
             cache: Arc::new(Mutex::new(cache)),
             file_path,
+            save_lock: Arc::new(tokio::sync::Mutex::new(())),
         })
     }
 
     pub fn get(&self, pubkey: &PublicKey) -> Option<UserMetadata> {
-        let key = pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_string());
+        let key = pubkey_to_bech32_or_fail(pubkey).ok()?;
         let cache = self.cache.lock().unwrap();
         cache.get(&key).cloned()
     }
@@ -145,22 +225,92 @@ impl MetadataCache {
             let mut cache = self.cache.lock().unwrap();
             cache.insert(key, metadata);
         }
-        // Save to file after releasing the lock
-        self.save_to_file();
+        // Save to file in the background after releasing the lock
+        if self.file_path.is_some() {
+            let cache = self.clone();
+            tokio::spawn(async move { cache.save_to_file_async().await });
+        }
+    }
+
+    /// Drops a cached profile so the next lookup fetches it again
+    pub fn invalidate(&self, pubkey: &PublicKey) {
+        let Ok(key) = pubkey_to_bech32_or_fail(pubkey) else {
+            return;
+        };
+        let removed = self.cache.lock().unwrap().remove(&key).is_some();
+        if removed && self.file_path.is_some() {
+            let cache = self.clone();
+            tokio::spawn(async move { cache.save_to_file_async().await });
+        }
+    }
+
+    /// The cached profiles of `pubkeys`, in order, looked up under a single lock
+    pub fn get_many(&self, pubkeys: &[PublicKey]) -> Vec<Option<UserMetadata>> {
+        let keys: Vec<Option<String>> = pubkeys.iter().map(|pubkey| pubkey_to_bech32_or_fail(pubkey).ok()).collect();
+        let cache = self.cache.lock().unwrap();
+        keys.into_iter()
+            .map(|key| key.and_then(|key| cache.get(&key).cloned()))
+            .collect()
+    }
+
+    /// Snapshot of every cached profile, cloned so the lock isn't held by the caller
+    pub fn get_all(&self) -> Vec<UserMetadata> {
+        let cache = self.cache.lock().unwrap();
+        cache.values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.lock().unwrap().is_empty()
     }
 
-    fn save_to_file(&self) {
+    /// Seeds the cache from a JSON array of profiles, keeping whichever copy is newer
+    pub async fn import_from_json(&self, path: &Path) -> Result<usize> {
+        let contents = tokio::fs::read_to_string(path).await
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let profiles: Vec<UserMetadata> = serde_json::from_str(&contents)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+        let mut imported = 0;
+        for profile in profiles {
+            let is_newer = {
+                let cache = self.cache.lock().unwrap();
+                cache.get(&profile.pubkey).is_none_or(|cached| profile.last_updated > cached.last_updated)
+            };
+            if is_newer {
+                self.put(profile);
+                imported += 1;
+            }
+        }
+
+        Ok(imported)
+    }
+
+    pub async fn save_to_file_async(&self) {
         if let Some(path) = &self.file_path {
+            let _guard = self.save_lock.lock().await;
+
             // Create a snapshot of the cache to avoid holding the lock during file I/O
             let json_result = {
                 let cache = self.cache.lock().unwrap();
-                serde_json::to_string(&*cache)
+                serde_json::to_vec(&MetadataCacheFile {
+                    version: METADATA_CACHE_VERSION,
+                    entries: &cache,
+                })
             };
             
             // Handle file writing outside the lock
             match json_result {
                 Ok(json) => {
-                    if let Err(e) = fs::write(path, json) {
+                    // A half-written cache would force every profile to be fetched again on restart
+                    let path = path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        persist::atomic_write(&path, |file| file.write_all(&json))
+                    }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    if let Err(e) = result {
                         error!("Failed to write metadata cache to file: {}", e);
                     }
                 }
@@ -171,6 +321,21 @@ impl MetadataCache {
         }
     }
 
+    /// Fetches every cached profile past its lifetime again, returning how many were refreshed
+    pub async fn refresh_stale(&self, client: &Client) -> usize {
+        let stale: Vec<PublicKey> = self.get_all().into_iter()
+            .filter(|metadata| metadata.needs_refresh())
+            .filter_map(|metadata| PublicKey::parse(&metadata.pubkey).ok())
+            .collect();
+
+        stream::iter(stale)
+            .map(|pubkey| async move { self.fetch_metadata(client, &pubkey).await })
+            .buffer_unordered(REFRESH_CONCURRENCY)
+            .filter(|result| std::future::ready(result.is_ok()))
+            .count()
+            .await
+    }
+
     pub async fn fetch_metadata(&self, client: &Client, pubkey: &PublicKey) -> Result<UserMetadata> {
         // Check if we already have recent metadata
         if let Some(metadata) = self.get(pubkey) {
@@ -187,12 +352,12 @@ impl MetadataCache {
         
         if let Some(metadata) = metadata_result {
             // Create and store user metadata
-            let user_metadata = UserMetadata::from_metadata(pubkey, metadata);
+            let user_metadata = UserMetadata::from_metadata(pubkey, metadata)?;
             self.put(user_metadata.clone());
             Ok(user_metadata)
         } else {
             // If no metadata is available, create a default entry
-            let metadata = UserMetadata::new(pubkey);
+            let metadata = UserMetadata::new(pubkey)?;
             self.put(metadata.clone());
             Ok(metadata)
         }
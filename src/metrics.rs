@@ -0,0 +1,295 @@
+use anyhow::Result;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tracing::{debug, info};
+
+/// A monotonically increasing Prometheus counter
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A Prometheus counter with one series per value of a single label
+pub struct LabeledCounter {
+    name: &'static str,
+    help: &'static str,
+    label: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+}
+
+impl LabeledCounter {
+    pub const fn new(name: &'static str, help: &'static str, label: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            label,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, label_value: &str) {
+        let mut values = self.values.lock().unwrap();
+        *values.entry(label_value.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn get(&self, label_value: &str) -> u64 {
+        self.values.lock().unwrap().get(label_value).copied().unwrap_or(0)
+    }
+}
+
+// Upper bounds (exclusive) of the content length buckets, with a final unbounded bucket
+const CONTENT_LENGTH_BOUNDS: [u64; 4] = [64, 256, 1024, 4096];
+
+// A content length summary is logged after this many messages or this long, whichever comes first
+const CONTENT_LENGTH_SUMMARY_MESSAGES: usize = 1000;
+const CONTENT_LENGTH_SUMMARY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const CONTENT_LENGTH_NAME: &str = "bridge_message_content_length_chars";
+
+/// Lengths seen since the last summary, and when the first of them arrived
+#[derive(Default)]
+struct RecentLengths {
+    lengths: Vec<u64>,
+    since: Option<Instant>,
+}
+
+/// A Prometheus histogram of bridged message lengths in characters, for one direction
+pub struct ContentLengthHistogram {
+    direction: &'static str,
+    buckets: [AtomicU64; CONTENT_LENGTH_BOUNDS.len() + 1],
+    sum: AtomicU64,
+    recent: Mutex<RecentLengths>,
+}
+
+impl ContentLengthHistogram {
+    pub const fn new(direction: &'static str) -> Self {
+        Self {
+            direction,
+            buckets: [const { AtomicU64::new(0) }; CONTENT_LENGTH_BOUNDS.len() + 1],
+            sum: AtomicU64::new(0),
+            recent: Mutex::new(RecentLengths { lengths: Vec::new(), since: None }),
+        }
+    }
+
+    /// Records one message, logging a summary once enough have been seen
+    pub fn observe(&self, content: &str) {
+        let length = content.chars().count() as u64;
+        debug!(direction = self.direction, content_length_chars = length, "Bridged message length");
+
+        let bucket = CONTENT_LENGTH_BOUNDS.iter().position(|&bound| length < bound).unwrap_or(CONTENT_LENGTH_BOUNDS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(length, Ordering::Relaxed);
+
+        let mut recent = self.recent.lock().unwrap();
+        recent.lengths.push(length);
+        let since = *recent.since.get_or_insert_with(Instant::now);
+        if recent.lengths.len() < CONTENT_LENGTH_SUMMARY_MESSAGES && since.elapsed() < CONTENT_LENGTH_SUMMARY_INTERVAL {
+            return;
+        }
+
+        let mut lengths = std::mem::take(&mut recent.lengths);
+        recent.since = None;
+        drop(recent);
+
+        lengths.sort_unstable();
+        let count = lengths.len();
+        let avg = lengths.iter().sum::<u64>() / count as u64;
+        let p95 = lengths[(count * 95).div_ceil(100) - 1];
+        let max = lengths[count - 1];
+        info!(direction = self.direction, "Last {} messages: avg={}, p95={}, max={} chars.", count, avg, p95, max);
+    }
+
+    /// Writes this direction's cumulative `_bucket`, `_sum` and `_count` series
+    fn render(&self, output: &mut String) {
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = CONTENT_LENGTH_BOUNDS.get(i).map_or_else(|| "+Inf".to_string(), u64::to_string);
+            let _ = writeln!(output, "{}_bucket{{direction=\"{}\",le=\"{}\"}} {}", CONTENT_LENGTH_NAME, self.direction, le, cumulative);
+        }
+        let _ = writeln!(output, "{}_sum{{direction=\"{}\"}} {}", CONTENT_LENGTH_NAME, self.direction, self.sum.load(Ordering::Relaxed));
+        let _ = writeln!(output, "{}_count{{direction=\"{}\"}} {}", CONTENT_LENGTH_NAME, self.direction, cumulative);
+    }
+}
+
+/// A usage count kept both in total and since the last daily reset
+#[derive(Debug, Default)]
+pub struct DailyCounter {
+    total: AtomicU64,
+    today: AtomicU64,
+}
+
+impl DailyCounter {
+    pub fn inc(&self) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        self.today.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn today(&self) -> u64 {
+        self.today.load(Ordering::Relaxed)
+    }
+}
+
+/// Cumulative usage of one bridge since startup, reported by `!stats`
+#[derive(Debug, Default)]
+pub struct BridgeStats {
+    pub discord_to_nostr_total: DailyCounter,
+    pub nostr_to_discord_total: DailyCounter,
+    pub subscribe_events_total: DailyCounter,
+    pub unsubscribe_events_total: DailyCounter,
+    pub failed_sends_total: DailyCounter,
+    pub metadata_fetches_total: DailyCounter,
+}
+
+impl BridgeStats {
+    /// Each counter with the label `!stats` shows for it
+    pub fn counters(&self) -> [(&'static str, &DailyCounter); 6] {
+        [
+            ("Discord → Nostr messages", &self.discord_to_nostr_total),
+            ("Nostr → Discord messages", &self.nostr_to_discord_total),
+            ("Subscribes", &self.subscribe_events_total),
+            ("Unsubscribes", &self.unsubscribe_events_total),
+            ("Failed sends", &self.failed_sends_total),
+            ("Metadata fetches", &self.metadata_fetches_total),
+        ]
+    }
+
+    /// Starts a new day, the running totals are kept
+    pub fn reset_daily(&self) {
+        for (_, counter) in self.counters() {
+            counter.today.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+pub static NOSTR_EVENT_VERIFICATION_FAILURES: Counter = Counter::new(
+    "nostr_event_verification_failures_total",
+    "Nostr events dropped because a signature or event ID failed verification",
+);
+
+pub static NOSTR_GIFTWRAP_MISMATCHES: Counter = Counter::new(
+    "nostr_giftwrap_mismatch_total",
+    "Giftwraps whose seal sender differed from the rumor author",
+);
+
+pub static NOSTR_SPAM_DETECTIONS: Counter = Counter::new(
+    "nostr_spam_detections_total",
+    "Nostr senders muted for repeatedly sending the same message",
+);
+
+pub static NOSTR_FLOOD_DETECTIONS: Counter = Counter::new(
+    "nostr_flood_detections_total",
+    "Nostr senders cooled down for bursting too many messages",
+);
+
+pub static NOSTR_POW_REJECTIONS: Counter = Counter::new(
+    "nostr_pow_rejections_total",
+    "Nostr messages rejected for insufficient proof-of-work",
+);
+
+pub static RELAY_PUBLISH_OK: LabeledCounter = LabeledCounter::new(
+    "relay_publish_ok_total",
+    "Published events a relay accepted with an OK message",
+    "relay",
+);
+
+pub static RELAY_PUBLISH_FAIL: LabeledCounter = LabeledCounter::new(
+    "relay_publish_fail_total",
+    "Published events a relay rejected with an OK message",
+    "relay",
+);
+
+pub static DISCORD_TO_NOSTR_CONTENT_LENGTH: ContentLengthHistogram = ContentLengthHistogram::new("discord_to_nostr");
+
+pub static NOSTR_TO_DISCORD_CONTENT_LENGTH: ContentLengthHistogram = ContentLengthHistogram::new("nostr_to_discord");
+
+/// Every counter exported by `render`
+static COUNTERS: &[&Counter] = &[
+    &NOSTR_EVENT_VERIFICATION_FAILURES,
+    &NOSTR_GIFTWRAP_MISMATCHES,
+    &NOSTR_SPAM_DETECTIONS,
+    &NOSTR_FLOOD_DETECTIONS,
+    &NOSTR_POW_REJECTIONS,
+];
+
+/// Every labeled counter exported by `render`
+static LABELED_COUNTERS: &[&LabeledCounter] = &[
+    &RELAY_PUBLISH_OK,
+    &RELAY_PUBLISH_FAIL,
+];
+
+/// Every content length histogram exported by `render`, all under one metric name
+static CONTENT_LENGTH_HISTOGRAMS: &[&ContentLengthHistogram] = &[
+    &DISCORD_TO_NOSTR_CONTENT_LENGTH,
+    &NOSTR_TO_DISCORD_CONTENT_LENGTH,
+];
+
+/// Renders all counters in the Prometheus text exposition format
+pub fn render() -> String {
+    let mut output = String::new();
+    for counter in COUNTERS {
+        let _ = writeln!(output, "# HELP {} {}", counter.name, counter.help);
+        let _ = writeln!(output, "# TYPE {} counter", counter.name);
+        let _ = writeln!(output, "{} {}", counter.name, counter.get());
+    }
+    for counter in LABELED_COUNTERS {
+        let _ = writeln!(output, "# HELP {} {}", counter.name, counter.help);
+        let _ = writeln!(output, "# TYPE {} counter", counter.name);
+        for (label_value, value) in counter.values.lock().unwrap().iter() {
+            let escaped = label_value.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(output, "{}{{{}=\"{}\"}} {}", counter.name, counter.label, escaped, value);
+        }
+    }
+    let _ = writeln!(output, "# HELP {} Length of bridged message content in characters", CONTENT_LENGTH_NAME);
+    let _ = writeln!(output, "# TYPE {} histogram", CONTENT_LENGTH_NAME);
+    for histogram in CONTENT_LENGTH_HISTOGRAMS {
+        histogram.render(&mut output);
+    }
+    output
+}
+
+/// Serves `render` at `GET /metrics`, unauthenticated since Prometheus scrapers can't sign requests
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(|| async {
+        ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], render()).into_response()
+    }))
+}
+
+/// Serves the metrics on `addr` until the process exits, independently of the admin API
+pub async fn serve(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Metrics listening on {}", addr);
+    axum::serve(listener, router()).await?;
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use super::{RelayStats, SubscriberStats};
+use crate::metrics::BridgeStats;
+use chrono::DateTime;
+use std::collections::HashSet;
+
+/// Help text for a single bot command
+pub struct CommandHelp {
+    pub name: &'static str,
+    pub short: &'static str,
+    pub long: &'static str,
+    /// Only bridge admins may run it, so it's hidden from everyone else
+    pub admin: bool,
+}
+
+/// Documentation for every command, in the order `!help` lists them
+pub static COMMAND_DOCS: &[CommandHelp] = &[
+    CommandHelp {
+        name: "subscribe",
+        short: "Start receiving Discord messages",
+        long: "!subscribe\n\
+            Subscribes you to the bridged Discord channel. Every message posted there is \
+            forwarded to you as a private message, and your own messages to this bot are \
+            relayed into the channel.\n\
+            Example: !subscribe",
+        admin: false,
+    },
+    CommandHelp {
+        name: "unsubscribe",
+        short: "Stop receiving Discord messages",
+        long: "!unsubscribe\n\
+            Stops forwarding Discord messages to you and stops relaying your messages to \
+            Discord. You can subscribe again at any time.\n\
+            Example: !unsubscribe",
+        admin: false,
+    },
+    CommandHelp {
+        name: "mystats",
+        short: "Show your bridge activity",
+        long: "!mystats\n\
+            Shows when you subscribed, how many of your messages have been forwarded to \
+            Discord and when you last sent one. Counts reset when the bridge restarts.\n\
+            Example: !mystats",
+        admin: false,
+    },
+    CommandHelp {
+        name: "filter",
+        short: "Manage your keyword filters",
+        long: "!filter add <keyword> | !filter remove <keyword> | !filter list\n\
+            Discord messages containing any of your keywords are not sent to you. Keywords \
+            are case-insensitive, up to 50 characters, and you can have up to 20.\n\
+            Example: !filter add spoiler",
+        admin: false,
+    },
+    CommandHelp {
+        name: "pref",
+        short: "Choose how Discord messages look",
+        long: "!pref format plain|prefixed | !pref username on|off\n\
+            `plain` sends Discord messages as-is, `prefixed` (the default) starts them with \
+            [Discord]. `username` controls whether the Discord author's name is included.\n\
+            Example: !pref format plain",
+        admin: false,
+    },
+    CommandHelp {
+        name: "list-channels",
+        short: "List the bridge's channels",
+        long: "!list-channels\n\
+            Lists the named Discord channels bridged by this instance, each with its own \
+            Nostr identity. Subscribe to one of them with !subscribe-channel.\n\
+            Example: !list-channels",
+        admin: false,
+    },
+    CommandHelp {
+        name: "subscribe-channel",
+        short: "Receive a single bridged channel",
+        long: "!subscribe-channel <name>\n\
+            Subscribes you to one named channel of this instance. Its messages come from that \
+            channel's own Nostr identity, and !subscribe still subscribes you to this one.\n\
+            Example: !subscribe-channel general",
+        admin: false,
+    },
+    CommandHelp {
+        name: "unsubscribe-channel",
+        short: "Stop receiving a bridged channel",
+        long: "!unsubscribe-channel <name>\n\
+            Stops forwarding the named channel's Discord messages to you, leaving your other \
+            channels as they are.\n\
+            Example: !unsubscribe-channel general",
+        admin: false,
+    },
+    CommandHelp {
+        name: "register-discord",
+        short: "Receive Discord mentions of your username",
+        long: "!register-discord <username>\n\
+            Links a Discord username to you, so Discord messages mentioning it are also sent to \
+            you directly. Registering again replaces your previous username.\n\
+            Example: !register-discord satoshi",
+        admin: false,
+    },
+    CommandHelp {
+        name: "ping",
+        short: "Check that the bridge is responding",
+        long: "!ping\n\
+            Replies with the bridge's current round-trip time to its relays, roughly how long \
+            your message took to be answered, and how many subscribers the bridge has.\n\
+            Example: !ping",
+        admin: false,
+    },
+    CommandHelp {
+        name: "version",
+        short: "Show which version of the bridge is running",
+        long: "!version\n\
+            Shows the bridge's version and build time, the Nostr SDK it was built with, how \
+            long it has been running, how many relays are connected and the subscriptions DMs \
+            arrive through. Useful to include when reporting an issue.\n\
+            Example: !version",
+        admin: false,
+    },
+    CommandHelp {
+        name: "help",
+        short: "Show this help message",
+        long: "!help [command]\n\
+            Without an argument, lists every command. With a command name, shows its \
+            detailed documentation.\n\
+            Example: !help subscribe",
+        admin: false,
+    },
+    CommandHelp {
+        name: "pause",
+        short: "Temporarily halt bridging",
+        long: "!pause\n\
+            Stops bridging in both directions and tells every subscriber. Messages sent \
+            while paused are held (up to PAUSE_BUFFER_SIZE) and delivered on !resume.\n\
+            Example: !pause",
+        admin: true,
+    },
+    CommandHelp {
+        name: "resume",
+        short: "Resume bridging after a pause",
+        long: "!resume\n\
+            Restarts bridging, delivers the messages held while paused and tells every \
+            subscriber.\n\
+            Example: !resume",
+        admin: true,
+    },
+    CommandHelp {
+        name: "relay-status",
+        short: "Show relay connections and delivery statistics",
+        long: "!relay-status\n\
+            Lists each relay with whether the bridge is connected, how many of its events \
+            the relay accepted and rejected since startup, and its average latency. A relay \
+            that rejects most events is likely dropping messages.\n\
+            Example: !relay-status",
+        admin: true,
+    },
+    CommandHelp {
+        name: "addrelay",
+        short: "Connect the bridge to another relay",
+        long: "!addrelay <url>\n\
+            Asks for confirmation with a one-time token, then `!confirm <token>` within 60 \
+            seconds connects the relay until the next restart and sends every subscriber the \
+            new relay list.\n\
+            Example: !addrelay wss://relay.example.com",
+        admin: true,
+    },
+    CommandHelp {
+        name: "confirm",
+        short: "Confirm a pending !addrelay",
+        long: "!confirm <token>\n\
+            Completes the !addrelay that replied with this token. Tokens work once and expire \
+            after 60 seconds.\n\
+            Example: !confirm K7QW2M",
+        admin: true,
+    },
+    CommandHelp {
+        name: "redact",
+        short: "Remove a bridged message",
+        long: "!redact <nevent>\n\
+            Publishes a deletion for the event, also accepted as a hex ID, deletes the Discord \
+            message it was posted as and tells every subscriber that a message was removed, \
+            without its content.\n\
+            Example: !redact nevent1...",
+        admin: true,
+    },
+    CommandHelp {
+        name: "stats",
+        short: "Show bridge usage statistics",
+        long: "!stats\n\
+            Shows how many messages the bridge relayed in each direction, subscribe and \
+            unsubscribe events, failed sends and metadata fetches, both since startup and \
+            since midnight, along with the subscriber count and metadata cache size.\n\
+            Example: !stats",
+        admin: true,
+    },
+    CommandHelp {
+        name: "rotate-key",
+        short: "Switch the bridge to a new Nostr key",
+        long: "!rotate-key <nsec>\n\
+            Replaces the bridge's Nostr identity without a restart. The old profile is \
+            deleted and every subscriber is told the new npub from the old key. Requires \
+            ALLOW_KEY_ROTATION=true.\n\
+            Example: !rotate-key nsec1...",
+        admin: true,
+    },
+];
+
+/// Whether the operator left `name` enabled, an empty set enables every command
+pub fn is_enabled(name: &str, enabled_commands: &HashSet<String>) -> bool {
+    enabled_commands.is_empty() || enabled_commands.contains(name)
+}
+
+/// Looks up a command's documentation, with or without its leading `!`
+pub fn find(name: &str, is_admin: bool, enabled_commands: &HashSet<String>) -> Option<&'static CommandHelp> {
+    let name = name.trim_start_matches('!');
+    COMMAND_DOCS.iter().find(|doc| {
+        doc.name.eq_ignore_ascii_case(name) && (is_admin || !doc.admin) && is_enabled(doc.name, enabled_commands)
+    })
+}
+
+/// Builds the `!help` reply, either the compact listing or one command in detail
+pub fn help_text(topic: Option<&str>, is_admin: bool, enabled_commands: &HashSet<String>) -> String {
+    match topic {
+        Some(topic) => match find(topic, is_admin, enabled_commands) {
+            Some(doc) => doc.long.to_string(),
+            None => format!("Unknown command '{}'. Send !help for the list of commands.", topic),
+        },
+        None => {
+            let mut text = String::from("Available commands:");
+            for doc in COMMAND_DOCS.iter().filter(|doc| (is_admin || !doc.admin) && is_enabled(doc.name, enabled_commands)) {
+                text.push_str(&format!("\n!{} - {}", doc.name, doc.short));
+            }
+            text.push_str("\nSend !help <command> for details.");
+            text
+        }
+    }
+}
+
+/// Replaces each `{name}` placeholder in `template` with its value
+pub fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Builds the `!mystats` reply for a subscriber
+pub fn stats_text(stats: &SubscriberStats, now: u64) -> String {
+    let joined = stats.joined_at
+        .and_then(|ts| DateTime::from_timestamp(ts as i64, 0))
+        .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let last_message = stats.last_message_at
+        .map(|ts| time_ago(now.saturating_sub(ts)))
+        .unwrap_or_else(|| "never".to_string());
+
+    format!(
+        "Joined: {}, Messages forwarded: {}, Last message: {}.",
+        joined, stats.message_count, last_message
+    )
+}
+
+/// Builds the `!relay-status` reply, one line per relay
+pub fn relay_status_text(relays: &[(String, RelayStats)]) -> String {
+    if relays.is_empty() {
+        return "No relay activity recorded yet.".to_string();
+    }
+
+    relays.iter().map(|(relay, stats)| {
+        let icon = if stats.connected { "✅" } else { "❌" };
+        let detail = match (stats.connected, stats.latency_ewma_ms) {
+            (false, _) => " (reconnecting)".to_string(),
+            (true, Some(latency)) => format!(", {:.0}ms avg", latency),
+            (true, None) => String::new(),
+        };
+        format!("{}: {} {} sent, {} failed{}", relay, icon, stats.messages_sent, stats.messages_failed, detail)
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// Builds the `!stats` reply, with each counter's total and its count since midnight
+pub fn bridge_stats_text(stats: &BridgeStats, subscriber_count: usize, cached_profiles: usize) -> String {
+    let mut text = String::from("Bridge stats (total / today):");
+    for (label, counter) in stats.counters() {
+        text.push_str(&format!("\n{}: {} / {}", label, counter.total(), counter.today()));
+    }
+    text.push_str(&format!("\nSubscribers: {}\nCached profiles: {}", subscriber_count, cached_profiles));
+    text
+}
+
+/// The relay pool's state, reported by `!version` for debugging
+pub struct PoolStatus {
+    pub connected_relays: usize,
+    pub relays: usize,
+    pub subscriptions: usize,
+    /// IDs of the subscriptions DMs arrive through
+    pub giftwrap_subscriptions: Vec<String>,
+}
+
+/// Builds the `!version` reply from the build metadata, when the bridge started and its relay pool
+pub fn version_text(started_at: u64, now: u64, pool: &PoolStatus) -> String {
+    let started = DateTime::from_timestamp(started_at as i64, 0)
+        .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let giftwrap_subscriptions = if pool.giftwrap_subscriptions.is_empty() {
+        "none".to_string()
+    } else {
+        pool.giftwrap_subscriptions.join(", ")
+    };
+
+    format!(
+        "Vecord {} (built {})\nNostr SDK: {}\nRunning since: {} ({})\n\
+        Relays: {} of {} connected\nSubscriptions: {} (giftwrap: {})",
+        env!("CARGO_PKG_VERSION"),
+        env!("BUILD_TIMESTAMP"),
+        env!("NOSTR_SDK_VERSION"),
+        started,
+        time_ago(now.saturating_sub(started_at)),
+        pool.connected_relays,
+        pool.relays,
+        pool.subscriptions,
+        giftwrap_subscriptions,
+    )
+}
+
+/// Builds the `!ping` reply, from the time a relay took to answer an event with an OK when any did
+pub fn ping_text(relay_rtt_ms: Option<u64>, end_to_end_ms: u64, subscriber_count: usize) -> String {
+    let relay_rtt = relay_rtt_ms.map_or_else(|| "unknown".to_string(), |ms| format!("{}ms", ms));
+    let plural = if subscriber_count == 1 { "" } else { "s" };
+
+    format!(
+        "Pong! Bridge latency: {} (since your message was sent: ~{}ms).\n{} subscriber{} online",
+        relay_rtt, end_to_end_ms, subscriber_count, plural,
+    )
+}
+
+/// Formats an elapsed number of seconds as e.g. "5 minutes ago"
+fn time_ago(secs: u64) -> String {
+    let (value, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (secs / 60, "minute"),
+        3_600..=86_399 => (secs / 3_600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    let plural = if value == 1 { "" } else { "s" };
+
+    format!("{} {}{} ago", value, unit, plural)
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use nostr_sdk::nips::nip65::{self, RelayMetadata};
+use nostr_sdk::{EventId, NostrSigner, RelayOptions, RelayServiceFlags, Tags, UnsignedEvent};
+use tokio::sync::Mutex;
+use super::RelayRateLimits;
+use tracing::{debug, error, warn};
+use vector_sdk::nostr::{Client, EventBuilder, Filter, Kind, PublicKey, Tag, TagKind, Timestamp};
+use vector_sdk::Channel;
+
+/// Subscribers' preferred relays for receiving DMs, read from their NIP-65 relay lists
+#[derive(Clone, Default)]
+pub struct InboxRelays {
+    relays: Arc<Mutex<HashMap<PublicKey, Vec<String>>>>,
+    /// Paces DMs that fall back to the write relays
+    limits: RelayRateLimits,
+}
+
+impl InboxRelays {
+    pub fn new(limits: RelayRateLimits) -> Self {
+        Self { relays: Arc::default(), limits }
+    }
+
+    /// Fetches `pubkey`'s relay list and keeps its read relays, forgetting them when it has none
+    pub async fn refresh(&self, client: &Client, pubkey: PublicKey) {
+        let filter = Filter::new().author(pubkey).kind(Kind::RelayList).limit(1);
+        let events = match client.fetch_events(filter, Duration::from_secs(5)).await {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to fetch relay list for {}: {:?}", pubkey, e);
+                return;
+            }
+        };
+
+        // Unmarked relays are used for both reading and writing
+        let inbox: Vec<String> = events.first()
+            .map(|event| nip65::extract_relay_list(event)
+                .filter(|(_, marker)| !matches!(marker, Some(RelayMetadata::Write)))
+                .map(|(url, _)| url.to_string())
+                .collect())
+            .unwrap_or_default();
+
+        let mut relays = self.relays.lock().await;
+        if inbox.is_empty() {
+            relays.remove(&pubkey);
+        } else {
+            debug!("Found {} inbox relays for {}", inbox.len(), pubkey);
+            relays.insert(pubkey, inbox);
+        }
+    }
+
+    pub async fn get(&self, pubkey: &PublicKey) -> Option<Vec<String>> {
+        self.relays.lock().await.get(pubkey).cloned()
+    }
+
+    pub async fn remove(&self, pubkey: &PublicKey) {
+        self.relays.lock().await.remove(pubkey);
+    }
+
+    /// DMs `message` to the subscriber's inbox relays, or through `chat` to the write relays without any
+    pub async fn send_private_message(&self, client: &Client, chat: &Channel, pubkey: PublicKey, message: &str) -> bool {
+        if let Some(inbox) = self.get(&pubkey).await {
+            connect_inbox(client, &inbox).await;
+            match client.send_private_msg_to(&inbox, pubkey, message, [millis_tag()]).await {
+                Ok(output) if !output.success.is_empty() => return true,
+                Ok(_) | Err(_) => warn!("No inbox relay of {} accepted the DM, falling back to the write relays", pubkey),
+            }
+        }
+
+        if !self.limits.is_active() {
+            return chat.send_private_message(message).await;
+        }
+        match rumor(client, pubkey, message).await {
+            Some(rumor) => self.send_to_write_relays(client, pubkey, rumor).await,
+            None => false,
+        }
+    }
+
+    /// Sends like `send_private_message`, returning the ID of the rumor the subscriber sees so replies can be matched
+    pub async fn send_tracked_private_message(&self, client: &Client, pubkey: PublicKey, message: &str) -> Option<EventId> {
+        let mut rumor = rumor(client, pubkey, message).await?;
+        let rumor_id = rumor.id();
+
+        if let Some(inbox) = self.get(&pubkey).await {
+            connect_inbox(client, &inbox).await;
+            match client.gift_wrap_to(&inbox, &pubkey, rumor.clone(), []).await {
+                Ok(output) if !output.success.is_empty() => return Some(rumor_id),
+                Ok(_) | Err(_) => warn!("No inbox relay of {} accepted the DM, falling back to the write relays", pubkey),
+            }
+        }
+
+        if self.limits.is_active() {
+            return self.send_to_write_relays(client, pubkey, rumor).await.then_some(rumor_id);
+        }
+        match client.gift_wrap(&pubkey, rumor, []).await {
+            Ok(_) => Some(rumor_id),
+            Err(e) => {
+                error!("Failed to send private message: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Gift wraps `rumor` and publishes it to each write relay at that relay's rate
+    async fn send_to_write_relays(&self, client: &Client, pubkey: PublicKey, rumor: UnsignedEvent) -> bool {
+        let wrapped = match client.signer().await {
+            Ok(signer) => EventBuilder::gift_wrap(&signer, &pubkey, rumor, []).await,
+            Err(e) => {
+                error!("Failed to get the signer for a DM to {}: {:?}", pubkey, e);
+                return false;
+            }
+        };
+        match wrapped {
+            Ok(event) => self.limits.publish(client, &event).await,
+            Err(e) => {
+                error!("Failed to gift wrap a DM to {}: {:?}", pubkey, e);
+                false
+            }
+        }
+    }
+}
+
+/// The unsigned NIP-17 message to `pubkey` that gets gift wrapped
+async fn rumor(client: &Client, pubkey: PublicKey, message: &str) -> Option<UnsignedEvent> {
+    let author = match client.signer().await {
+        Ok(signer) => signer.get_public_key().await.ok()?,
+        Err(e) => {
+            error!("Failed to get the signer for a DM to {}: {:?}", pubkey, e);
+            return None;
+        }
+    };
+    Some(EventBuilder::private_msg_rumor(pubkey, message)
+        .tags([millis_tag()])
+        .build(author))
+}
+
+/// Adds the inbox relays for sending only, they never receive subscriptions or broadcasts
+async fn connect_inbox(client: &Client, inbox: &[String]) {
+    for relay in inbox {
+        let options = RelayOptions::new().flags(RelayServiceFlags::PING | RelayServiceFlags::GOSSIP);
+        match client.pool().add_relay(relay, options).await {
+            Ok(true) => {}
+            Ok(false) => {
+                if let Ok(relay) = client.relay(relay).await {
+                    relay.flags().add(RelayServiceFlags::GOSSIP);
+                }
+            }
+            Err(e) => warn!("Failed to add inbox relay {}: {:?}", relay, e),
+        }
+        let _ = client.connect_relay(relay).await;
+    }
+}
+
+/// Same millisecond tag the Vector SDK adds, so clients can order messages within a second
+fn millis_tag() -> Tag {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() % 1000;
+    Tag::custom(TagKind::custom("ms"), [millis.to_string()])
+}
+
+/// When a message was sent in milliseconds, refined by its `ms` tag when the sender's client added one
+pub fn sent_at_millis(created_at: Timestamp, tags: &Tags) -> u64 {
+    let millis = tags.iter()
+        .find(|tag| tag.kind() == TagKind::custom("ms"))
+        .and_then(|tag| tag.content())
+        .and_then(|millis| millis.parse::<u64>().ok())
+        .filter(|millis| *millis < 1000)
+        .unwrap_or(0);
+    created_at.as_u64() * 1000 + millis
+}
@@ -1,21 +1,79 @@
-use crate::config::Config;
-use crate::message::{BridgeMessage, NostrMessageMetadata};
+mod commands;
+mod inbox;
+mod preferences;
+mod rate_limit;
+
+use crate::archive::MessageArchive;
+use crate::config::{self, Config};
+use crate::crypto;
+use crate::filter::{ContentFilter, FILTERED_NOTICE};
+use crate::mapping::MessageIdMapping;
+use crate::message::{BridgeMessage, NostrMessageMetadata, SystemEventType};
 use crate::metadata::{MetadataCache, UserMetadata};
+use crate::metrics::{self, BridgeStats};
+use crate::pause::BridgePause;
+use crate::persist;
+use crate::priority::{priority_channel, PrioritySender};
+use crate::sanitize::normalize_content;
+use crate::time;
+use crate::usernames::UsernameMap;
 use anyhow::{Result, anyhow};
+use chrono_tz::Tz;
 use vector_sdk::nostr::{
-    FromBech32, Keys, Kind, PublicKey, SecretKey, ToBech32, UnwrappedGift, RelayPoolNotification,
+    Client, Event, EventBuilder, Filter, FromBech32, Keys, Kind, Metadata, PublicKey, Tag, TagKind, Timestamp, ToBech32, UnwrappedGift, RelayPoolNotification,
 };
-use std::time::Duration;
-use std::str::FromStr;
-use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use std::fs;
 use std::io::{Read, Write};
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 
 // Vector SDK
 use vector_sdk::{VectorBot, AttachmentFile};
+use nostr_sdk::nips::nip09::EventDeletionRequest;
+use nostr_sdk::nips::nip13::get_leading_zero_bits;
+use nostr_sdk::nips::nip19::Nip19Event;
+use nostr_sdk::nips::nip46::NostrConnectURI;
+use nostr_sdk::nips::nip65::{self, RelayMetadata};
+use nostr_sdk::{EventId, JsonUtil, NostrSigner, RelayMessage, RelayStatus, RelayUrl};
+use nostr_connect::client::NostrConnect;
+
+use inbox::InboxRelays;
+pub use preferences::{MessageFormat, PreferenceStore, UserPreferences};
+pub use rate_limit::{RelayRateLimits, TokenBucket};
+
+// Default avatar used for the bridge's profile
+const DEFAULT_BOT_PICTURE: &str = "https://jskitty.cat/vector/img/vecord.png";
+
+// How long to wait for the NIP-46 bunker to answer a signing request
+const BUNKER_TIMEOUT: Duration = Duration::from_secs(60);
+
+// NIP-89 handler record advertising the bridge as a NIP-17 DM app, refreshed weekly
+const APP_HANDLER_KIND: Kind = Kind::Custom(31990);
+const APP_HANDLER_ID: &str = "vecord";
+const APP_HANDLER_REPUBLISH: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+// Pause before re-opening a closed notification stream, so a shut down pool isn't polled in a tight loop
+const NOTIFICATION_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// How often every subscriber's NIP-65 inbox relays are fetched again
+const INBOX_RELAYS_REFRESH: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+// Pause between sending a bridge notice to each subscriber, as a broadcast to all of them can trip relay rate limits
+const NOTICE_SEND_INTERVAL: Duration = Duration::from_millis(200);
+
+// How long an `!addrelay` waits for its `!confirm`
+const RELAY_CONFIRMATION_TTL: Duration = Duration::from_secs(60);
+const RELAY_CONFIRMATION_TOKEN_LEN: usize = 6;
+
+// Ephemeral kind `!ping` publishes to time a relay's OK, relays forward it without storing it
+const PING_PROBE_KIND: Kind = Kind::Custom(20_000);
+
+// `d` tag of the synced subscriber list when the imported list has none
+const DEFAULT_SUBSCRIBER_LIST_ID: &str = "vecord-subscribers";
 
 /// Helper function to parse a pubkey from either bech32 or hex format
 fn parse_pubkey(key_str: &str) -> Result<PublicKey> {
@@ -26,15 +84,374 @@ fn parse_pubkey(key_str: &str) -> Result<PublicKey> {
     }
 }
 
+/// Encodes a pubkey as an npub, failing rather than falling back to hex
+pub fn pubkey_to_bech32_or_fail(pk: &PublicKey) -> Result<String> {
+    pk.to_bech32().map_err(|e| anyhow!("Failed to encode pubkey {} as an npub: {}", pk.to_hex(), e))
+}
+
+/// Whether `name` (without its `!`) is one of the bot's commands
+pub fn is_command(name: &str) -> bool {
+    commands::COMMAND_DOCS.iter().any(|doc| doc.name == name)
+}
+
+/// A subscriber's bridge activity since the bot started
+#[derive(Clone, Debug, Default)]
+pub struct SubscriberStats {
+    /// Unix time of the `!subscribe`, unknown for subscribers loaded from file
+    pub joined_at: Option<u64>,
+    pub message_count: u64,
+    pub last_message_at: Option<u64>,
+}
+
+/// The bridge's own relays, split into those DMs are read from and those events are published to
+#[derive(Clone, Debug)]
+struct RelaySet {
+    read: Vec<String>,
+    write: Vec<String>,
+}
+
+impl RelaySet {
+    /// Every relay with its NIP-65 marker, unmarked when it is used for both
+    fn with_markers(&self) -> Vec<(&String, Option<RelayMetadata>)> {
+        let read = self.read.iter()
+            .map(|relay| (relay, (!self.write.contains(relay)).then_some(RelayMetadata::Read)));
+        let write = self.write.iter()
+            .filter(|relay| !self.read.contains(relay))
+            .map(|relay| (relay, Some(RelayMetadata::Write)));
+        read.chain(write).collect()
+    }
+}
+
+/// A NIP-46 bunker that signs for the bridge, and the bridge pubkey it signs as
+type RemoteSigner = (Arc<dyn NostrSigner>, PublicKey);
+
+/// Sets up a NIP-46 client for the bunker at `url`, talking to it with `app_keys`
+fn connect_bunker(url: &str, secret: Option<&str>, pubkey: Option<&str>, app_keys: &Keys) -> Result<RemoteSigner> {
+    let mut uri = NostrConnectURI::parse(url)
+        .map_err(|e| anyhow!("Invalid NOSTR_BUNKER_URL: {}", e))?;
+    if let (NostrConnectURI::Bunker { secret: uri_secret, .. }, Some(secret)) = (&mut uri, secret) {
+        *uri_secret = Some(secret.to_string());
+    }
+
+    let pubkey = pubkey
+        .ok_or_else(|| anyhow!("NOSTR_BUNKER_PUBKEY is required when NOSTR_BUNKER_URL is set"))?;
+    let pubkey = parse_pubkey(pubkey)?;
+
+    let signer = NostrConnect::new(uri, app_keys.clone(), BUNKER_TIMEOUT, None)?;
+    // The pubkey is configured up front, so there's no need to ask the bunker for it
+    signer.non_secure_set_user_public_key(pubkey)?;
+
+    Ok((Arc::new(signer), pubkey))
+}
+
+/// Builds and connects a `VectorBot` for `keys`, publishing `profile` if requested
+///
+/// With a remote signer, `keys` only identify the bridge to the bunker and every event is signed remotely.
+async fn connect_bot(keys: Keys, remote_signer: Option<&RemoteSigner>, profile: &Metadata, relays: &RelaySet, publish_profile: bool, publish_relay_list: bool, support_nip04: bool) -> VectorBot {
+    // Build VectorBot with default metadata (SDK sets up client, metadata and giftwrap subscription)
+    let name = profile.name.clone().unwrap_or_default();
+    let picture = profile.picture.clone().unwrap_or_default();
+    let mut pubkey = keys.public_key();
+    let bot = VectorBot::new(
+        keys,
+        name.clone(),
+        name,
+        profile.about.clone().unwrap_or_default(),
+        &picture,
+        &picture,
+        "",
+        "",
+    ).await;
+
+    // Optionally add user-configured relays on top of SDK defaults, subscriptions only go to read relays
+    // and events only to write relays
+    for (relay, marker) in relays.with_markers() {
+        let added = match marker {
+            None => bot.client.add_relay(relay).await,
+            Some(RelayMetadata::Read) => bot.client.add_read_relay(relay).await,
+            Some(RelayMetadata::Write) => bot.client.add_write_relay(relay).await,
+        };
+        if let Err(e) = added {
+            error!("Failed to add relay {}: {:?}", relay, e);
+        }
+    }
+
+    // Ensure connections are established (SDK already connects, but reconnect to include any added relays)
+    bot.client.connect().await;
+
+    // Sign through the bunker, and listen for giftwraps to its key instead of the local one
+    if let Some((signer, remote_pubkey)) = remote_signer {
+        bot.client.set_signer(signer.clone()).await;
+        pubkey = *remote_pubkey;
+        let filter = Filter::new().pubkey(pubkey).kind(Kind::GiftWrap).limit(0);
+        if let Err(e) = bot.client.subscribe(filter, None).await {
+            error!("Failed to subscribe to giftwraps for {}: {:?}", pubkey, e);
+        }
+    }
+
+    // Legacy NIP-04 DMs arrive as plain kind 4 events rather than giftwraps
+    if support_nip04 {
+        let filter = Filter::new().pubkey(pubkey).kind(Kind::EncryptedDirectMessage).limit(0);
+        if let Err(e) = bot.client.subscribe(filter, None).await {
+            error!("Failed to subscribe to NIP-04 DMs: {:?}", e);
+        }
+    }
+
+    // Publish the bridge's public profile (opt-in to avoid spamming relays in development)
+    if publish_profile {
+        match bot.client.set_metadata(profile).await {
+            Ok(output) => info!("Published bridge profile: {}", output.id()),
+            Err(e) => error!("Failed to publish bridge profile: {:?}", e),
+        }
+        publish_app_handler(&bot.client, pubkey, profile).await;
+    }
+
+    // Advertise the configured relays so clients know where to reach the bridge
+    if publish_relay_list {
+        let relay_list = relays.with_markers().into_iter()
+            .filter_map(|(relay, marker)| Some((RelayUrl::parse(relay).ok()?, marker)))
+            .collect();
+        publish_relay_list_event(&bot.client, pubkey, relay_list).await;
+    }
+
+    bot
+}
+
+/// Publishes the NIP-65 relay list, unless the latest one already lists the same relays
+async fn publish_relay_list_event(client: &Client, pubkey: PublicKey, relay_list: HashSet<(RelayUrl, Option<RelayMetadata>)>) {
+    let filter = Filter::new().author(pubkey).kind(Kind::RelayList).limit(1);
+    match client.fetch_events(filter, Duration::from_secs(5)).await {
+        Ok(events) => {
+            let published = events.first_owned().map(|event| nip65::extract_owned_relay_list(event).collect::<HashSet<_>>());
+            if published.as_ref() == Some(&relay_list) {
+                return;
+            }
+        }
+        Err(e) => warn!("Failed to check for an existing relay list: {:?}", e),
+    }
+
+    match client.send_event_builder(EventBuilder::relay_list(relay_list)).await {
+        Ok(output) => info!("Published relay list: {}", output.id()),
+        Err(e) => error!("Failed to publish relay list: {:?}", e),
+    }
+}
+
+/// The notice sent to every subscriber after the bridge's relays change
+pub fn relay_list_notice(relays: &[String]) -> String {
+    format!(
+        "This bridge's relay list has been updated. New relay list:\n{}\nIf you use a client that caches relay lists, please reconnect.",
+        relays.join("\n")
+    )
+}
+
+/// The Discord emoji for a NIP-25 reaction, where `+` is a like and `-` a dislike
+///
+/// Custom `:shortcode:` emoji only exist on Nostr, so they have none.
+fn reaction_emoji(content: &str) -> Option<String> {
+    match content.trim() {
+        "" | "+" => Some("👍".to_string()),
+        "-" => Some("👎".to_string()),
+        emoji if emoji.starts_with(':') => None,
+        emoji => Some(emoji.to_string()),
+    }
+}
+
+/// Subscribes `client` to the DMs addressed to `pubkey`, as the stream is lost with a closed relay pool
+async fn subscribe_to_dms(client: &Client, pubkey: PublicKey, support_nip04: bool) {
+    let mut kinds = vec![Kind::GiftWrap];
+    if support_nip04 {
+        kinds.push(Kind::EncryptedDirectMessage);
+    }
+    let filter = Filter::new().pubkey(pubkey).kinds(kinds).limit(0);
+    if let Err(e) = client.subscribe(filter, None).await {
+        error!("Failed to resubscribe to DMs for {}: {:?}", pubkey, e);
+    }
+}
+
+/// Publishes the NIP-89 handler record so clients can suggest the bridge for its DMs, unless one is recent
+async fn publish_app_handler(client: &Client, pubkey: PublicKey, profile: &Metadata) {
+    let since = Timestamp::now() - APP_HANDLER_REPUBLISH;
+    let filter = Filter::new().author(pubkey).kind(APP_HANDLER_KIND).identifier(APP_HANDLER_ID).since(since);
+    match client.fetch_events(filter, Duration::from_secs(5)).await {
+        Ok(events) if !events.is_empty() => return,
+        Ok(_) => {}
+        Err(e) => warn!("Failed to check for an existing app handler record: {:?}", e),
+    }
+
+    let npub = match pubkey_to_bech32_or_fail(&pubkey) {
+        Ok(npub) => npub,
+        Err(e) => {
+            error!("Failed to publish app handler record: {}", e);
+            return;
+        }
+    };
+    let builder = EventBuilder::new(APP_HANDLER_KIND, profile.as_json()).tags([
+        Tag::identifier(APP_HANDLER_ID),
+        Tag::alt("Vecord Discord Bridge"),
+        Tag::custom(TagKind::k(), [Kind::PrivateDirectMessage.as_u16().to_string()]),
+        Tag::custom(TagKind::custom("web"), [format!("https://njump.me/{}", npub), "npub".to_string()]),
+    ]);
+    match client.send_event_builder(builder).await {
+        Ok(output) => info!("Published app handler record: {}", output.id()),
+        Err(e) => error!("Failed to publish app handler record: {:?}", e),
+    }
+}
+
+/// Publishes a NIP-09 deletion for every kind 0 profile `client`'s key has published
+async fn delete_profile(client: &Client, pubkey: PublicKey) {
+    let filter = Filter::new().author(pubkey).kind(Kind::Metadata);
+    let profiles = match client.fetch_events(filter, Duration::from_secs(5)).await {
+        Ok(events) => events,
+        Err(e) => {
+            error!("Failed to fetch profile for deletion: {:?}", e);
+            return;
+        }
+    };
+
+    let request = profiles.iter()
+        .fold(EventDeletionRequest::new(), |request, event: &Event| request.id(event.id))
+        .reason("Bridge key rotated");
+    if request.ids.is_empty() {
+        return;
+    }
+
+    if let Err(e) = client.send_event_builder(EventBuilder::delete(request)).await {
+        error!("Failed to delete old profile: {:?}", e);
+    }
+}
+
+/// Times how long the relays take to answer a published event with an OK, `None` when none accepted it
+async fn relay_round_trip(client: &Client) -> Option<u64> {
+    let started = Instant::now();
+    match client.send_event_builder(EventBuilder::new(PING_PROBE_KIND, "ping")).await {
+        Ok(output) if !output.success.is_empty() => Some(started.elapsed().as_millis() as u64),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Failed to publish the ping probe: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Renders a subscription reply with the sender's npub and the bridge's current counts
+async fn render_subscription_reply(template: &str, pubkey: &PublicKey, subscribers: &SubscriberList, client: &Client) -> Result<String> {
+    let npub = pubkey_to_bech32_or_fail(pubkey)?;
+    let subscriber_count = subscribers.get_all().await.len().to_string();
+    let relay_count = client.relays().await.len().to_string();
+
+    Ok(commands::render_template(template, &[
+        ("pubkey", &npub),
+        ("subscriber_count", &subscriber_count),
+        ("relay_count", &relay_count),
+    ]))
+}
+
+// Subscriber changes buffered for each admin session that falls behind
+const SUBSCRIBER_EVENT_CAPACITY: usize = 64;
+
+/// A change to the subscriber list, streamed to admin sessions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubscriberEvent {
+    Joined(PublicKey),
+    Left(PublicKey),
+}
+
+// Shards of the subscriber set, picked by the low bits of a pubkey's first byte
+const SUBSCRIBER_SHARDS: usize = 16;
+
+fn shard_index(pubkey: &PublicKey) -> usize {
+    (pubkey.as_bytes()[0] & 0x0F) as usize
+}
+
+/// A set of pubkeys split across independently locked shards, so writers only block readers of one shard
+#[derive(Clone, Default)]
+pub struct ShardedSubscriberList([Arc<RwLock<HashSet<PublicKey>>>; SUBSCRIBER_SHARDS]);
+
+impl ShardedSubscriberList {
+    pub fn from_pubkeys(pubkeys: impl IntoIterator<Item = PublicKey>) -> Self {
+        let mut shards: [HashSet<PublicKey>; SUBSCRIBER_SHARDS] = Default::default();
+        for pubkey in pubkeys {
+            shards[shard_index(&pubkey)].insert(pubkey);
+        }
+        Self(shards.map(|shard| Arc::new(RwLock::new(shard))))
+    }
+
+    fn shard(&self, pubkey: &PublicKey) -> &RwLock<HashSet<PublicKey>> {
+        &self.0[shard_index(pubkey)]
+    }
+
+    pub async fn add(&self, pubkey: PublicKey) -> bool {
+        self.shard(&pubkey).write().await.insert(pubkey)
+    }
+
+    pub async fn remove(&self, pubkey: &PublicKey) -> bool {
+        self.shard(pubkey).write().await.remove(pubkey)
+    }
+
+    pub async fn contains(&self, pubkey: &PublicKey) -> bool {
+        self.shard(pubkey).read().await.contains(pubkey)
+    }
+
+    /// Every pubkey, read under all shard locks at once for a consistent snapshot
+    pub async fn get_all(&self) -> Vec<PublicKey> {
+        // Always locked in shard order, so two snapshots can't deadlock each other
+        let mut shards = Vec::with_capacity(SUBSCRIBER_SHARDS);
+        for shard in &self.0 {
+            shards.push(shard.read().await);
+        }
+        shards.iter().flat_map(|shard| shard.iter().copied()).collect()
+    }
+
+    pub async fn count(&self) -> usize {
+        let mut count = 0;
+        for shard in &self.0 {
+            count += shard.read().await.len();
+        }
+        count
+    }
+}
+
+/// A named bridge's identity and subscribers, for `!subscribe-channel` from another bridge
+#[derive(Clone)]
+pub struct BridgeChannel {
+    pub npub: String,
+    pub subscribers: SubscriberList,
+}
+
+/// Every named bridge of this process, shared so any bridge can subscribe senders to the others
+#[derive(Clone, Default)]
+pub struct BridgeChannels(Arc<std::sync::RwLock<BTreeMap<String, BridgeChannel>>>);
+
+impl BridgeChannels {
+    pub fn register(&self, name: String, channel: BridgeChannel) {
+        self.0.write().unwrap().insert(name, channel);
+    }
+
+    /// Channel names in alphabetical order
+    pub fn names(&self) -> Vec<String> {
+        self.0.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<BridgeChannel> {
+        self.0.read().unwrap().get(name).cloned()
+    }
+}
+
 /// Manages the list of subscribers
 #[derive(Clone)]
-struct SubscriberList {
-    subscribers: Arc<Mutex<HashSet<PublicKey>>>,
+pub struct SubscriberList {
+    subscribers: ShardedSubscriberList,
+    stats: Arc<RwLock<HashMap<PublicKey, SubscriberStats>>>,
+    /// Pubkeys an admin banned, with the reason, until the bridge restarts
+    banned: Arc<RwLock<HashMap<PublicKey, String>>>,
+    events: broadcast::Sender<SubscriberEvent>,
     file_path: Option<String>,
+    /// Serializes background saves so concurrent writes can't interleave
+    save_lock: Arc<Mutex<()>>,
 }
 
 impl SubscriberList {
-    fn new(file_path: Option<String>) -> Result<Self> {
+    pub fn new(file_path: Option<String>) -> Result<Self> {
         let mut subscribers = HashSet::new();
 
         // Try to load subscribers from the file if it exists
@@ -58,291 +475,1822 @@ impl SubscriberList {
         }
 
         Ok(Self {
-            subscribers: Arc::new(Mutex::new(subscribers)),
+            subscribers: ShardedSubscriberList::from_pubkeys(subscribers),
+            stats: Arc::new(RwLock::new(HashMap::new())),
+            banned: Arc::new(RwLock::new(HashMap::new())),
+            events: broadcast::channel(SUBSCRIBER_EVENT_CAPACITY).0,
             file_path,
+            save_lock: Arc::new(Mutex::new(())),
         })
     }
 
-    fn add(&self, pubkey: PublicKey) -> bool {
-        let added;
-        {
-            let mut lock = self.subscribers.lock().unwrap();
-            added = lock.insert(pubkey);
-        }
+    /// Streams every subscribe and unsubscribe from now on
+    pub fn events(&self) -> broadcast::Receiver<SubscriberEvent> {
+        self.events.subscribe()
+    }
+
+    pub async fn add(&self, pubkey: PublicKey) -> bool {
+        let added = self.subscribers.add(pubkey).await;
 
         // Save to file if a path is specified
         if added {
-            self.save_to_file();
+            let stats = SubscriberStats {
+                joined_at: Some(Timestamp::now().as_u64()),
+                ..Default::default()
+            };
+            self.stats.write().await.insert(pubkey, stats);
+            self.spawn_save();
+            // Nobody listening is fine, the event is only for admin sessions
+            let _ = self.events.send(SubscriberEvent::Joined(pubkey));
         }
 
         added
     }
 
-    fn remove(&self, pubkey: &PublicKey) -> bool {
-        let removed;
-        {
-            let mut lock = self.subscribers.lock().unwrap();
-            removed = lock.remove(pubkey);
-        }
+    pub async fn remove(&self, pubkey: &PublicKey) -> bool {
+        let removed = self.subscribers.remove(pubkey).await;
 
         // Save to file if a path is specified
         if removed {
-            self.save_to_file();
+            self.stats.write().await.remove(pubkey);
+            self.spawn_save();
+            let _ = self.events.send(SubscriberEvent::Left(*pubkey));
         }
 
         removed
     }
 
-    fn contains(&self, pubkey: &PublicKey) -> bool {
-        let lock = self.subscribers.lock().unwrap();
-        lock.contains(pubkey)
+    /// Unsubscribes `pubkey` and refuses its `!subscribe` until the bridge restarts
+    pub async fn ban(&self, pubkey: PublicKey, reason: &str) {
+        self.banned.write().await.insert(pubkey, reason.to_string());
+        self.remove(&pubkey).await;
+    }
+
+    /// The reason `pubkey` was banned, if it was
+    pub async fn ban_reason(&self, pubkey: &PublicKey) -> Option<String> {
+        self.banned.read().await.get(pubkey).cloned()
+    }
+
+    pub async fn contains(&self, pubkey: &PublicKey) -> bool {
+        self.subscribers.contains(pubkey).await
+    }
+
+    pub async fn get_all(&self) -> Vec<PublicKey> {
+        self.subscribers.get_all().await
+    }
+
+    /// Every subscriber with their cached profile, taking each list's lock once for bulk reads
+    pub async fn with_metadata_snapshot(&self, cache: &MetadataCache) -> Vec<(PublicKey, Option<UserMetadata>)> {
+        let subscribers = self.subscribers.get_all().await;
+        let profiles = cache.get_many(&subscribers);
+        subscribers.into_iter().zip(profiles).collect()
+    }
+
+    /// Every subscriber with their activity, for admin reporting
+    pub async fn get_all_with_info(&self) -> Vec<(PublicKey, SubscriberStats)> {
+        let subscribers = self.subscribers.get_all().await;
+        let stats = self.stats.read().await;
+        subscribers.into_iter()
+            .map(|pubkey| (pubkey, stats.get(&pubkey).cloned().unwrap_or_default()))
+            .collect()
+    }
+
+    pub async fn count(&self) -> usize {
+        self.subscribers.count().await
+    }
+
+    /// Counts a message forwarded to Discord on behalf of a subscriber
+    pub async fn record_message(&self, pubkey: &PublicKey) {
+        let mut lock = self.stats.write().await;
+        let stats = lock.entry(*pubkey).or_default();
+        stats.message_count += 1;
+        stats.last_message_at = Some(Timestamp::now().as_u64());
+    }
+
+    /// Returns a subscriber's activity, or `None` if they aren't subscribed
+    pub async fn stats(&self, pubkey: &PublicKey) -> Option<SubscriberStats> {
+        if !self.contains(pubkey).await {
+            return None;
+        }
+
+        let lock = self.stats.read().await;
+        Some(lock.get(pubkey).cloned().unwrap_or_default())
+    }
+
+    /// Adds every `p` tag of a NIP-51 people list, returning the list's `d` identifier
+    pub async fn import_from_list(&self, client: &Client, event_id: &str) -> Result<Option<String>> {
+        let id = EventId::parse(event_id).map_err(|e| anyhow!("Invalid subscriber list event ID: {}", e))?;
+        let events = client.fetch_events(Filter::new().id(id), Duration::from_secs(10)).await?;
+        let event = events.first()
+            .ok_or_else(|| anyhow!("Subscriber list event {} not found on any relay", event_id))?;
+
+        let mut imported = 0;
+        for pubkey in event.tags.public_keys() {
+            if self.add(*pubkey).await {
+                imported += 1;
+            }
+        }
+        info!("Imported {} subscribers from list {}", imported, event_id);
+
+        Ok(event.tags.identifier().map(str::to_string))
+    }
+
+    /// Publishes the current subscribers as a kind 30000 follow set
+    pub async fn publish_list(&self, client: &Client, identifier: &str) {
+        let builder = EventBuilder::follow_set(identifier, self.get_all().await);
+        if let Err(e) = client.send_event_builder(builder).await {
+            error!("Failed to publish subscriber list: {:?}", e);
+        }
     }
 
-    fn get_all(&self) -> Vec<PublicKey> {
-        let lock = self.subscribers.lock().unwrap();
-        lock.iter().cloned().collect()
+    /// Saves in the background so the file write doesn't delay message processing
+    fn spawn_save(&self) {
+        if self.file_path.is_some() {
+            let list = self.clone();
+            tokio::spawn(async move { list.save_to_file_async().await });
+        }
     }
 
-    fn save_to_file(&self) {
+    async fn save_to_file_async(&self) {
         if let Some(path) = &self.file_path {
-            let lock = self.subscribers.lock().unwrap();
-            if let Ok(mut file) = fs::File::create(path) {
-                for pubkey in lock.iter() {
-                    let bech32 = pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_string());
-                    if let Err(e) = writeln!(file, "{}", bech32) {
-                        error!("Failed to write subscriber to file: {}", e);
-                    }
+            let _guard = self.save_lock.lock().await;
+
+            // Snapshot the list so the lock isn't held during file I/O
+            let contents: Result<String> = self.subscribers.get_all().await
+                .iter()
+                .map(|pubkey| pubkey_to_bech32_or_fail(pubkey).map(|npub| format!("{}\n", npub)))
+                .collect();
+            let contents = match contents {
+                Ok(contents) => contents,
+                Err(e) => {
+                    error!("Failed to save subscribers: {}", e);
+                    return;
                 }
-            } else {
-                error!("Failed to open subscribers file for writing: {}", path);
+            };
+
+            // Written beside the file and renamed over it, so a crash can't leave the list truncated
+            let tmp_path = path.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                persist::atomic_write(&tmp_path, |file| file.write_all(contents.as_bytes()))
+            }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            if let Err(e) = result {
+                error!("Failed to write subscribers file {}: {}", path, e);
             }
         }
     }
 }
 
-pub struct NostrClient {
-    keys: Keys,
-    relays: Vec<String>,
-    subscribers: SubscriberList,
-    metadata_cache: MetadataCache,
-    bot: Option<VectorBot>,
+/// Outcome of checking a message against the spam or flood detector
+#[derive(Debug, PartialEq, Eq)]
+pub enum SpamCheck {
+    Allowed,
+    /// The sender just crossed the threshold and is now muted
+    Triggered,
+    /// The sender is still serving an earlier mute
+    Muted,
 }
 
-impl NostrClient {
-    pub fn new(config: &Config) -> Result<Self> {
-        // Create keys from secret key
-        let secret_key = SecretKey::from_str(&config.nostr_private_key)?;
-        let keys = Keys::new(secret_key);
-
-        // Initialize subscriber list with optional file path
-        let subscribers = SubscriberList::new(config.subscribers_file.clone())?;
-
-        // Initialize metadata cache
-        let metadata_cache = MetadataCache::new(config.metadata_cache_file.clone())?;
+/// Mutes Nostr senders who repeat the same message too often
+pub struct SpamDetector {
+    history: HashMap<PublicKey, VecDeque<(u64 /*timestamp*/, u64 /*content_hash*/)>>,
+    muted_until: HashMap<PublicKey, u64>,
+    duplicate_threshold: u8,
+    window_secs: u64,
+    mute_secs: u64,
+}
 
-        Ok(Self {
-            keys,
-            relays: config.nostr_relays.clone(),
-            subscribers,
-            metadata_cache,
-            bot: None,
-        })
+impl SpamDetector {
+    pub fn new(duplicate_threshold: u8, window_secs: u64, mute_secs: u64) -> Self {
+        Self {
+            history: HashMap::new(),
+            muted_until: HashMap::new(),
+            duplicate_threshold,
+            window_secs,
+            mute_secs,
+        }
     }
 
-    pub async fn start(
-        &mut self,
-        discord_sender: mpsc::Sender<BridgeMessage>,
-    ) -> Result<mpsc::Sender<BridgeMessage>> {
-        // Build VectorBot with default metadata (SDK sets up client, metadata and giftwrap subscription)
-        let bot = VectorBot::new(
-            self.keys.clone(),
-            "Vecord",
-            "Vecord",
-            "The Vecord Bridge - Bringing the anonymity of Vector to the Discord realm.",
-            "https://jskitty.cat/vector/img/vecord.png",
-            "https://jskitty.cat/vector/img/vecord.png",
-            "",
-            "",
-        ).await;
-
-        // Optionally add user-configured relays on top of SDK defaults
-        for relay in &self.relays {
-            if let Err(e) = bot.client.add_relay(relay).await {
-                error!("Failed to add relay {}: {:?}", relay, e);
+    /// Records a message sent at `now` and reports whether it should be dropped
+    pub fn check(&mut self, sender: &PublicKey, content: &str, now: u64) -> SpamCheck {
+        if let Some(until) = self.muted_until.get(sender) {
+            if now < *until {
+                return SpamCheck::Muted;
             }
+            self.muted_until.remove(sender);
         }
 
-        // Ensure connections are established (SDK already connects, but reconnect to include any added relays)
-        bot.client.connect().await;
+        // Forget messages that have left the window
+        let history = self.history.entry(*sender).or_default();
+        while history.front().is_some_and(|(ts, _)| now.saturating_sub(*ts) >= self.window_secs) {
+            history.pop_front();
+        }
 
-        // Wait briefly for connections to establish
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        let hash = fnv1a_64(content.as_bytes());
+        history.push_back((now, hash));
 
-        // Create a channel for sending messages to Nostr
-        let (nostr_sender, mut nostr_receiver) = mpsc::channel::<BridgeMessage>(100);
+        let duplicates = history.iter().filter(|(_, h)| *h == hash).count();
+        if duplicates > self.duplicate_threshold as usize {
+            self.history.remove(sender);
+            self.muted_until.insert(*sender, now + self.mute_secs);
+            return SpamCheck::Triggered;
+        }
 
-        // Clone bot for the sender task
-        let bot_clone = bot.clone();
-        let subscribers_clone = self.subscribers.clone();
+        SpamCheck::Allowed
+    }
+}
 
-        // Spawn a task to handle sending messages from Discord to Nostr
-        tokio::spawn(async move {
-            while let Some(message) = nostr_receiver.recv().await {
-                if let BridgeMessage::Discord { author, content, image } = message {
-                    // Prepare text content
-                    let nostr_message = format!("[Discord] {}: {}", author, content);
+/// Cools down Nostr senders who burst too many messages, whatever their content
+pub struct FloodDetector {
+    /// Recent message times per sender, holding at most one more than the threshold
+    windows: HashMap<PublicKey, VecDeque<u64>>,
+    cooldown_until: HashMap<PublicKey, u64>,
+    threshold: usize,
+    window_secs: u64,
+    cooldown_secs: u64,
+}
 
-                    // Get subscribers snapshot
-                    let subscribers = subscribers_clone.get_all();
+impl FloodDetector {
+    pub fn new(threshold: usize, window_secs: u64, cooldown_secs: u64) -> Self {
+        Self {
+            windows: HashMap::new(),
+            cooldown_until: HashMap::new(),
+            threshold,
+            window_secs,
+            cooldown_secs,
+        }
+    }
 
-                    for pubkey in subscribers {
-                        // Use Vector SDK Channel API
-                        let chat = bot_clone.get_chat(pubkey).await;
+    /// Records a message sent at `now` and reports whether it should be dropped
+    pub fn check(&mut self, sender: &PublicKey, now: u64) -> SpamCheck {
+        if let Some(until) = self.cooldown_until.get(sender) {
+            if now < *until {
+                return SpamCheck::Muted;
+            }
+            self.cooldown_until.remove(sender);
+        }
 
-                        // If there's an image, send it first
-                        if let Some(img) = &image {
-                            let file = AttachmentFile::from_bytes(img.bytes.as_slice());
-                            let ok_file = chat.send_private_file(Some(file)).await;
-                            if !ok_file {
-                                error!("Error sending image to Nostr user {}", pubkey);
-                            } else {
-                                info!("Sent image to Nostr user: {}", pubkey);
-                            }
-                        }
+        let window = self.windows.entry(*sender).or_default();
+        while window.front().is_some_and(|ts| now.saturating_sub(*ts) >= self.window_secs) {
+            window.pop_front();
+        }
+        window.push_back(now);
+        // Older entries can't change the outcome once the window holds more than the threshold
+        if window.len() > self.threshold + 1 {
+            window.pop_front();
+        }
 
-                        // Send the text content
-                        let ok_text = chat.send_private_message(&nostr_message).await;
-                        if !ok_text {
-                            error!("Error sending private message to Nostr user {}", pubkey);
-                        } else {
-                            info!("Sent Discord message to Nostr user: {}", pubkey);
-                        }
-                    }
-                }
-            }
-        });
+        if window.len() > self.threshold {
+            self.windows.remove(sender);
+            self.cooldown_until.insert(*sender, now + self.cooldown_secs);
+            return SpamCheck::Triggered;
+        }
 
-        // Get our pubkey for filtering own messages
-        let my_pubkey = self.keys.public_key();
+        SpamCheck::Allowed
+    }
+}
 
-        // Store the bot
-        self.bot = Some(bot.clone());
+/// 64-bit FNV-1a, good enough to spot duplicate messages
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
 
-        // Clone for the notification handler
-        let subscribers_clone = self.subscribers.clone();
-        let metadata_cache_clone = self.metadata_cache.clone();
-        let bot_clone = bot.clone();
+// How often the relay health monitor checks connection state and latency
+const RELAY_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
 
-        // Spawn a task to handle incoming Nostr private messages
-        tokio::spawn(async move {
-            let mut notifications = bot.client.notifications();
+// Weight of the newest latency sample in the moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
 
-            while let Ok(notification) = notifications.recv().await {
-                match notification {
-                    RelayPoolNotification::Event { event, relay_url: _, subscription_id: _ } => {
-                        // Skip our own events to prevent loops
-                        if event.pubkey == my_pubkey {
-                            continue;
-                        }
+/// Connection state and publish results for one relay
+#[derive(Debug, Clone, Default)]
+pub struct RelayStats {
+    pub connected: bool,
+    /// Events the relay accepted with an `OK`
+    pub messages_sent: u64,
+    /// Events the relay rejected with an `OK`
+    pub messages_failed: u64,
+    /// Exponentially weighted moving average, unset until the first sample
+    pub latency_ewma_ms: Option<f64>,
+}
 
-                        // Try to decrypt the message via SDK-configured client
-                        if let Ok(UnwrappedGift { rumor, sender }) = bot.client.unwrap_gift_wrap(&event).await {
-                            // Only process encrypted direct messages
-                            if rumor.kind != Kind::PrivateDirectMessage {
-                                continue;
-                            };
+/// Per-relay stats fed by the send path and the relay health monitor
+#[derive(Clone, Default)]
+pub struct RelayMonitor {
+    relays: Arc<std::sync::Mutex<HashMap<String, RelayStats>>>,
+}
 
-                            // Create some simplified utility variables
-                            let sender_pubkey = sender;
-                            let message_content = rumor.content.trim();
+impl RelayMonitor {
+    /// Records a relay's `OK` reply to one of our events
+    pub fn record(&self, relay_url: &str, accepted: bool) {
+        let mut relays = self.relays.lock().unwrap();
+        let stats = relays.entry(relay_url.to_string()).or_default();
+        if accepted {
+            stats.messages_sent += 1;
+            metrics::RELAY_PUBLISH_OK.inc(relay_url);
+        } else {
+            stats.messages_failed += 1;
+            metrics::RELAY_PUBLISH_FAIL.inc(relay_url);
+        }
+    }
 
-                            // Handle subscription commands
-                            if message_content == "!subscribe" {
-                                if subscribers_clone.add(sender_pubkey) {
-                                    info!("New subscriber: {}", sender_pubkey);
-                                    // Send confirmation
-                                    let chat = bot_clone.get_chat(sender_pubkey).await;
-                                    let _ = chat.send_private_message(
-                                        "You are now subscribed to the Discord channel. You will receive all messages from the Discord channel. Send !unsubscribe to stop receiving messages."
-                                    ).await;
-                                } else {
-                                    // Already subscribed
-                                    let chat = bot_clone.get_chat(sender_pubkey).await;
-                                    let _ = chat.send_private_message(
-                                        "You are already subscribed to the Discord channel."
-                                    ).await;
-                                }
-                                continue;
-                            } else if message_content == "!unsubscribe" {
-                                if subscribers_clone.remove(&sender_pubkey) {
-                                    info!("Unsubscribed: {}", sender_pubkey);
-                                    // Send confirmation
-                                    let chat = bot_clone.get_chat(sender_pubkey).await;
-                                    let _ = chat.send_private_message(
-                                        "You have been unsubscribed from the Discord channel. You will no longer receive messages."
-                                    ).await;
-                                } else {
-                                    // Not subscribed
-                                    let chat = bot_clone.get_chat(sender_pubkey).await;
-                                    let _ = chat.send_private_message(
-                                        "You are not currently subscribed to the Discord channel."
-                                    ).await;
-                                }
-                                continue;
-                            } else if message_content == "!help" {
-                                // Send help information
-                                let chat = bot_clone.get_chat(sender_pubkey).await;
-                                let _ = chat.send_private_message(
-                                    "Available commands:\n!subscribe - Start receiving Discord messages\n!unsubscribe - Stop receiving Discord messages\n!help - Show this help message"
-                                ).await;
-                                continue;
-                            }
+    /// Records a health check of a relay's connection
+    pub fn record_health(&self, relay_url: &str, connected: bool, latency: Option<Duration>) {
+        let mut relays = self.relays.lock().unwrap();
+        let stats = relays.entry(relay_url.to_string()).or_default();
+        stats.connected = connected;
+        if let Some(latency) = latency {
+            let sample = latency.as_secs_f64() * 1000.0;
+            stats.latency_ewma_ms = Some(match stats.latency_ewma_ms {
+                Some(average) => LATENCY_EWMA_ALPHA * sample + (1.0 - LATENCY_EWMA_ALPHA) * average,
+                None => sample,
+            });
+        }
+    }
 
-                            // Only relay messages from subscribed users
-                            if subscribers_clone.contains(&sender_pubkey) {
-                                // Try to fetch user metadata (via SDK client)
-                                let metadata = match metadata_cache_clone.fetch_metadata(&bot.client, &sender_pubkey).await {
-                                    Ok(metadata) => metadata,
-                                    Err(e) => {
-                                        error!("Failed to fetch metadata for {}: {}", sender_pubkey, e);
-                                        // Create a default metadata entry if fetch fails
-                                        UserMetadata::new(&sender_pubkey)
-                                    }
-                                };
+    /// Stats for every relay seen so far, sorted by relay URL
+    pub fn snapshot(&self) -> Vec<(String, RelayStats)> {
+        let mut relays: Vec<_> = self.relays.lock().unwrap()
+            .iter()
+            .map(|(relay, stats)| (relay.clone(), stats.clone()))
+            .collect();
+        relays.sort_by(|(a, _), (b, _)| a.cmp(b));
+        relays
+    }
+}
 
-                                // Get the best username for display
-                                let username = metadata.get_best_name();
+// Limits on a subscriber's personal keyword filters
+const MAX_FILTER_KEYWORD_LEN: usize = 50;
+const MAX_FILTER_KEYWORDS: usize = 20;
 
-                                // Create metadata for the message
-                                let pubkey_str = sender_pubkey.to_bech32().unwrap_or_else(|_| sender_pubkey.to_string());
-                                let message_metadata = NostrMessageMetadata {
-                                    username: username.clone(),
-                                    pubkey: pubkey_str,
-                                    avatar_url: metadata.picture,
-                                };
+/// Keywords each subscriber doesn't want to receive Discord messages about
+#[derive(Clone)]
+pub struct SubscriberFilters {
+    filters: Arc<RwLock<HashMap<PublicKey, Vec<String>>>>,
+    file_path: Option<String>,
+}
 
-                                // Create the bridge message
-                                let bridge_message = BridgeMessage::Nostr {
-                                    content: message_content.to_string(),
-                                    metadata: message_metadata,
-                                };
+impl SubscriberFilters {
+    pub fn new(file_path: Option<String>) -> Result<Self> {
+        let mut filters = HashMap::new();
 
-                                // Send the decrypted message to Discord
-                                if let Err(e) = discord_sender.send(bridge_message).await {
-                                    error!("Error forwarding message to Discord: {}", e);
-                                } else {
-                                    info!("Forwarded Nostr DM to Discord from: {}", username);
+        // Try to load filters from the file if it exists
+        if let Some(path) = &file_path {
+            if let Ok(contents) = fs::read_to_string(path) {
+                match serde_json::from_str::<HashMap<String, Vec<String>>>(&contents) {
+                    Ok(loaded) => {
+                        for (key, keywords) in loaded {
+                            match parse_pubkey(&key) {
+                                Ok(pubkey) => {
+                                    filters.insert(pubkey, keywords);
                                 }
+                                Err(_) => error!("Failed to parse pubkey: {}", key),
+                            }
+                        }
+                        info!("Loaded keyword filters for {} subscribers", filters.len());
+                    }
+                    Err(e) => warn!("Failed to parse subscriber filters file: {}", e),
+                }
+            }
+        }
+
+        Ok(Self {
+            filters: Arc::new(RwLock::new(filters)),
+            file_path,
+        })
+    }
+
+    /// Adds a keyword, returning false if it was already present
+    pub async fn add(&self, pubkey: PublicKey, keyword: &str) -> Result<bool> {
+        let keyword = keyword.to_lowercase();
+        if keyword.chars().count() > MAX_FILTER_KEYWORD_LEN {
+            return Err(anyhow!("Keywords can be at most {} characters long.", MAX_FILTER_KEYWORD_LEN));
+        }
+
+        {
+            let mut lock = self.filters.write().await;
+            let keywords = lock.entry(pubkey).or_default();
+            if keywords.contains(&keyword) {
+                return Ok(false);
+            }
+            if keywords.len() >= MAX_FILTER_KEYWORDS {
+                return Err(anyhow!("You can have at most {} filters.", MAX_FILTER_KEYWORDS));
+            }
+            keywords.push(keyword);
+        }
+
+        self.save_to_file().await;
+        Ok(true)
+    }
+
+    pub async fn remove(&self, pubkey: &PublicKey, keyword: &str) -> bool {
+        let keyword = keyword.to_lowercase();
+        let removed = {
+            let mut lock = self.filters.write().await;
+            let Some(keywords) = lock.get_mut(pubkey) else {
+                return false;
+            };
+            let before = keywords.len();
+            keywords.retain(|k| *k != keyword);
+            let removed = keywords.len() < before;
+            if keywords.is_empty() {
+                lock.remove(pubkey);
+            }
+            removed
+        };
+
+        if removed {
+            self.save_to_file().await;
+        }
+
+        removed
+    }
+
+    pub async fn list(&self, pubkey: &PublicKey) -> Vec<String> {
+        let lock = self.filters.read().await;
+        lock.get(pubkey).cloned().unwrap_or_default()
+    }
+
+    /// Returns true if the content contains any of the subscriber's keywords
+    pub async fn blocks(&self, pubkey: &PublicKey, content: &str) -> bool {
+        let lock = self.filters.read().await;
+        match lock.get(pubkey) {
+            Some(keywords) => {
+                let content = content.to_lowercase();
+                keywords.iter().any(|keyword| content.contains(keyword.as_str()))
+            }
+            None => false,
+        }
+    }
+
+    async fn save_to_file(&self) {
+        if let Some(path) = &self.file_path {
+            // Snapshot the filters so the lock isn't held during file I/O
+            let snapshot: Result<HashMap<String, Vec<String>>> = {
+                let lock = self.filters.read().await;
+                lock.iter()
+                    .map(|(pubkey, keywords)| Ok((pubkey_to_bech32_or_fail(pubkey)?, keywords.clone())))
+                    .collect()
+            };
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    error!("Failed to save subscriber filters: {}", e);
+                    return;
+                }
+            };
+
+            match serde_json::to_vec(&snapshot) {
+                Ok(json) => {
+                    let write_path = path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        persist::atomic_write(&write_path, |file| file.write_all(&json))
+                    }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    if let Err(e) = result {
+                        error!("Failed to write subscriber filters file {}: {}", path, e);
+                    }
+                }
+                Err(e) => error!("Failed to serialize subscriber filters: {}", e),
+            }
+        }
+    }
+}
+
+pub struct NostrClient {
+    keys: Keys,
+    relays: RelaySet,
+    allowed_kinds: Vec<u16>,
+    strict_giftwrap: bool,
+    support_nip04: bool,
+    /// Show subscribers' NIP-25 reactions on the Discord messages they react to
+    bridge_reactions: bool,
+    mention_on_nostr_reply: bool,
+    delivery_receipt_reactions: bool,
+    min_pow_difficulty: Option<u8>,
+    subscriber_list_event_id: Option<String>,
+    sync_subscriber_list: bool,
+    spam_duplicate_threshold: u8,
+    spam_window_secs: u64,
+    spam_mute_secs: u64,
+    flood_threshold: usize,
+    flood_window_secs: u64,
+    flood_cooldown_secs: u64,
+    profile: Metadata,
+    publish_profile: bool,
+    publish_relay_list: bool,
+    subscribers: SubscriberList,
+    subscriber_filters: SubscriberFilters,
+    preferences: PreferenceStore,
+    inbox_relays: InboxRelays,
+    metadata_cache: MetadataCache,
+    metadata_refresh_interval: Duration,
+    content_filter: ContentFilter,
+    notify_filtered_sender: bool,
+    /// Lowercased keyword Nostr messages need to be forwarded, unset to forward everything
+    content_must_contain: Option<String>,
+    admin_pubkeys: HashSet<PublicKey>,
+    enabled_commands: HashSet<String>,
+    silent_disabled_commands: bool,
+    allow_key_rotation: bool,
+    bridge_pause: BridgePause,
+    /// Relays from `!addrelay` awaiting their `!confirm`, by token
+    pending_relays: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    subscribe_message: String,
+    unsubscribe_message: String,
+    remote_signer: Option<RemoteSigner>,
+    relay_monitor: RelayMonitor,
+    stats: Arc<BridgeStats>,
+    /// Times the notification stream closed and was re-opened
+    notification_reconnects: Arc<AtomicU32>,
+    /// Discord messages posted for Nostr messages, filled in by the Discord bot
+    message_ids: MessageIdMapping,
+    /// Zone whose midnight starts a new day for `!stats`
+    timezone: Tz,
+    /// The bot currently in use, swapped by `!rotate-key`, unset until `start`
+    bot: Option<Arc<RwLock<VectorBot>>>,
+    /// This bridge's `[[bridges]]` name, registered in `channels` when set
+    bridge_name: Option<String>,
+    /// Paces DMs to the write relays configured with a rate
+    relay_limits: RelayRateLimits,
+    /// The named bridges senders can pick with `!subscribe-channel`
+    channels: BridgeChannels,
+    /// Discord usernames linked with `!register-discord`, shared by all bridges
+    usernames: UsernameMap,
+    /// Where Discord messages are stored once they reach a subscriber, unset when archiving is off
+    archive: Option<MessageArchive>,
+    /// The forwarding task's input, so messages held while paused are federated when flushed
+    discord_to_nostr: Option<PrioritySender<BridgeMessage>>,
+}
+
+impl NostrClient {
+    pub fn new(config: &Config) -> Result<Self> {
+        // Create keys from secret key, a bunker only needs a throwaway key to talk to it
+        let keys = if config.nostr_private_key.is_empty() && config.nostr_bunker_url.is_some() {
+            Keys::generate()
+        } else {
+            Keys::new(config::parse_secret_key(&config.nostr_private_key)?)
+        };
+
+        // Sign through a NIP-46 bunker instead of the local key when configured
+        let remote_signer = match &config.nostr_bunker_url {
+            Some(url) => Some(connect_bunker(
+                url,
+                config.nostr_bunker_secret.as_deref(),
+                config.nostr_bunker_pubkey.as_deref(),
+                &keys,
+            )?),
+            None => None,
+        };
+
+        // Initialize subscriber list with optional file path
+        let subscribers = SubscriberList::new(config.subscribers_file.clone())?;
+
+        // Initialize per-subscriber keyword filters
+        let subscriber_filters = SubscriberFilters::new(config.subscriber_filters_file.clone())?;
+
+        // Initialize per-subscriber message preferences
+        let preferences = PreferenceStore::new(config.preferences_file.clone())?;
+
+        // Initialize metadata cache
+        let metadata_cache = MetadataCache::new(config.metadata_cache_file.clone())?;
+
+        let admin_pubkeys = config.admin_pubkeys.iter()
+            .map(|admin| parse_pubkey(admin))
+            .collect::<Result<HashSet<PublicKey>>>()?;
+
+        // Compile the content filter up front so bad patterns fail at startup
+        let content_filter = ContentFilter::new(&config.content_filter_patterns)?;
+
+        // Build the bridge's own profile from the configuration
+        let profile = Metadata {
+            name: Some(config.bot_name.clone()),
+            display_name: Some(config.bot_name.clone()),
+            about: Some(config.bot_description.clone()),
+            picture: Some(config.bot_picture_url.clone().unwrap_or_else(|| DEFAULT_BOT_PICTURE.to_string())),
+            website: config.bot_website.clone(),
+            ..Default::default()
+        };
+
+        // DMs through the write relays are paced per relay, those to inbox relays aren't
+        let relay_limits = RelayRateLimits::new(
+            &config.relay_configs,
+            &config.nostr_write_relays,
+            config.nostr_max_msgs_per_sec,
+        );
+
+        Ok(Self {
+            keys,
+            relays: RelaySet {
+                read: config.nostr_read_relays.clone(),
+                write: config.nostr_write_relays.clone(),
+            },
+            allowed_kinds: config.allowed_nostr_event_kinds.clone(),
+            strict_giftwrap: config.strict_giftwrap,
+            support_nip04: config.support_nip04,
+            bridge_reactions: config.bridge_reactions,
+            mention_on_nostr_reply: config.discord_mention_on_nostr_reply,
+            delivery_receipt_reactions: config.delivery_receipt_reactions,
+            min_pow_difficulty: config.min_pow_difficulty,
+            subscriber_list_event_id: config.nostr_subscriber_list_event_id.clone(),
+            sync_subscriber_list: config.sync_subscriber_list,
+            spam_duplicate_threshold: config.spam_duplicate_threshold,
+            spam_window_secs: config.spam_window_secs,
+            spam_mute_secs: config.spam_mute_secs,
+            flood_threshold: config.flood_threshold,
+            flood_window_secs: config.flood_window_secs,
+            flood_cooldown_secs: config.flood_cooldown_secs,
+            profile,
+            publish_profile: config.publish_profile,
+            publish_relay_list: config.publish_relay_list,
+            subscribers,
+            subscriber_filters,
+            preferences,
+            inbox_relays: InboxRelays::new(relay_limits.clone()),
+            metadata_cache,
+            metadata_refresh_interval: Duration::from_secs(config.metadata_refresh_interval_secs),
+            content_filter,
+            notify_filtered_sender: config.notify_filtered_sender,
+            content_must_contain: config.nostr_content_must_contain.as_ref().map(|keyword| keyword.to_lowercase()),
+            admin_pubkeys,
+            enabled_commands: config.enabled_commands.clone(),
+            silent_disabled_commands: config.silent_disabled_commands,
+            allow_key_rotation: config.allow_key_rotation,
+            bridge_pause: BridgePause::new(config.pause_buffer_size),
+            pending_relays: Arc::new(Mutex::new(HashMap::new())),
+            subscribe_message: config.subscribe_message.clone(),
+            unsubscribe_message: config.unsubscribe_message.clone(),
+            remote_signer,
+            relay_monitor: RelayMonitor::default(),
+            stats: Arc::new(BridgeStats::default()),
+            notification_reconnects: Arc::new(AtomicU32::new(0)),
+            message_ids: MessageIdMapping::default(),
+            timezone: time::parse_timezone(&config.timezone).unwrap_or(Tz::UTC),
+            bot: None,
+            bridge_name: config.bridge_name.clone(),
+            relay_limits,
+            channels: BridgeChannels::default(),
+            usernames: UsernameMap::default(),
+            archive: None,
+            discord_to_nostr: None,
+        })
+    }
+
+    /// Shares the Discord bot's record of bridged messages, so Discord reactions reach their Nostr authors
+    pub fn with_message_ids(mut self, message_ids: MessageIdMapping) -> Self {
+        self.message_ids = message_ids;
+        self
+    }
+
+    /// Shares the registry of named bridges, so `!subscribe-channel` can reach the other bridges' subscribers
+    pub fn with_channels(mut self, channels: BridgeChannels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Shares the username registrations with the Discord bot, which DMs mentions of them
+    pub fn with_usernames(mut self, usernames: UsernameMap) -> Self {
+        self.usernames = usernames;
+        self
+    }
+
+    /// Archives Discord messages once they've been delivered to at least one subscriber
+    pub fn with_archive(mut self, archive: Option<MessageArchive>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Flushes Discord messages held while paused back through the forwarding task that broadcasts them to peers
+    pub fn with_discord_to_nostr(mut self, sender: PrioritySender<BridgeMessage>) -> Self {
+        self.discord_to_nostr = Some(sender);
+        self
+    }
+
+    /// Pause control shared with the forwarding tasks, toggled by `!pause` and `!resume`
+    pub fn bridge_pause(&self) -> BridgePause {
+        self.bridge_pause.clone()
+    }
+
+    pub fn subscribers(&self) -> SubscriberList {
+        self.subscribers.clone()
+    }
+
+    pub fn metadata_cache(&self) -> MetadataCache {
+        self.metadata_cache.clone()
+    }
+
+    pub fn relay_monitor(&self) -> RelayMonitor {
+        self.relay_monitor.clone()
+    }
+
+    pub fn current_bot(&self) -> Option<Arc<RwLock<VectorBot>>> {
+        self.bot.clone()
+    }
+
+    /// How often the Nostr notification stream has had to be re-opened since startup
+    pub fn notification_reconnects(&self) -> u32 {
+        self.notification_reconnects.load(Ordering::Relaxed)
+    }
+
+    /// Usage counters shared with the forwarding tasks, reported by `!stats`
+    pub fn stats(&self) -> Arc<BridgeStats> {
+        self.stats.clone()
+    }
+
+    pub async fn start(
+        &mut self,
+        discord_sender: PrioritySender<BridgeMessage>,
+    ) -> Result<PrioritySender<BridgeMessage>> {
+        let mut bot = connect_bot(self.keys.clone(), self.remote_signer.as_ref(), &self.profile, &self.relays, self.publish_profile, self.publish_relay_list, self.support_nip04).await;
+
+        // Wait briefly for connections to establish
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // Bootstrap subscribers from a NIP-51 people list
+        let mut list_identifier = DEFAULT_SUBSCRIBER_LIST_ID.to_string();
+        if let Some(event_id) = &self.subscriber_list_event_id {
+            match self.subscribers.import_from_list(&bot.client, event_id).await {
+                Ok(identifier) => list_identifier = identifier.unwrap_or(list_identifier),
+                Err(e) => error!("Failed to import subscriber list: {}", e),
+            }
+        }
+
+        // Create a channel for sending messages to Nostr
+        let (nostr_sender, mut nostr_receiver) = priority_channel::<BridgeMessage>(100);
+
+        // Shared with the sender task so a key rotation switches both directions
+        let current_bot = Arc::new(RwLock::new(bot.clone()));
+        let current_bot_clone = current_bot.clone();
+        let subscribers_clone = self.subscribers.clone();
+        let subscriber_filters_clone = self.subscriber_filters.clone();
+        let preferences_clone = self.preferences.clone();
+        let inbox_relays = self.inbox_relays.clone();
+        let relay_limits = self.relay_limits.clone();
+        for relay in relay_limits.relays() {
+            info!("Rate limiting DMs published to {}", relay);
+        }
+        let message_ids = self.message_ids.clone();
+        let mention_on_nostr_reply = self.mention_on_nostr_reply;
+        let delivery_receipt_reactions = self.delivery_receipt_reactions;
+        let receipt_sender = discord_sender.clone();
+        let stats = self.stats.clone();
+        let archive = self.archive.clone();
+
+        // Spawn a task to handle sending messages from Discord to Nostr
+        tokio::spawn(async move {
+            while let Some(message) = nostr_receiver.recv().await {
+                let archived = (archive.is_some() && matches!(message, BridgeMessage::Discord { .. })).then(|| message.clone());
+                if let BridgeMessage::Discord { author, content, image, image_url, author_id, message_id, guild_name, span, .. } = message {
+                    async {
+                        // Images sent by URL, such as stickers, are linked in the text
+                        let content = match image_url {
+                            Some(url) if content.is_empty() => url,
+                            Some(url) => format!("{}\n{}", content, url),
+                            None => content,
+                        };
+
+                        // Get subscribers snapshot
+                        let subscribers = subscribers_clone.get_all().await;
+                        let bot_clone = current_bot_clone.read().await.clone();
+                        let (mut delivered, mut failed) = (0, 0);
+
+                        for pubkey in subscribers {
+                            // Respect the subscriber's personal keyword filters
+                            if subscriber_filters_clone.blocks(&pubkey, &content).await {
+                                continue;
+                            }
+
+                            // Use Vector SDK Channel API
+                            let chat = bot_clone.get_chat(pubkey).await;
+
+                            // If there's an image, send it first
+                            let mut ok_file = true;
+                            if let Some(img) = &image {
+                                let file = AttachmentFile::from_bytes(img.bytes.as_slice());
+                                relay_limits.acquire_all().await;
+                                ok_file = chat.send_private_file(Some(file)).await;
+                                if !ok_file {
+                                    stats.failed_sends_total.inc();
+                                    error!("Error sending image to Nostr user {}", pubkey);
+                                } else {
+                                    info!("Sent image to Nostr user: {}", pubkey);
+                                }
+                            }
+
+                            // Send the text content in the subscriber's preferred format
+                            let nostr_message = preferences_clone.get(&pubkey).await.format_discord_message(&author, &content, guild_name.as_deref());
+                            let ok_text = match author_id.filter(|_| mention_on_nostr_reply) {
+                                // Replies name the rumor the subscriber received, which leads back to the Discord author
+                                Some(author_id) => {
+                                    let rumor_id = inbox_relays.send_tracked_private_message(&bot_clone.client, pubkey, &nostr_message).await;
+                                    if let Some(rumor_id) = rumor_id {
+                                        message_ids.insert_discord_author(rumor_id, author_id);
+                                    }
+                                    rumor_id.is_some()
+                                }
+                                None => inbox_relays.send_private_message(&bot_clone.client, &chat, pubkey, &nostr_message).await,
+                            };
+                            if !ok_text {
+                                stats.failed_sends_total.inc();
+                                error!("Error sending private message to Nostr user {}", pubkey);
+                            } else {
+                                info!("Sent Discord message to Nostr user: {}", pubkey);
+                            }
+                            if ok_file && ok_text {
+                                delivered += 1;
                             } else {
-                                // Inform the user they need to subscribe first
+                                failed += 1;
+                            }
+                        }
+                        stats.discord_to_nostr_total.inc();
+
+                        // Only messages that reached a subscriber are archived
+                        if let (Some(archive), Some(archived)) = (&archive, archived.filter(|_| delivered > 0)) {
+                            if let Err(e) = archive.insert(&archived, message_id).await {
+                                warn!("Failed to archive message: {}", e);
+                            }
+                        }
+
+                        // Nobody to deliver to isn't worth a receipt
+                        if let Some(discord_message_id) = message_id.filter(|_| delivery_receipt_reactions && delivered + failed > 0) {
+                            let emoji = match (delivered, failed) {
+                                (_, 0) => "✅",
+                                (0, _) => "❌",
+                                _ => "⚠️",
+                            };
+                            let receipt = BridgeMessage::DeliveryReceipt {
+                                discord_message_id,
+                                emoji: emoji.to_string(),
+                                span: Span::current(),
+                            };
+                            if let Err(e) = receipt_sender.send(receipt).await {
+                                error!("Error sending delivery receipt to Discord: {}", e);
+                            }
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                } else if let BridgeMessage::DiscordReaction { discord_message_id, emoji, span } = message {
+                    async {
+                        // Only messages bridged from Nostr have an author to tell
+                        let Some(original) = message_ids.get_nostr(discord_message_id) else {
+                            return;
+                        };
+                        let bot_clone = current_bot_clone.read().await.clone();
+                        let chat = bot_clone.get_chat(original.author).await;
+                        relay_limits.acquire_all().await;
+                        if chat.send_reaction(original.event_id.to_hex(), emoji).await {
+                            info!("Sent Discord reaction to Nostr user: {}", original.author);
+                        } else {
+                            stats.failed_sends_total.inc();
+                            error!("Error sending reaction to Nostr user {}", original.author);
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                } else if let BridgeMessage::Mention { pubkey, author, content, span } = message {
+                    async {
+                        let Ok(pubkey) = PublicKey::from_hex(&pubkey) else {
+                            warn!("Dropping a mention for invalid pubkey {}", pubkey);
+                            return;
+                        };
+                        if subscribers_clone.ban_reason(&pubkey).await.is_some() {
+                            return;
+                        }
+                        let bot_clone = current_bot_clone.read().await.clone();
+                        let chat = bot_clone.get_chat(pubkey).await;
+                        let mention = format!("You were mentioned by {}: {}", author, content);
+                        if inbox_relays.send_private_message(&bot_clone.client, &chat, pubkey, &mention).await {
+                            info!("Sent Discord mention to Nostr user: {}", pubkey);
+                        } else {
+                            stats.failed_sends_total.inc();
+                            error!("Error sending mention to Nostr user {}", pubkey);
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                } else if let BridgeMessage::System { message, delivered, span, .. } = message {
+                    async {
+                        // Bridge notices go to every subscriber, regardless of their filters
+                        let bot_clone = current_bot_clone.read().await.clone();
+                        for (i, pubkey) in subscribers_clone.get_all().await.into_iter().enumerate() {
+                            if i > 0 {
+                                tokio::time::sleep(NOTICE_SEND_INTERVAL).await;
+                            }
+                            let chat = bot_clone.get_chat(pubkey).await;
+                            if !inbox_relays.send_private_message(&bot_clone.client, &chat, pubkey, &message).await {
+                                stats.failed_sends_total.inc();
+                                error!("Error sending bridge notice to Nostr user {}", pubkey);
+                            }
+                        }
+                        if let Some(delivered) = delivered {
+                            delivered.notify_one();
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                }
+            }
+        });
+
+        // Start each day's counts for !stats afresh at local midnight
+        let daily_stats = self.stats.clone();
+        let timezone = self.timezone;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(time::until_next_midnight(chrono::Utc::now(), timezone)).await;
+                daily_stats.reset_daily();
+            }
+        });
+
+        // Look up where new subscribers want their DMs, forgetting it when they leave
+        let inbox_relays = self.inbox_relays.clone();
+        let inbox_bot = current_bot.clone();
+        let mut subscriber_events = self.subscribers.events();
+        tokio::spawn(async move {
+            loop {
+                match subscriber_events.recv().await {
+                    Ok(SubscriberEvent::Joined(pubkey)) => {
+                        let client = inbox_bot.read().await.client.clone();
+                        inbox_relays.refresh(&client, pubkey).await;
+                    }
+                    Ok(SubscriberEvent::Left(pubkey)) => inbox_relays.remove(&pubkey).await,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        // Refresh every subscriber's inbox relays weekly, starting now for those loaded from file
+        let inbox_relays = self.inbox_relays.clone();
+        let inbox_bot = current_bot.clone();
+        let inbox_subscribers = self.subscribers.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(INBOX_RELAYS_REFRESH);
+            loop {
+                interval.tick().await;
+                let client = inbox_bot.read().await.client.clone();
+                for pubkey in inbox_subscribers.get_all().await {
+                    inbox_relays.refresh(&client, pubkey).await;
+                }
+            }
+        });
+
+        // Keep cached profiles fresh without waiting for their owners to message the bridge
+        if !self.metadata_refresh_interval.is_zero() {
+            let refresh_cache = self.metadata_cache.clone();
+            let refresh_bot = current_bot.clone();
+            let refresh_interval = self.metadata_refresh_interval;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(refresh_interval);
+                // The first tick is immediate, and the cache was only just loaded
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let client = refresh_bot.read().await.client.clone();
+                    let refreshed = refresh_cache.refresh_stale(&client).await;
+                    info!("Refreshed {} stale cached profiles", refreshed);
+                }
+            });
+        }
+
+        // Periodically record each relay's connection state and latency for !relay-status
+        let health_bot = current_bot.clone();
+        let health_monitor = self.relay_monitor.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RELAY_HEALTH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let client = health_bot.read().await.client.clone();
+                for (url, relay) in client.relays().await {
+                    let connected = relay.status() == RelayStatus::Connected;
+                    health_monitor.record_health(url.as_str(), connected, relay.stats().latency());
+                }
+            }
+        });
+
+        // Get our pubkey for filtering own messages
+        let mut my_pubkey = self.remote_signer.as_ref()
+            .map(|(_, pubkey)| *pubkey)
+            .unwrap_or_else(|| self.keys.public_key());
+
+        // Store the bot
+        self.bot = Some(current_bot.clone());
+
+        // Let senders of the other bridges pick this one by name
+        if let Some(name) = &self.bridge_name {
+            let npub = pubkey_to_bech32_or_fail(&my_pubkey)?;
+            self.channels.register(name.clone(), BridgeChannel { npub, subscribers: self.subscribers.clone() });
+        }
+
+        // Clone for the notification handler
+        let subscribers_clone = self.subscribers.clone();
+        let channels = self.channels.clone();
+        let bridge_name = self.bridge_name.clone();
+        let usernames = self.usernames.clone();
+        let subscriber_filters_clone = self.subscriber_filters.clone();
+        let preferences_clone = self.preferences.clone();
+        let metadata_cache_clone = self.metadata_cache.clone();
+        let allowed_kinds = self.allowed_kinds.clone();
+        let strict_giftwrap = self.strict_giftwrap;
+        let support_nip04 = self.support_nip04;
+        let bridge_reactions = self.bridge_reactions;
+        let mention_on_nostr_reply = self.mention_on_nostr_reply;
+        let message_ids = self.message_ids.clone();
+        let min_pow_difficulty = self.min_pow_difficulty;
+        let sync_subscriber_list = self.sync_subscriber_list;
+        let mut spam_detector = SpamDetector::new(
+            self.spam_duplicate_threshold,
+            self.spam_window_secs,
+            self.spam_mute_secs,
+        );
+        let spam_mute_secs = self.spam_mute_secs;
+        let mut flood_detector = FloodDetector::new(
+            self.flood_threshold,
+            self.flood_window_secs,
+            self.flood_cooldown_secs,
+        );
+        let flood_cooldown_secs = self.flood_cooldown_secs;
+        let content_filter = self.content_filter.clone();
+        let notify_filtered_sender = self.notify_filtered_sender;
+        let content_must_contain = self.content_must_contain.clone();
+        let admin_pubkeys = self.admin_pubkeys.clone();
+        let enabled_commands = self.enabled_commands.clone();
+        let silent_disabled_commands = self.silent_disabled_commands;
+        let allow_key_rotation = self.allow_key_rotation;
+        let remote_signer = self.remote_signer.is_some();
+        let profile = self.profile.clone();
+        let relays = self.relays.clone();
+        let publish_profile = self.publish_profile;
+        let publish_relay_list = self.publish_relay_list;
+        let bridge_pause = self.bridge_pause.clone();
+        let pending_relays = self.pending_relays.clone();
+        let subscribe_message = self.subscribe_message.clone();
+        let unsubscribe_message = self.unsubscribe_message.clone();
+        let relay_monitor = self.relay_monitor.clone();
+        let stats = self.stats.clone();
+        let nostr_sender_clone = nostr_sender.clone();
+        let held_discord_sender = self.discord_to_nostr.clone().unwrap_or_else(|| nostr_sender.clone());
+        let notification_reconnects = self.notification_reconnects.clone();
+        let started_at = Timestamp::now().as_u64();
+        let mut bot_clone = bot.clone();
+
+        // Spawn a task to handle incoming Nostr private messages
+        tokio::spawn(async move {
+            let mut notifications = bot.client.notifications();
+
+            loop {
+                let notification = match notifications.recv().await {
+                    Ok(notification) => notification,
+                    // Falling behind only drops the oldest notifications, the stream itself is still open
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Nostr notification stream lagged, skipped {} notifications", skipped);
+                        continue;
+                    }
+                    // A closed stream can't be received from again, so reconnect and take a fresh one
+                    Err(broadcast::error::RecvError::Closed) => {
+                        let attempt = notification_reconnects.fetch_add(1, Ordering::Relaxed) + 1;
+                        warn!("Nostr notification stream closed, reconnecting (attempt {})", attempt);
+                        tokio::time::sleep(NOTIFICATION_RECONNECT_DELAY).await;
+
+                        bot.client.connect().await;
+                        subscribe_to_dms(&bot.client, my_pubkey, support_nip04).await;
+                        notifications = bot.client.notifications();
+
+                        let notice = BridgeMessage::System {
+                            event_type: SystemEventType::RelayReconnected,
+                            message: "The bridge reconnected to its Nostr relays, messages sent in the last few moments may not have arrived.".to_string(),
+                            delivered: None,
+                            span: info_span!("bridge_message", direction = "system", source_id = "relay_reconnect"),
+                        };
+                        if let Err(e) = nostr_sender_clone.send(notice).await {
+                            error!("Error queueing reconnection notice: {}", e);
+                        }
+                        continue;
+                    }
+                };
+
+                match notification {
+                    // Relays answer each event we publish with an OK saying whether they stored it
+                    RelayPoolNotification::Message { relay_url, message: RelayMessage::Ok { event_id, status, message } } => {
+                        relay_monitor.record(relay_url.as_str(), status);
+                        if !status {
+                            warn!("Relay {} rejected event {}: {}", relay_url, event_id, message);
+                        }
+                    }
+                    RelayPoolNotification::Event { event, relay_url, subscription_id: _ } => {
+                        // Skip our own events to prevent loops
+                        if event.pubkey == my_pubkey {
+                            continue;
+                        }
+
+                        // Decrypt the message, either a NIP-17 giftwrap or a legacy NIP-04 DM when enabled
+                        let is_nip04 = event.kind == Kind::EncryptedDirectMessage;
+                        if event.kind != Kind::GiftWrap && !(is_nip04 && support_nip04) {
+                            // Only these kinds carry processable messages, so skip anything else before decrypting
+                            continue;
+                        }
+
+                        // Reject events with a tampered outer signature
+                        if event.verify().is_err() {
+                            error!("Dropping event {} with an invalid signature", event.id);
+                            metrics::NOSTR_EVENT_VERIFICATION_FAILURES.inc();
+                            continue;
+                        }
+
+                        // Enforce NIP-13 PoW on the outer event ID before paying for decryption. A giftwrap
+                        // is signed by a throwaway key, so only NIP-04 senders can be exempted or told why
+                        if let Some(difficulty) = min_pow_difficulty {
+                            let bits = get_leading_zero_bits(event.id.as_bytes());
+                            if bits < difficulty && !(is_nip04 && admin_pubkeys.contains(&event.pubkey)) {
+                                debug!("Rejected event {} with {} bits of PoW", event.id, bits);
+                                metrics::NOSTR_POW_REJECTIONS.inc();
+                                // Notices share the flood budget so cheap events can't make the bridge reply in bulk
+                                if is_nip04 && flood_detector.check(&event.pubkey, Timestamp::now().as_u64()) == SpamCheck::Allowed {
+                                    let chat = bot_clone.get_chat(event.pubkey).await;
+                                    let _ = chat.send_private_message(&format!(
+                                        "Messages to this bridge require at least {} bits of PoW. Your message had {} bits.",
+                                        difficulty, bits
+                                    )).await;
+                                }
+                                continue;
+                            }
+                        }
+
+                        let (sender, content, created_at, sent_at_ms, message_id, reply_to) = if is_nip04 {
+                            let decrypted = match bot.client.signer().await {
+                                Ok(signer) => signer.nip04_decrypt(&event.pubkey, &event.content).await.ok(),
+                                Err(_) => None,
+                            };
+                            let content = match decrypted {
+                                Some(content) => content,
+                                None => {
+                                    error!("Failed to decrypt NIP-04 message from: {}", event.pubkey);
+                                    continue;
+                                }
+                            };
+                            warn!("Received a deprecated NIP-04 DM from {}, they should upgrade to a NIP-17 client", event.pubkey);
+                            (event.pubkey, content, event.created_at, inbox::sent_at_millis(event.created_at, &event.tags), event.id, event.tags.event_ids().last().copied())
+                        } else {
+                            // Try to decrypt the message via SDK-configured client (this also verifies the seal)
+                            let UnwrappedGift { mut rumor, sender } = match bot.client.unwrap_gift_wrap(&event).await {
+                                Ok(gift) => gift,
+                                Err(_) => {
+                                    error!("Failed to decrypt direct message from: {}", event.pubkey);
+                                    continue;
+                                }
+                            };
+
+                            // Rumors are unsigned by design, but their ID must still match the content
+                            if rumor.verify_id().is_err() {
+                                error!("Dropping rumor from {} with a mismatched event ID", sender);
+                                metrics::NOSTR_EVENT_VERIFICATION_FAILURES.inc();
+                                continue;
+                            }
+
+                            // The seal signer must be the author the rumor claims, otherwise the sender may be spoofed
+                            if sender != rumor.pubkey {
+                                warn!("Giftwrap sender mismatch: seal sender {} vs rumor pubkey {}", sender, rumor.pubkey);
+                                metrics::NOSTR_GIFTWRAP_MISMATCHES.inc();
+                                if strict_giftwrap {
+                                    continue;
+                                }
+                            }
+
+                            // Reactions are shown on the Discord message they react to, never forwarded as text
+                            if bridge_reactions && rumor.kind == Kind::Reaction {
+                                // NIP-25 puts the reacted-to event last among the `e` tags
+                                let target = rumor.tags.event_ids().last().and_then(|id| message_ids.get_discord(id));
+                                if let (Some(discord_message_id), Some(emoji)) = (target, reaction_emoji(&rumor.content)) {
+                                    if subscribers_clone.contains(&sender).await {
+                                        let message = BridgeMessage::NostrReaction {
+                                            discord_message_id,
+                                            emoji,
+                                            span: info_span!("bridge_message", direction = "nostr_to_discord", source_id = %event.id),
+                                        };
+                                        if let Err(e) = discord_sender.send(message).await {
+                                            error!("Error forwarding reaction to Discord: {}", e);
+                                        }
+                                    }
+                                }
+                                continue;
+                            }
+
+                            // Only process whitelisted event kinds (NIP-17 DMs by default)
+                            if !allowed_kinds.contains(&rumor.kind.as_u16()) {
+                                continue;
+                            };
+
+                            // Giftwrap timestamps are randomized, the rumor holds the real send time
+                            (sender, rumor.content.clone(), rumor.created_at, inbox::sent_at_millis(rumor.created_at, &rumor.tags), rumor.id(), rumor.tags.event_ids().last().copied())
+                        };
+
+                        // Drop senders bursting messages faster than the flood threshold allows
+                        match flood_detector.check(&sender, Timestamp::now().as_u64()) {
+                            SpamCheck::Allowed => {}
+                            SpamCheck::Triggered => {
+                                warn!("Cooling down {} for {}s after a message flood", sender, flood_cooldown_secs);
+                                metrics::NOSTR_FLOOD_DETECTIONS.inc();
+                                let chat = bot_clone.get_chat(sender).await;
+                                let _ = chat.send_private_message(&format!(
+                                    "You are sending messages too quickly, further messages will be ignored for {} seconds.",
+                                    flood_cooldown_secs
+                                )).await;
+                                continue;
+                            }
+                            SpamCheck::Muted => continue,
+                        }
+
+                        // Drop senders flooding the bridge with duplicate messages
+                        match spam_detector.check(&sender, &content, Timestamp::now().as_u64()) {
+                            SpamCheck::Allowed => {}
+                            SpamCheck::Triggered => {
+                                warn!("Muting {} for {}s after repeated duplicate messages", sender, spam_mute_secs);
+                                metrics::NOSTR_SPAM_DETECTIONS.inc();
+                                let chat = bot_clone.get_chat(sender).await;
+                                let _ = chat.send_private_message(&format!(
+                                    "You have been muted for {} seconds for sending the same message repeatedly.",
+                                    spam_mute_secs
+                                )).await;
+                                continue;
+                            }
+                            SpamCheck::Muted => continue,
+                        }
+
+                        // Create some simplified utility variables
+                        let sender_pubkey = sender;
+                        let is_admin = admin_pubkeys.contains(&sender_pubkey);
+                        let message_content = content.trim();
+                        let (command, args) = message_content
+                            .split_once(char::is_whitespace)
+                            .map(|(command, args)| (command, args.trim()))
+                            .unwrap_or((message_content, ""));
+
+                        // Commands the operator disabled never reach their handler
+                        if let Some(name) = command.strip_prefix('!') {
+                            if is_command(name) && !commands::is_enabled(name, &enabled_commands) {
+                                if !silent_disabled_commands {
+                                    let chat = bot_clone.get_chat(sender_pubkey).await;
+                                    let _ = chat.send_private_message("This command is not available on this bridge.").await;
+                                }
+                                continue;
+                            }
+                        }
+
+                        // Handle subscription commands
+                        if command == "!subscribe" {
+                            if let Some(reason) = subscribers_clone.ban_reason(&sender_pubkey).await {
                                 let chat = bot_clone.get_chat(sender_pubkey).await;
-                                let _ = chat.send_private_message(
-                                    "Your message was not forwarded to Discord because you're not subscribed. Send !subscribe to start forwarding your messages."
-                                ).await;
-                                info!("Ignored message from non-subscribed user: {}", sender_pubkey);
+                                let _ = chat.send_private_message(&format!("You have been banned from this bridge: {}", reason)).await;
+                                continue;
+                            }
+                            let template = if subscribers_clone.add(sender_pubkey).await {
+                                info!("New subscriber: {}", sender_pubkey);
+                                stats.subscribe_events_total.inc();
+                                if sync_subscriber_list {
+                                    subscribers_clone.publish_list(&bot.client, &list_identifier).await;
+                                }
+                                // Send confirmation
+                                subscribe_message.as_str()
+                            } else {
+                                // Already subscribed
+                                "You are already subscribed to the Discord channel."
+                            };
+                            let reply = match render_subscription_reply(template, &sender_pubkey, &subscribers_clone, &bot.client).await {
+                                Ok(reply) => reply,
+                                Err(e) => {
+                                    error!("Failed to render subscription reply: {}", e);
+                                    continue;
+                                }
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!unsubscribe" {
+                            let template = if subscribers_clone.remove(&sender_pubkey).await {
+                                info!("Unsubscribed: {}", sender_pubkey);
+                                stats.unsubscribe_events_total.inc();
+                                if sync_subscriber_list {
+                                    subscribers_clone.publish_list(&bot.client, &list_identifier).await;
+                                }
+                                // Send confirmation
+                                unsubscribe_message.as_str()
+                            } else {
+                                // Not subscribed
+                                "You are not currently subscribed to the Discord channel."
+                            };
+                            let reply = match render_subscription_reply(template, &sender_pubkey, &subscribers_clone, &bot.client).await {
+                                Ok(reply) => reply,
+                                Err(e) => {
+                                    error!("Failed to render subscription reply: {}", e);
+                                    continue;
+                                }
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!list-channels" {
+                            let names = channels.names();
+                            let reply = if names.is_empty() {
+                                "This bridge has no other channels.".to_string()
+                            } else {
+                                format!("Channels: {}\nSend !subscribe-channel <name> to receive one of them.", names.join(", "))
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!subscribe-channel" || command == "!unsubscribe-channel" {
+                            // Each channel is its own bridge, so this joins or leaves that bridge's subscribers
+                            let reply = match channels.get(args) {
+                                None if args.is_empty() => format!("Usage: {} <name>", command),
+                                None => format!("Unknown channel '{}'. Send !list-channels to see them.", args),
+                                Some(channel) if command == "!subscribe-channel" => {
+                                    if let Some(reason) = channel.subscribers.ban_reason(&sender_pubkey).await {
+                                        format!("You have been banned from channel '{}': {}", args, reason)
+                                    } else if channel.subscribers.add(sender_pubkey).await {
+                                        info!("New subscriber to channel {}: {}", args, sender_pubkey);
+                                        format!("Subscribed to channel '{}'. Its messages will come from {}.", args, channel.npub)
+                                    } else {
+                                        format!("You are already subscribed to channel '{}'.", args)
+                                    }
+                                }
+                                Some(channel) => {
+                                    if channel.subscribers.remove(&sender_pubkey).await {
+                                        info!("Unsubscribed from channel {}: {}", args, sender_pubkey);
+                                        format!("Unsubscribed from channel '{}'.", args)
+                                    } else {
+                                        format!("You are not subscribed to channel '{}'.", args)
+                                    }
+                                }
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!register-discord" {
+                            // One username per sender, so registering again moves the link
+                            let username = args.trim_start_matches('@');
+                            let reply = if username.is_empty() {
+                                "Usage: !register-discord <username>".to_string()
+                            } else {
+                                usernames.register(username, sender_pubkey).await;
+                                info!("Linked Discord username {} to {}", username, sender_pubkey);
+                                format!("Linked Discord username '{}' to you. Discord messages mentioning it will be sent to you.", username)
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!ping" {
+                            // The reply can't carry its own round trip, so an ephemeral probe is timed to its OK first
+                            let relay_rtt_ms = relay_round_trip(&bot_clone.client).await;
+                            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                            let end_to_end_ms = now_ms.saturating_sub(sent_at_ms);
+                            let reply = commands::ping_text(relay_rtt_ms, end_to_end_ms, subscribers_clone.count().await);
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!version" {
+                            // Build metadata for diagnosing differences between instances
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let relays = bot_clone.client.relays().await;
+                            let subscriptions = bot_clone.client.subscriptions().await;
+                            let pool = commands::PoolStatus {
+                                connected_relays: relays.values().filter(|relay| relay.status() == RelayStatus::Connected).count(),
+                                relays: relays.len(),
+                                subscriptions: subscriptions.len(),
+                                giftwrap_subscriptions: subscriptions.iter()
+                                    .filter(|(_, filter)| filter.kinds.as_ref().is_some_and(|kinds| kinds.contains(&Kind::GiftWrap)))
+                                    .map(|(id, _)| id.to_string())
+                                    .collect(),
+                            };
+                            let _ = chat.send_private_message(&commands::version_text(started_at, Timestamp::now().as_u64(), &pool)).await;
+                            continue;
+                        } else if command == "!help" {
+                            // Send help information, optionally for a single command
+                            let topic = (!args.is_empty()).then_some(args);
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let mut help = commands::help_text(topic, is_admin, &enabled_commands);
+                            if is_nip04 {
+                                help.push_str("\n\nYou are using legacy NIP-04 DMs, which are deprecated. Please upgrade to a client that supports NIP-17.");
+                            }
+                            let _ = chat.send_private_message(&help).await;
+                            continue;
+                        } else if command == "!filter" {
+                            // Manage the sender's personal keyword filters
+                            let (action, keyword) = args
+                                .split_once(char::is_whitespace)
+                                .map(|(action, keyword)| (action, keyword.trim()))
+                                .unwrap_or((args, ""));
+                            let reply = match (action, keyword) {
+                                ("add", keyword) if !keyword.is_empty() => {
+                                    match subscriber_filters_clone.add(sender_pubkey, keyword).await {
+                                        Ok(true) => format!("Added filter '{}'. Discord messages containing it will not be sent to you.", keyword),
+                                        Ok(false) => format!("You already filter '{}'.", keyword),
+                                        Err(e) => e.to_string(),
+                                    }
+                                }
+                                ("remove", keyword) if !keyword.is_empty() => {
+                                    if subscriber_filters_clone.remove(&sender_pubkey, keyword).await {
+                                        format!("Removed filter '{}'.", keyword)
+                                    } else {
+                                        format!("You don't have a filter for '{}'.", keyword)
+                                    }
+                                }
+                                ("list", _) => {
+                                    let keywords = subscriber_filters_clone.list(&sender_pubkey).await;
+                                    if keywords.is_empty() {
+                                        "You have no filters.".to_string()
+                                    } else {
+                                        format!("Your filters: {}", keywords.join(", "))
+                                    }
+                                }
+                                _ => "Usage: !filter add <keyword>, !filter remove <keyword> or !filter list".to_string(),
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!pref" {
+                            // Update the sender's message format preferences
+                            let (setting, value) = args
+                                .split_once(char::is_whitespace)
+                                .map(|(setting, value)| (setting, value.trim()))
+                                .unwrap_or((args, ""));
+                            let reply = match (setting, value.to_lowercase().as_str()) {
+                                ("format", "plain" | "prefixed") => {
+                                    let format = if value.eq_ignore_ascii_case("plain") { MessageFormat::Plain } else { MessageFormat::Prefixed };
+                                    preferences_clone.update(sender_pubkey, |prefs| prefs.message_format = format).await;
+                                    format!("Discord messages will now be sent to you in {} format.", value.to_lowercase())
+                                }
+                                ("username", "on" | "off") => {
+                                    let include = value.eq_ignore_ascii_case("on");
+                                    preferences_clone.update(sender_pubkey, |prefs| prefs.include_discord_username = include).await;
+                                    format!("Discord usernames turned {}.", value.to_lowercase())
+                                }
+                                _ => "Usage: !pref format plain|prefixed or !pref username on|off".to_string(),
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!pause" || command == "!resume" {
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+
+                            let (event_type, notice) = if command == "!pause" {
+                                if bridge_pause.is_paused() {
+                                    let _ = chat.send_private_message("The bridge is already paused.").await;
+                                    continue;
+                                }
+                                (SystemEventType::BridgePaused, "Bridge is temporarily paused for maintenance.")
+                            } else {
+                                let Some(held) = bridge_pause.resume() else {
+                                    let _ = chat.send_private_message("The bridge is not paused.").await;
+                                    continue;
+                                };
+                                info!("Bridge resumed by {}, flushing {} held messages", sender_pubkey, held.len());
+
+                                // Deliver everything held while paused in its original direction, back through the
+                                // forwarding tasks so Discord messages also reach federation peers
+                                for message in held {
+                                    let result = match message {
+                                        BridgeMessage::Discord { .. }
+                                        | BridgeMessage::DiscordReaction { .. }
+                                        | BridgeMessage::Mention { .. }
+                                        | BridgeMessage::System { .. } => held_discord_sender.send(message).await,
+                                        BridgeMessage::Nostr { .. }
+                                        | BridgeMessage::NostrReaction { .. }
+                                        | BridgeMessage::DeliveryReceipt { .. }
+                                        | BridgeMessage::Redaction { .. } => discord_sender.send(message).await,
+                                    };
+                                    if let Err(e) = result {
+                                        error!("Error flushing held message: {}", e);
+                                    }
+                                }
+                                (SystemEventType::BridgeResumed, "Bridge has resumed.")
+                            };
+
+                            // Subscribers hear it from the send task, which is past the pause check so the notice is never held
+                            let system = BridgeMessage::System {
+                                event_type,
+                                message: notice.to_string(),
+                                delivered: None,
+                                span: info_span!("bridge_message", direction = "system", source_id = "pause"),
+                            };
+                            if let Err(e) = nostr_sender_clone.send(system).await {
+                                error!("Error queueing pause notice: {}", e);
+                            }
+                            if command == "!pause" {
+                                bridge_pause.pause();
+                                info!("Bridge paused by {}", sender_pubkey);
+                            }
+                            if !subscribers_clone.contains(&sender_pubkey).await {
+                                let _ = chat.send_private_message(notice).await;
+                            }
+                            continue;
+                        } else if command == "!rotate-key" {
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+                            if !allow_key_rotation {
+                                let _ = chat.send_private_message("Key rotation is disabled. Set ALLOW_KEY_ROTATION=true to enable it.").await;
+                                continue;
+                            }
+                            if remote_signer {
+                                let _ = chat.send_private_message("Key rotation is unavailable while the bridge signs through a NIP-46 bunker.").await;
+                                continue;
+                            }
+                            let new_keys = match config::parse_secret_key(args) {
+                                Ok(secret_key) => Keys::new(secret_key),
+                                Err(_) => {
+                                    let _ = chat.send_private_message("Invalid key. Usage: !rotate-key <nsec>").await;
+                                    continue;
+                                }
+                            };
+                            if new_keys.public_key() == my_pubkey {
+                                let _ = chat.send_private_message("The bridge is already using that key.").await;
+                                continue;
+                            }
+
+                            // Encoded before anything changes, the rotation can't be announced without it
+                            let new_npub = match pubkey_to_bech32_or_fail(&new_keys.public_key()) {
+                                Ok(npub) => npub,
+                                Err(e) => {
+                                    error!("Cannot rotate the bridge key: {}", e);
+                                    continue;
+                                }
+                            };
+
+                            // Retract the old profile, then bring up the new identity before announcing it
+                            delete_profile(&bot.client, my_pubkey).await;
+                            let new_bot = connect_bot(new_keys.clone(), None, &profile, &relays, publish_profile, publish_relay_list, support_nip04).await;
+
+                            // Announce from the old key so subscribers can trust the notice
+                            let notice = format!("Bridge pubkey rotating to {}. Please update your contact list.", new_npub);
+                            let delivered = Arc::new(Notify::new());
+                            let system = BridgeMessage::System {
+                                event_type: SystemEventType::KeyRotated,
+                                message: notice.clone(),
+                                delivered: Some(delivered.clone()),
+                                span: info_span!("bridge_message", direction = "system", source_id = "rotate_key"),
+                            };
+                            if let Err(e) = nostr_sender_clone.send(system).await {
+                                error!("Error queueing key rotation notice: {}", e);
+                                delivered.notify_one();
+                            }
+                            if !subscribers_clone.contains(&sender_pubkey).await {
+                                let _ = chat.send_private_message(&notice).await;
+                            }
+                            info!("Rotated bridge key from {} to {}", my_pubkey, new_keys.public_key());
+
+                            // The new key only lives in memory, a restart would bring back the old one and its deleted profile
+                            warn!("The rotated key is not persisted, update NOSTR_PRIVATE_KEY or NOSTR_PRIVATE_KEY_ENCRYPTED to {} before restarting", new_npub);
+                            let _ = chat.send_private_message("Key rotated. Update NOSTR_PRIVATE_KEY (or NOSTR_PRIVATE_KEY_ENCRYPTED) to the new key before restarting the bridge, otherwise it comes back on the old one.").await;
+
+                            // Senders of the other bridges are pointed at the new identity
+                            if let Some(name) = &bridge_name {
+                                channels.register(name.clone(), BridgeChannel { npub: new_npub.clone(), subscribers: subscribers_clone.clone() });
+                            }
+
+                            // The send task keeps the old key until its notice is out, then retires it
+                            let retiring_bot = current_bot.clone();
+                            let next_bot = new_bot.clone();
+                            tokio::spawn(async move {
+                                delivered.notified().await;
+                                let old_bot = std::mem::replace(&mut *retiring_bot.write().await, next_bot);
+                                old_bot.client.disconnect().await;
+                            });
+
+                            // Commands are answered by the new identity straight away
+                            my_pubkey = new_keys.public_key();
+                            notifications = new_bot.client.notifications();
+                            bot_clone = new_bot.clone();
+                            bot = new_bot;
+                            continue;
+                        } else if command == "!mystats" {
+                            // Report the sender's own bridge activity
+                            let reply = match subscribers_clone.stats(&sender_pubkey).await {
+                                Some(stats) => commands::stats_text(&stats, Timestamp::now().as_u64()),
+                                None => "You are not subscribed. Send !subscribe first.".to_string(),
+                            };
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!relay-status" {
+                            // Report each relay's health and how often it accepted the bridge's events
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+                            let reply = commands::relay_status_text(&relay_monitor.snapshot());
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        } else if command == "!addrelay" || command == "!confirm" {
+                            // Adding a relay redirects bridge traffic, so it only happens once the admin confirms it
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+                            let now = Instant::now();
+                            let mut pending = pending_relays.lock().await;
+                            pending.retain(|_, (_, expires)| *expires > now);
+                            if command == "!addrelay" {
+                                if !args.starts_with("ws://") && !args.starts_with("wss://") {
+                                    let _ = chat.send_private_message("Usage: !addrelay <wss://relay.example.com>").await;
+                                    continue;
+                                }
+                                let token = crypto::confirmation_token(RELAY_CONFIRMATION_TOKEN_LEN);
+                                pending.insert(token.clone(), (args.to_string(), now + RELAY_CONFIRMATION_TTL));
+                                let _ = chat.send_private_message(&format!(
+                                    "Confirm adding {} by sending: !confirm {} (expires in {}s).",
+                                    args, token, RELAY_CONFIRMATION_TTL.as_secs()
+                                )).await;
+                                continue;
+                            }
+
+                            let Some((url, _)) = pending.remove(&args.to_ascii_uppercase()) else {
+                                let _ = chat.send_private_message("Confirmation failed or expired. Please repeat !addrelay.").await;
+                                continue;
+                            };
+                            drop(pending);
+                            if let Err(e) = bot_clone.client.add_relay(url.as_str()).await {
+                                let _ = chat.send_private_message(&format!("Failed to add relay {}: {}", url, e)).await;
+                                continue;
+                            }
+                            if let Err(e) = bot_clone.client.connect_relay(url.as_str()).await {
+                                warn!("Failed to connect to relay {}: {}", url, e);
+                            }
+                            info!("Admin {} added relay {}", sender_pubkey, url);
+                            let _ = chat.send_private_message(&format!("Added relay {} until the next restart.", url)).await;
+
+                            // Subscribers whose clients cached the bridge's relays would otherwise miss the new one
+                            let relays: Vec<String> = bot_clone.client.relays().await.into_keys().map(|url| url.to_string()).collect();
+                            let notice = BridgeMessage::System {
+                                event_type: SystemEventType::RelayListChanged,
+                                message: relay_list_notice(&relays),
+                                delivered: None,
+                                span: info_span!("bridge_message", direction = "system", source_id = %url),
+                            };
+                            if let Err(e) = nostr_sender_clone.send(notice).await {
+                                error!("Failed to announce the relay list change: {}", e);
+                            }
+                            continue;
+                        } else if command == "!redact" {
+                            // Withdraw a bridged message from Nostr and Discord, without repeating its content
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+                            let event_id = Nip19Event::from_bech32(args)
+                                .map(|nevent| nevent.event_id)
+                                .or_else(|_| EventId::parse(args));
+                            let Ok(event_id) = event_id else {
+                                let _ = chat.send_private_message("Usage: !redact <nevent1... or hex event ID>").await;
+                                continue;
+                            };
+
+                            let request = EventDeletionRequest::new().id(event_id).reason("Removed by a bridge admin");
+                            let relay_count = match bot_clone.client.send_event_builder(EventBuilder::delete(request)).await {
+                                Ok(output) => output.success.len(),
+                                Err(e) => {
+                                    let _ = chat.send_private_message(&format!("Failed to publish the redaction: {}", e)).await;
+                                    continue;
+                                }
+                            };
+                            info!("Admin {} redacted event {}", sender_pubkey, event_id);
+
+                            if let Some(discord_message_id) = message_ids.get_discord(&event_id) {
+                                let redaction = BridgeMessage::Redaction {
+                                    discord_message_id,
+                                    span: info_span!("bridge_message", direction = "nostr_to_discord", source_id = %event_id),
+                                };
+                                if let Err(e) = discord_sender.send(redaction).await {
+                                    error!("Failed to delete the redacted Discord message: {}", e);
+                                }
+                            }
+                            let notice = BridgeMessage::System {
+                                event_type: SystemEventType::MessageRedacted,
+                                message: "[Moderation] A message has been removed.".to_string(),
+                                delivered: None,
+                                span: info_span!("bridge_message", direction = "system", source_id = %event_id),
+                            };
+                            if let Err(e) = nostr_sender_clone.send(notice).await {
+                                error!("Failed to announce the redaction: {}", e);
+                            }
+
+                            let id_short: String = event_id.to_hex().chars().take(8).collect();
+                            let _ = chat.send_private_message(&format!("Event {} redaction published to {} relays.", id_short, relay_count)).await;
+                            continue;
+                        } else if command == "!stats" {
+                            // Report the bridge's usage since startup and since midnight
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            if !is_admin {
+                                let _ = chat.send_private_message("This command is restricted to bridge admins.").await;
+                                continue;
+                            }
+                            let reply = commands::bridge_stats_text(&stats, subscribers_clone.count().await, metadata_cache_clone.len());
+                            let _ = chat.send_private_message(&reply).await;
+                            continue;
+                        }
+
+                        // Only relay messages from subscribed users
+                        if subscribers_clone.contains(&sender_pubkey).await {
+                            // Strip invisible characters first so they can't hide content from the filter
+                            let message_content = normalize_content(message_content);
+
+                            // Drop messages matching the operator's content filter
+                            if content_filter.is_blocked(&message_content) {
+                                if notify_filtered_sender {
+                                    let chat = bot_clone.get_chat(sender_pubkey).await;
+                                    let _ = chat.send_private_message(FILTERED_NOTICE).await;
+                                }
+                                continue;
+                            }
+
+                            // Off-topic messages are dropped quietly, this isn't something the sender asked for
+                            if let Some(keyword) = &content_must_contain {
+                                if !message_content.to_lowercase().contains(keyword) {
+                                    debug!("Filtered Nostr message from {}: content did not match required keyword.", sender_pubkey);
+                                    continue;
+                                }
+                            }
+
+                            // Try to fetch user metadata (via SDK client), which only hits relays for missing or stale profiles
+                            if metadata_cache_clone.get(&sender_pubkey).is_none_or(|m| m.needs_refresh()) {
+                                stats.metadata_fetches_total.inc();
+                            }
+                            let metadata = match metadata_cache_clone.fetch_metadata(&bot.client, &sender_pubkey).await {
+                                Ok(metadata) => metadata,
+                                Err(e) => {
+                                    error!("Failed to fetch metadata for {}: {}", sender_pubkey, e);
+                                    // Create a default metadata entry if fetch fails
+                                    match UserMetadata::new(&sender_pubkey) {
+                                        Ok(metadata) => metadata,
+                                        Err(e) => {
+                                            error!("Dropping message: {}", e);
+                                            continue;
+                                        }
+                                    }
+                                }
+                            };
+
+                            // Get the best username for display
+                            let username = metadata.get_best_name();
+
+                            // Create metadata for the message
+                            let pubkey_str = match pubkey_to_bech32_or_fail(&sender_pubkey) {
+                                Ok(npub) => npub,
+                                Err(e) => {
+                                    error!("Dropping message: {}", e);
+                                    continue;
+                                }
+                            };
+                            let message_metadata = NostrMessageMetadata {
+                                username: username.clone(),
+                                pubkey: pubkey_str,
+                                avatar_url: metadata.picture,
+                                banner_url: metadata.banner_url,
+                                event_created_at: Some(created_at.as_u64()),
+                                event_id: Some(message_id.to_hex()),
+                                lud16: metadata.lud16,
+                                website: metadata.website,
+                                reply_to_discord_user_id: reply_to
+                                    .filter(|_| mention_on_nostr_reply)
+                                    .and_then(|id| message_ids.get_discord_author(&id)),
+                                relay_url: Some(relay_url.to_string()),
+                            };
+
+                            // Create the bridge message
+                            metrics::NOSTR_TO_DISCORD_CONTENT_LENGTH.observe(&message_content);
+                            let span = info_span!("bridge_message", direction = "nostr_to_discord", source_id = %event.id);
+                            let bridge_message = BridgeMessage::Nostr {
+                                content: message_content,
+                                metadata: message_metadata,
+                                span: span.clone(),
+                            };
+
+                            // Send the decrypted message to Discord
+                            async {
+                                if let Err(e) = discord_sender.send(bridge_message).await {
+                                    error!("Error forwarding message to Discord: {}", e);
+                                } else {
+                                    subscribers_clone.record_message(&sender_pubkey).await;
+                                    info!("Forwarded Nostr DM to Discord from: {}", username);
+                                }
                             }
+                            .instrument(span)
+                            .await;
                         } else {
-                            error!("Failed to decrypt direct message from: {}", event.pubkey);
+                            // Inform the user they need to subscribe first
+                            let chat = bot_clone.get_chat(sender_pubkey).await;
+                            let _ = chat.send_private_message(
+                                "Your message was not forwarded to Discord because you're not subscribed. Send !subscribe to start forwarding your messages."
+                            ).await;
+                            info!("Ignored message from non-subscribed user: {}", sender_pubkey);
                         }
                     },
                     _ => {}, // Ignore other notifications
@@ -0,0 +1,145 @@
+use super::{parse_pubkey, pubkey_to_bech32_or_fail};
+use crate::persist;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use vector_sdk::nostr::PublicKey;
+
+/// How Discord messages are rendered in a subscriber's DMs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// No prefix, just the message
+    Plain,
+    /// `[Discord] name: content`
+    #[default]
+    Prefixed,
+    /// Reserved for clients that render rich content, DMs fall back to `Prefixed`
+    Rich,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub message_format: MessageFormat,
+    pub include_discord_username: bool,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            message_format: MessageFormat::Prefixed,
+            include_discord_username: true,
+        }
+    }
+}
+
+impl UserPreferences {
+    /// Renders a Discord message as this subscriber wants to receive it
+    ///
+    /// The prefix names the message's Discord server when it is known.
+    pub fn format_discord_message(&self, author: &str, content: &str, guild_name: Option<&str>) -> String {
+        let body = if self.include_discord_username {
+            format!("{}: {}", author, content)
+        } else {
+            content.to_string()
+        };
+
+        match self.message_format {
+            MessageFormat::Plain => body,
+            MessageFormat::Prefixed | MessageFormat::Rich => match guild_name {
+                Some(guild_name) => format!("[{} / Discord] {}", guild_name, body),
+                None => format!("[Discord] {}", body),
+            },
+        }
+    }
+}
+
+/// Per-subscriber preferences, persisted as JSON keyed by npub
+#[derive(Clone)]
+pub struct PreferenceStore {
+    preferences: Arc<RwLock<HashMap<PublicKey, UserPreferences>>>,
+    file_path: Option<String>,
+}
+
+impl PreferenceStore {
+    pub fn new(file_path: Option<String>) -> Result<Self> {
+        let mut preferences = HashMap::new();
+
+        // Try to load preferences from the file if it exists
+        if let Some(path) = &file_path {
+            if let Ok(contents) = fs::read_to_string(path) {
+                match serde_json::from_str::<HashMap<String, UserPreferences>>(&contents) {
+                    Ok(loaded) => {
+                        for (key, prefs) in loaded {
+                            match parse_pubkey(&key) {
+                                Ok(pubkey) => {
+                                    preferences.insert(pubkey, prefs);
+                                }
+                                Err(_) => error!("Failed to parse pubkey: {}", key),
+                            }
+                        }
+                        info!("Loaded preferences for {} subscribers", preferences.len());
+                    }
+                    Err(e) => warn!("Failed to parse preferences file: {}", e),
+                }
+            }
+        }
+
+        Ok(Self {
+            preferences: Arc::new(RwLock::new(preferences)),
+            file_path,
+        })
+    }
+
+    pub async fn get(&self, pubkey: &PublicKey) -> UserPreferences {
+        let lock = self.preferences.read().await;
+        lock.get(pubkey).cloned().unwrap_or_default()
+    }
+
+    /// Applies `change` to a subscriber's preferences and saves them
+    pub async fn update(&self, pubkey: PublicKey, change: impl FnOnce(&mut UserPreferences)) {
+        {
+            let mut lock = self.preferences.write().await;
+            change(lock.entry(pubkey).or_default());
+        }
+
+        self.save_to_file().await;
+    }
+
+    async fn save_to_file(&self) {
+        if let Some(path) = &self.file_path {
+            // Snapshot the preferences so the lock isn't held during file I/O
+            let snapshot: Result<HashMap<String, UserPreferences>> = {
+                let lock = self.preferences.read().await;
+                lock.iter()
+                    .map(|(pubkey, prefs)| Ok((pubkey_to_bech32_or_fail(pubkey)?, prefs.clone())))
+                    .collect()
+            };
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    error!("Failed to save preferences: {}", e);
+                    return;
+                }
+            };
+
+            match serde_json::to_vec(&snapshot) {
+                Ok(json) => {
+                    let write_path = path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        persist::atomic_write(&write_path, |file| file.write_all(&json))
+                    }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    if let Err(e) = result {
+                        error!("Failed to write preferences file {}: {}", path, e);
+                    }
+                }
+                Err(e) => error!("Failed to serialize preferences: {}", e),
+            }
+        }
+    }
+}
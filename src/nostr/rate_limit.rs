@@ -0,0 +1,140 @@
+use crate::config::RelayConfig;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tracing::warn;
+use vector_sdk::nostr::{Client, Event};
+
+// Events a rate-limited relay may have waiting before senders wait for it
+const MAX_PENDING_PER_RELAY: usize = 1000;
+
+/// Allows `rate` events a second on average, with bursts of up to one second's worth
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64) -> Self {
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Waits until a token is available and takes it
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let (tokens, last) = &mut *state;
+                let now = Instant::now();
+                *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(self.capacity);
+                *last = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - *tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A rate-limited relay, published to on its own so it never holds back the others
+struct LimitedRelay {
+    url: String,
+    bucket: TokenBucket,
+    /// Caps the events waiting for a token, making senders wait once the relay falls far behind
+    pending: Arc<Semaphore>,
+}
+
+/// Publishes events to the write relays, each rate-limited relay at its own pace
+///
+/// Relays configured with a `max_msgs_per_sec` get their own token bucket, the other write relays
+/// share the global one when `NOSTR_MAX_MSGS_PER_SEC` is set.
+#[derive(Clone, Default)]
+pub struct RelayRateLimits {
+    limited: Arc<Vec<Arc<LimitedRelay>>>,
+    shared: Arc<Vec<String>>,
+    global: Option<Arc<TokenBucket>>,
+}
+
+impl RelayRateLimits {
+    pub fn new(relays: &[RelayConfig], write_relays: &[String], global_rate: Option<f64>) -> Self {
+        let limited: Vec<Arc<LimitedRelay>> = relays.iter()
+            .filter(|relay| !relay.read_only && write_relays.contains(&relay.url))
+            .filter_map(|relay| relay.max_msgs_per_sec.map(|rate| Arc::new(LimitedRelay {
+                url: relay.url.clone(),
+                bucket: TokenBucket::new(rate),
+                pending: Arc::new(Semaphore::new(MAX_PENDING_PER_RELAY)),
+            })))
+            .collect();
+        let shared = write_relays.iter()
+            .filter(|url| !limited.iter().any(|relay| relay.url == **url))
+            .cloned()
+            .collect();
+        Self {
+            limited: Arc::new(limited),
+            shared: Arc::new(shared),
+            global: global_rate.map(|rate| Arc::new(TokenBucket::new(rate))),
+        }
+    }
+
+    /// Whether any write relay is rate limited, otherwise events can be sent the usual way
+    pub fn is_active(&self) -> bool {
+        !self.limited.is_empty() || self.global.is_some()
+    }
+
+    /// The relays with a rate of their own
+    pub fn relays(&self) -> impl Iterator<Item = &str> {
+        self.limited.iter().map(|relay| relay.url.as_str())
+    }
+
+    /// Publishes `event` to every write relay, returning false if none took it
+    ///
+    /// Rate-limited relays are handed the event to publish once they have a token, so only the
+    /// shared relays' result is known here.
+    pub async fn publish(&self, client: &Client, event: &Event) -> bool {
+        let mut accepted = false;
+        for relay in self.limited.iter() {
+            let Ok(permit) = relay.pending.clone().acquire_owned().await else {
+                continue;
+            };
+            let (relay, client, event) = (relay.clone(), client.clone(), event.clone());
+            tokio::spawn(async move {
+                relay.bucket.acquire().await;
+                if let Err(e) = client.send_event_to([relay.url.as_str()], &event).await {
+                    warn!("Failed to publish to {}: {:?}", relay.url, e);
+                }
+                drop(permit);
+            });
+            accepted = true;
+        }
+
+        if !self.shared.is_empty() {
+            if let Some(global) = &self.global {
+                global.acquire().await;
+            }
+            match client.send_event_to(self.shared.iter().map(String::as_str), event).await {
+                Ok(output) => accepted |= !output.success.is_empty(),
+                Err(e) => warn!("Failed to publish to the write relays: {:?}", e),
+            }
+        }
+        accepted
+    }
+
+    /// Waits for a token from every limit, for events the Vector SDK publishes to all write relays at once
+    pub async fn acquire_all(&self) {
+        for relay in self.limited.iter() {
+            relay.bucket.acquire().await;
+        }
+        if let Some(global) = self.global.as_ref().filter(|_| !self.shared.is_empty()) {
+            global.acquire().await;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::message::BridgeMessage;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Lets admins halt bridging in both directions, holding messages until it resumes
+#[derive(Clone)]
+pub struct BridgePause {
+    bridge_paused: Arc<AtomicBool>,
+    queue: Arc<Mutex<VecDeque<BridgeMessage>>>,
+    buffer_size: usize,
+}
+
+impl BridgePause {
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            bridge_paused: Arc::new(AtomicBool::new(false)),
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            buffer_size,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.bridge_paused.load(Ordering::SeqCst)
+    }
+
+    /// Pauses bridging, returning false if it was already paused
+    pub fn pause(&self) -> bool {
+        !self.bridge_paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// Resumes bridging and returns the messages held while paused, or `None` if it wasn't paused
+    pub fn resume(&self) -> Option<Vec<BridgeMessage>> {
+        if !self.bridge_paused.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+
+        Some(self.queue.lock().unwrap().drain(..).collect())
+    }
+
+    /// Holds a message until the bridge resumes, dropping it if the buffer is full
+    pub fn hold(&self, message: BridgeMessage) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= self.buffer_size {
+            warn!("Pause buffer full ({} messages), dropping message", self.buffer_size);
+            return;
+        }
+        queue.push_back(message);
+    }
+}
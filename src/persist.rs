@@ -0,0 +1,30 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Replaces the file at `path` with what `write` puts in a temporary sibling
+///
+/// The temporary file is synced before being renamed over `path`, which is atomic on POSIX
+/// filesystems, so a crash mid-write leaves the previous contents intact. The directory is
+/// synced afterwards so the rename itself survives a power loss.
+pub fn atomic_write(path: &str, write: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    let result = fs::File::create(&tmp_path).and_then(|mut file| {
+        write(&mut file)?;
+        file.flush()?;
+        file.sync_all()
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, path)?;
+
+    // Directories can't be opened for syncing on every platform, where the rename is left to the OS
+    let dir = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if let Ok(dir) = fs::File::open(dir) {
+        dir.sync_all()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::message::BridgeMessage;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendError;
+
+/// Delivery order of queued messages, lower goes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessagePriority {
+    System = 0,
+    User = 1,
+}
+
+/// Messages that can be queued on a `PriorityChannel`
+pub trait Prioritized {
+    fn priority(&self) -> MessagePriority;
+}
+
+impl Prioritized for BridgeMessage {
+    fn priority(&self) -> MessagePriority {
+        match self {
+            BridgeMessage::System { .. } => MessagePriority::System,
+            BridgeMessage::Discord { .. }
+            | BridgeMessage::Nostr { .. }
+            | BridgeMessage::DiscordReaction { .. }
+            | BridgeMessage::NostrReaction { .. }
+            | BridgeMessage::DeliveryReceipt { .. }
+            | BridgeMessage::Mention { .. }
+            | BridgeMessage::Redaction { .. } => MessagePriority::User,
+        }
+    }
+}
+
+/// Creates an mpsc channel where system messages overtake queued user messages, holding up to
+/// `buffer` messages of each priority
+pub fn priority_channel<T: Prioritized>(buffer: usize) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    let (high_tx, high_rx) = mpsc::channel(buffer);
+    let (low_tx, low_rx) = mpsc::channel(buffer);
+    (
+        PrioritySender { high: high_tx, low: low_tx },
+        PriorityReceiver { high: high_rx, low: low_rx },
+    )
+}
+
+pub struct PrioritySender<T> {
+    high: mpsc::Sender<T>,
+    low: mpsc::Sender<T>,
+}
+
+// Derived Clone would needlessly require `T: Clone`
+impl<T> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            high: self.high.clone(),
+            low: self.low.clone(),
+        }
+    }
+}
+
+impl<T: Prioritized> PrioritySender<T> {
+    /// Queues a message on the lane for its priority, waiting while that lane is full
+    pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+        match message.priority() {
+            MessagePriority::System => self.high.send(message).await,
+            MessagePriority::User => self.low.send(message).await,
+        }
+    }
+}
+
+pub struct PriorityReceiver<T> {
+    high: mpsc::Receiver<T>,
+    low: mpsc::Receiver<T>,
+}
+
+impl<T> PriorityReceiver<T> {
+    /// Receives the next message, always draining system messages before user messages
+    pub async fn recv(&mut self) -> Option<T> {
+        tokio::select! {
+            biased;
+            Some(message) = self.high.recv() => Some(message),
+            Some(message) = self.low.recv() => Some(message),
+            else => None,
+        }
+    }
+}
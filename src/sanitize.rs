@@ -0,0 +1,29 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Bidirectional formatting controls, which can make text render differently from what it says
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Variation selectors VS1-VS16
+fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}')
+}
+
+/// Zero-width space and the zero-width no-break space (BOM)
+fn is_zero_width_space(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{FEFF}')
+}
+
+/// Strips invisible characters that can disguise a message and normalizes it to NFC
+pub fn normalize_content(s: &str) -> String {
+    s.chars()
+        .filter(|c| !is_bidi_control(*c) && !is_variation_selector(*c) && !is_zero_width_space(*c))
+        .nfc()
+        .collect()
+}
+
+/// True if `s` contains any bidirectional formatting control
+pub fn contains_bidi_control(s: &str) -> bool {
+    s.chars().any(is_bidi_control)
+}
@@ -0,0 +1,45 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+
+/// Parses an IANA timezone name such as "America/New_York"
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    Tz::from_str(name.trim())
+        .map_err(|_| anyhow!("TIMEZONE must be an IANA timezone name such as 'America/New_York', got '{}'", name))
+}
+
+/// Formats Unix seconds as local time in `tz`, for display only
+pub fn format_local(unix_secs: u64, tz: Tz) -> String {
+    DateTime::<Utc>::from_timestamp(unix_secs as i64, 0)
+        .unwrap_or_default()
+        .with_timezone(&tz)
+        .format("%Y-%m-%d %H:%M:%S %Z")
+        .to_string()
+}
+
+/// Time left from `now` until the next midnight in `tz`
+pub fn until_next_midnight(now: DateTime<Utc>, tz: Tz) -> Duration {
+    let local = now.with_timezone(&tz);
+    let next_midnight = local.date_naive().succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(tz).earliest());
+    match next_midnight {
+        Some(midnight) => (midnight.with_timezone(&Utc) - now).to_std().unwrap_or_default(),
+        // Some zones skip midnight on DST changes, a day from now is close enough there
+        None => Duration::from_secs(60 * 60 * 24),
+    }
+}
+
+/// Timestamps log lines in a configured timezone instead of UTC
+pub struct LocalTimer(pub Tz);
+
+impl FormatTime for LocalTimer {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(w, "{}", Utc::now().with_timezone(&self.0).format("%Y-%m-%dT%H:%M:%S%.6f%:z"))
+    }
+}
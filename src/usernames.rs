@@ -0,0 +1,78 @@
+use crate::persist;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+use vector_sdk::nostr::PublicKey;
+
+/// Discord usernames linked to Nostr users with `!register-discord`, persisted as JSON keyed by lowercase username
+///
+/// Shared by every bridge, a mention of a registered username is DMed to its Nostr user.
+#[derive(Clone, Default)]
+pub struct UsernameMap {
+    usernames: Arc<Mutex<HashMap<String, PublicKey>>>,
+    file_path: Option<String>,
+}
+
+impl UsernameMap {
+    pub fn new(file_path: Option<String>) -> Self {
+        let mut usernames = HashMap::new();
+
+        // Try to load the registrations from the file if it exists
+        if let Some(path) = &file_path {
+            if let Ok(contents) = fs::read_to_string(path) {
+                match serde_json::from_str::<HashMap<String, PublicKey>>(&contents) {
+                    Ok(loaded) => {
+                        usernames = loaded;
+                        info!("Loaded {} registered Discord usernames", usernames.len());
+                    }
+                    Err(e) => warn!("Failed to parse username map file: {}", e),
+                }
+            }
+        }
+
+        Self {
+            usernames: Arc::new(Mutex::new(usernames)),
+            file_path,
+        }
+    }
+
+    /// The Nostr user who registered `username`, in any case
+    pub fn get(&self, username: &str) -> Option<PublicKey> {
+        self.usernames.lock().unwrap().get(&username.to_lowercase()).copied()
+    }
+
+    /// Links `username` to `pubkey`, replacing the username `pubkey` registered before, and saves the map
+    pub async fn register(&self, username: &str, pubkey: PublicKey) {
+        {
+            let mut usernames = self.usernames.lock().unwrap();
+            usernames.retain(|_, registered| *registered != pubkey);
+            usernames.insert(username.to_lowercase(), pubkey);
+        }
+        self.save_to_file().await;
+    }
+
+    async fn save_to_file(&self) {
+        if let Some(path) = &self.file_path {
+            // Snapshot the registrations so the lock isn't held during file I/O
+            let json_result = {
+                let usernames = self.usernames.lock().unwrap();
+                serde_json::to_vec(&*usernames)
+            };
+
+            match json_result {
+                Ok(json) => {
+                    let path = path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        persist::atomic_write(&path, |file| file.write_all(&json))
+                    }).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    if let Err(e) = result {
+                        error!("Failed to write username map file: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to serialize username map: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Authentication and JSON shape of the admin HTTP API.
+
+use base64::engine::{general_purpose, Engine};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use vecord::admin::{self, AdminBridge, AdminState};
+use vecord::metadata::MetadataCache;
+use vecord::nostr::{RelayMonitor, SubscriberList};
+use vecord::pause::BridgePause;
+use nostr_sdk::JsonUtil;
+use vector_sdk::nostr::{EventBuilder, Keys, Kind, Tag, TagKind, Timestamp, ToBech32};
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+/// A base64 NIP-98 event signed by `keys` for `method` on `url`, created at `created_at`
+fn signed_auth(keys: &Keys, method: &str, url: &str, created_at: Timestamp) -> String {
+    let event = EventBuilder::new(Kind::HttpAuth, "")
+        .tags([Tag::custom(TagKind::u(), [url]), Tag::custom(TagKind::Method, [method])])
+        .custom_created_at(created_at)
+        .sign_with_keys(keys)
+        .unwrap();
+    general_purpose::STANDARD.encode(event.as_json())
+}
+
+/// An `Authorization` header value for `method` on `url`, signed by `keys` just now
+fn auth(keys: &Keys, method: &str, url: &str) -> String {
+    format!("Nostr {}", signed_auth(keys, method, url, Timestamp::now()))
+}
+
+/// Serves the admin API for one bridge with a relay on record, returning its address, subscribers and admin key
+async fn spawn_admin_api() -> (String, SubscriberList, Keys) {
+    let relay_monitor = RelayMonitor::default();
+    relay_monitor.record("wss://relay.example", true);
+    let subscribers = SubscriberList::new(None).unwrap();
+    let bridge = AdminBridge {
+        subscribers: subscribers.clone(),
+        metadata_cache: MetadataCache::new(None).unwrap(),
+        relay_monitor,
+        bridge_pause: BridgePause::new(10),
+        bot: None,
+        notices: None,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let admin = Keys::generate();
+    let app = admin::router(AdminState::new(vec![admin.public_key()], vec![bridge]));
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (addr, subscribers, admin)
+}
+
+#[tokio::test]
+async fn requests_without_admin_authorization_are_rejected() {
+    let (addr, _, admin) = spawn_admin_api().await;
+    let url = format!("http://{}/admin/subscribers", addr);
+    let client = reqwest::Client::new();
+
+    let missing = client.get(&url).send().await.unwrap();
+    assert_eq!(missing.status(), 401);
+
+    let bearer = client.get(&url).bearer_auth("guess").send().await.unwrap();
+    assert_eq!(bearer.status(), 401);
+
+    let stranger = client.get(&url)
+        .header("Authorization", auth(&Keys::generate(), "GET", &url))
+        .send().await.unwrap();
+    assert_eq!(stranger.status(), 401);
+
+    let other_url = client.get(&url)
+        .header("Authorization", auth(&admin, "GET", &format!("http://{}/admin/relays", addr)))
+        .send().await.unwrap();
+    assert_eq!(other_url.status(), 401);
+
+    let other_method = client.get(&url)
+        .header("Authorization", auth(&admin, "POST", &url))
+        .send().await.unwrap();
+    assert_eq!(other_method.status(), 401);
+
+    let stale = signed_auth(&admin, "GET", &url, Timestamp::now() - 120);
+    let expired = client.get(&url)
+        .header("Authorization", format!("Nostr {}", stale))
+        .send().await.unwrap();
+    assert_eq!(expired.status(), 401);
+
+    let signed = client.get(&url)
+        .header("Authorization", auth(&admin, "GET", &url))
+        .send().await.unwrap();
+    assert_eq!(signed.status(), 200);
+}
+
+#[tokio::test]
+async fn subscribers_can_be_added_listed_and_removed() {
+    let (addr, _, admin) = spawn_admin_api().await;
+    let subscribers_url = format!("http://{}/subscribers", addr);
+    let client = reqwest::Client::new();
+    let npub = Keys::generate().public_key().to_bech32().unwrap();
+
+    let added = client.post(&subscribers_url)
+        .header("Authorization", auth(&admin, "POST", &subscribers_url))
+        .json(&json!({ "pubkey": npub }))
+        .send().await.unwrap();
+    assert_eq!(added.status(), 201);
+
+    let listed: Value = client.get(&subscribers_url)
+        .header("Authorization", auth(&admin, "GET", &subscribers_url))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!(listed[0]["pubkey"], npub.as_str());
+
+    let subscriber_url = format!("{}/{}", subscribers_url, npub);
+    let removed = client.delete(&subscriber_url)
+        .header("Authorization", auth(&admin, "DELETE", &subscriber_url))
+        .send().await.unwrap();
+    assert_eq!(removed.status(), 200);
+
+    // Errors carry a JSON message
+    let missing = client.delete(&subscriber_url)
+        .header("Authorization", auth(&admin, "DELETE", &subscriber_url))
+        .send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+    let body: Value = missing.json().await.unwrap();
+    assert_eq!(body["error"], "Not subscribed");
+
+    let invalid = client.post(&subscribers_url)
+        .header("Authorization", auth(&admin, "POST", &subscribers_url))
+        .json(&json!({ "pubkey": "npub1nope" }))
+        .send().await.unwrap();
+    assert_eq!(invalid.status(), 400);
+}
+
+#[tokio::test]
+async fn health_reports_each_bridge() {
+    let (addr, _, _) = spawn_admin_api().await;
+    let url = format!("http://{}/health", addr);
+    // Health checks need no admin key
+    let health: Value = reqwest::Client::new()
+        .get(&url)
+        .send().await.unwrap()
+        .json().await.unwrap();
+
+    // The only recorded relay never passed a health check, so it isn't connected
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(health["bridges"][0]["subscribers"], 0);
+    assert_eq!(health["bridges"][0]["paused"], false);
+}
+
+#[tokio::test]
+async fn relays_are_reported_as_json() {
+    let (addr, _, admin) = spawn_admin_api().await;
+    let url = format!("http://{}/admin/relays", addr);
+    let relays: Value = reqwest::Client::new()
+        .get(&url)
+        .header("Authorization", auth(&admin, "GET", &url))
+        .send().await.unwrap()
+        .json().await.unwrap();
+
+    assert_eq!(relays[0]["url"], "wss://relay.example");
+    assert_eq!(relays[0]["messages_sent"], 1);
+    assert_eq!(relays[0]["messages_failed"], 0);
+}
+
+/// Reads the next JSON frame from an admin session
+async fn next_json<S>(session: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let Some(Ok(Message::Text(text))) = session.next().await else {
+        panic!("admin session closed");
+    };
+    serde_json::from_str(text.as_str()).unwrap()
+}
+
+#[tokio::test]
+async fn websocket_sessions_manage_and_stream_subscribers() {
+    let (addr, subscribers, admin) = spawn_admin_api().await;
+    let url = format!("ws://{}/admin/ws", addr);
+    let stranger = signed_auth(&Keys::generate(), "GET", &url, Timestamp::now());
+    let rejected = Url::parse_with_params(&url, [("auth", stranger)]).unwrap();
+    assert!(tokio_tungstenite::connect_async(rejected.as_str()).await.is_err());
+
+    let signed = signed_auth(&admin, "GET", &url, Timestamp::now());
+    let accepted = Url::parse_with_params(&url, [("auth", signed)]).unwrap();
+    let (mut session, _) = tokio_tungstenite::connect_async(accepted.as_str()).await.unwrap();
+
+    // A subscribe elsewhere in the bridge is pushed to the session
+    let pubkey = Keys::generate().public_key();
+    let npub = pubkey.to_bech32().unwrap();
+    subscribers.add(pubkey).await;
+    assert_eq!(next_json(&mut session).await, json!({ "event": "subscriber_joined", "pubkey": npub }));
+
+    let command = json!({ "cmd": "ban", "pubkey": npub, "reason": "spam" }).to_string();
+    session.send(Message::Text(command.into())).await.unwrap();
+
+    // The ban unsubscribes them, so its event and the reply both arrive
+    let replies = [next_json(&mut session).await, next_json(&mut session).await];
+    assert!(replies.contains(&json!({ "ok": true })));
+    assert!(replies.contains(&json!({ "event": "subscriber_left", "pubkey": npub })));
+    assert_eq!(subscribers.ban_reason(&pubkey).await.as_deref(), Some("spam"));
+}
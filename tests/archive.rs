@@ -0,0 +1,68 @@
+//! Archiving bridged messages and searching them.
+
+use tracing::Span;
+use vecord::archive::MessageArchive;
+use vecord::message::{BridgeMessage, NostrMessageMetadata};
+
+async fn temp_archive(name: &str) -> MessageArchive {
+    let path = std::env::temp_dir().join(format!("vecord-archive-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    MessageArchive::open(&path.to_string_lossy()).await.unwrap()
+}
+
+fn discord(author: &str, content: &str) -> BridgeMessage {
+    BridgeMessage::Discord {
+        author: author.to_string(),
+        content: content.to_string(),
+        image: None,
+        image_url: None,
+        federation_source: None,
+        guild_name: None,
+        guild_icon_url: None,
+        author_id: None,
+        message_id: None,
+        span: Span::none(),
+    }
+}
+
+#[tokio::test]
+async fn messages_are_found_by_content_and_author() {
+    let archive = temp_archive("search").await;
+    archive.insert(&discord("alice", "the relay is down again"), None).await.unwrap();
+    archive.insert(&discord("bob", "lunch anyone?"), None).await.unwrap();
+    archive.insert(&BridgeMessage::Nostr {
+        content: "relay looks fine from here".to_string(),
+        metadata: NostrMessageMetadata {
+            username: "carol".to_string(),
+            pubkey: "npub1carol".to_string(),
+            avatar_url: None,
+            banner_url: None,
+            event_created_at: Some(1_700_000_000),
+            lud16: None,
+            reply_to_discord_user_id: None,
+            website: None,
+            relay_url: None,
+            event_id: Some("ab".repeat(32)),
+        },
+        span: Span::none(),
+    }, Some(42)).await.unwrap();
+
+    let relay = archive.search("relay", 50).await.unwrap();
+    assert_eq!(relay.len(), 2);
+    let carol = relay.iter().find(|m| m.direction == "nostr_to_discord").unwrap();
+    assert_eq!(carol.pubkey.as_deref(), Some("npub1carol"));
+    assert_eq!(carol.discord_message_id.as_deref(), Some("42"));
+    assert_eq!(carol.created_at, Some(1_700_000_000));
+
+    let by_author = archive.search("author:bob", 50).await.unwrap();
+    assert_eq!(by_author.len(), 1);
+    assert_eq!(by_author[0].content, "lunch anyone?");
+
+    assert_eq!(archive.search("relay", 1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn malformed_queries_are_errors() {
+    let archive = temp_archive("malformed").await;
+    assert!(archive.search("\"unterminated", 50).await.is_err());
+}
@@ -0,0 +1,25 @@
+//! Named bridges that senders pick with `!subscribe-channel`.
+
+use vecord::nostr::{BridgeChannel, BridgeChannels, SubscriberList};
+use vector_sdk::nostr::Keys;
+
+#[tokio::test]
+async fn channels_share_their_bridges_subscribers() {
+    let channels = BridgeChannels::default();
+    let general = SubscriberList::new(None).unwrap();
+    let announcements = SubscriberList::new(None).unwrap();
+    for (name, subscribers) in [("general", &general), ("announcements", &announcements)] {
+        channels.register(name.to_string(), BridgeChannel {
+            npub: format!("npub-{}", name),
+            subscribers: subscribers.clone(),
+        });
+    }
+    assert_eq!(channels.names(), vec!["announcements", "general"]);
+    assert!(channels.get("random").is_none());
+
+    // Subscribing through the registry reaches the bridge's own list
+    let pubkey = Keys::generate().public_key();
+    assert!(channels.get("general").unwrap().subscribers.add(pubkey).await);
+    assert!(general.contains(&pubkey).await);
+    assert!(!announcements.contains(&pubkey).await);
+}
@@ -0,0 +1,36 @@
+//! Missing settings are reported as typed errors rather than panics.
+
+use vecord::config::{Config, ConfigError};
+
+fn missing(toml: &str) -> Option<String> {
+    let Err(error) = Config::from_toml_str(toml) else {
+        return None;
+    };
+    match error.downcast::<ConfigError>() {
+        Ok(ConfigError::MissingRequired(name)) => Some(name),
+        _ => None,
+    }
+}
+
+#[test]
+fn missing_settings_are_named() {
+    assert_eq!(missing(""), Some("DISCORD_TOKEN".to_string()));
+    assert_eq!(missing("[discord]\ntoken = \"token\""), Some("DISCORD_CHANNEL_ID".to_string()));
+    assert_eq!(
+        missing("[discord]\ntoken = \"token\"\nchannel_id = 1"),
+        Some("NOSTR_PRIVATE_KEY".to_string()),
+    );
+}
+
+#[test]
+fn plain_and_encrypted_keys_are_incompatible() {
+    let toml = "[discord]\ntoken = \"token\"\nchannel_id = 1\n\
+        [nostr]\nprivate_key = \"nsec1...\"\nprivate_key_encrypted = \"00\"";
+    let Err(error) = Config::from_toml_str(toml) else {
+        panic!("a plain and an encrypted key were both accepted");
+    };
+    assert!(matches!(
+        error.downcast::<ConfigError>(),
+        Ok(ConfigError::Incompatible(first, second)) if first == "NOSTR_PRIVATE_KEY" && second == "NOSTR_PRIVATE_KEY_ENCRYPTED"
+    ));
+}
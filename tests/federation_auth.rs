@@ -0,0 +1,35 @@
+//! Federation peers authenticate with the shared FEDERATION_TOKEN.
+
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use vecord::federation;
+use vecord::message::BridgeMessage;
+use vecord::priority::priority_channel;
+
+/// Starts a federation listener with `token` on a free local port, returning its URL
+async fn spawn_listener(token: &str) -> String {
+    let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().to_string();
+    let (tx, _) = priority_channel::<BridgeMessage>(10);
+    let listen_addr = addr.clone();
+    let token = token.to_string();
+    tokio::spawn(async move {
+        federation::listen(&listen_addr, token, vec![tx]).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    format!("ws://{}", addr)
+}
+
+#[tokio::test]
+async fn peers_without_the_token_are_refused() {
+    let url = spawn_listener("secret").await;
+    assert!(tokio_tungstenite::connect_async(url.as_str()).await.is_err());
+
+    let mut request = url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert("authorization", "Bearer wrong".parse().unwrap());
+    assert!(tokio_tungstenite::connect_async(request).await.is_err());
+
+    let mut request = url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert("authorization", "Bearer secret".parse().unwrap());
+    assert!(tokio_tungstenite::connect_async(request).await.is_ok());
+}
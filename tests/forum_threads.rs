@@ -0,0 +1,38 @@
+//! Forum threads remembered per Nostr sender across restarts.
+
+use serenity::all::ChannelId;
+use std::path::PathBuf;
+use vecord::discord::ForumThreads;
+
+const NPUB: &str = "npub1zutzeysacnf9rru6zqwmxd54mud0k44tst6l70ja5mhv8jjumytsd2x7nu";
+
+fn temp_file(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("vecord-forum-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path: PathBuf = dir.join("forum_threads.json");
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
+#[tokio::test]
+async fn threads_are_reloaded_from_the_file() {
+    let path = temp_file("reload");
+
+    let threads = ForumThreads::new(Some(path.clone()));
+    assert_eq!(threads.get(NPUB), None);
+    threads.insert(NPUB.to_string(), ChannelId::new(1234)).await;
+
+    let reloaded = ForumThreads::new(Some(path));
+    assert_eq!(reloaded.get(NPUB), Some(ChannelId::new(1234)));
+}
+
+#[tokio::test]
+async fn removed_threads_stay_removed() {
+    let path = temp_file("remove");
+
+    let threads = ForumThreads::new(Some(path.clone()));
+    threads.insert(NPUB.to_string(), ChannelId::new(1234)).await;
+    threads.remove(NPUB).await;
+
+    assert_eq!(ForumThreads::new(Some(path)).get(NPUB), None);
+}
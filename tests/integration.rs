@@ -0,0 +1,332 @@
+//! End-to-end bridge tests against an in-process mock Nostr relay and a mock
+//! Discord webhook, so no network access is needed.
+
+use axum::{extract::State, routing::post, Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use vecord::config::Config;
+use vecord::discord::DiscordBot;
+use vecord::priority::{priority_channel, PrioritySender};
+use vecord::message::BridgeMessage;
+use vecord::nostr::NostrClient;
+use vector_sdk::nostr::{Event, EventBuilder, Keys, Kind, Tag, ToBech32, UnwrappedGift};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A minimal NIP-01 relay that records published events and can push events
+/// to any subscription asking for gift wraps
+struct MockNostrRelay {
+    url: String,
+    received: Arc<Mutex<Vec<Event>>>,
+    inject: broadcast::Sender<Event>,
+}
+
+impl MockNostrRelay {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (inject, _) = broadcast::channel::<Event>(16);
+
+        let received_clone = received.clone();
+        let inject_clone = inject.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let received = received_clone.clone();
+                let injected = inject_clone.subscribe();
+                tokio::spawn(Self::serve(stream, received, injected));
+            }
+        });
+
+        Self { url, received, inject }
+    }
+
+    async fn serve(
+        stream: tokio::net::TcpStream,
+        received: Arc<Mutex<Vec<Event>>>,
+        mut injected: broadcast::Receiver<Event>,
+    ) {
+        let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+            return;
+        };
+        let (mut sink, mut source) = ws.split();
+        let mut giftwrap_subs: Vec<String> = Vec::new();
+
+        loop {
+            tokio::select! {
+                frame = source.next() => {
+                    let Some(Ok(Message::Text(text))) = frame else {
+                        if frame.is_none() { return; }
+                        continue;
+                    };
+                    let Ok(Value::Array(msg)) = serde_json::from_str::<Value>(&text) else {
+                        continue;
+                    };
+
+                    let replies = match msg.first().and_then(Value::as_str) {
+                        Some("EVENT") => {
+                            let event: Event = serde_json::from_value(msg[1].clone()).unwrap();
+                            let id = event.id.to_hex();
+                            received.lock().unwrap().push(event);
+                            vec![json!(["OK", id, true, ""])]
+                        }
+                        Some("REQ") => {
+                            let sub_id = msg[1].as_str().unwrap_or_default().to_string();
+                            let wants_giftwraps = msg[2..].iter().any(|filter| {
+                                filter["kinds"].as_array()
+                                    .is_some_and(|kinds| kinds.contains(&json!(Kind::GiftWrap.as_u16())))
+                            });
+                            if wants_giftwraps {
+                                giftwrap_subs.push(sub_id.clone());
+                            }
+                            vec![json!(["EOSE", sub_id])]
+                        }
+                        Some("CLOSE") => {
+                            let sub_id = msg[1].as_str().unwrap_or_default();
+                            giftwrap_subs.retain(|s| s != sub_id);
+                            vec![]
+                        }
+                        _ => vec![],
+                    };
+
+                    for reply in replies {
+                        if sink.send(Message::Text(reply.to_string().into())).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                event = injected.recv() => {
+                    let Ok(event) = event else { continue };
+                    for sub_id in &giftwrap_subs {
+                        let frame = json!(["EVENT", sub_id, event]).to_string();
+                        if sink.send(Message::Text(frame.into())).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Waits for a published gift wrap addressed to `receiver`
+    async fn wait_for_giftwrap_to(&self, receiver: &Keys) -> Event {
+        let receiver_hex = receiver.public_key().to_hex();
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let found = self.received.lock().unwrap().iter()
+                    .find(|e| e.kind == Kind::GiftWrap && e.tags.iter().any(|t| t.as_slice() == ["p", receiver_hex.as_str()]))
+                    .cloned();
+                if let Some(event) = found {
+                    return event;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("relay never received a gift wrap for the subscriber")
+    }
+}
+
+/// Stands in for a Discord webhook, capturing every posted JSON payload
+struct MockDiscordHttp {
+    url: String,
+    captured: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockDiscordHttp {
+    async fn start() -> Self {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route("/webhook", post(|State(captured): State<Arc<Mutex<Vec<Value>>>>, Json(body): Json<Value>| async move {
+                captured.lock().unwrap().push(body);
+            }))
+            .with_state(captured.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self { url, captured }
+    }
+
+    async fn wait_for_message(&self) -> Value {
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                if let Some(body) = self.captured.lock().unwrap().first().cloned() {
+                    return body;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("Discord webhook never received a message")
+    }
+}
+
+/// A scratch directory for this test's subscriber and metadata files
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vecord-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Builds a config for the mocks with `subscriber` already subscribed
+fn test_config(name: &str, bridge: &Keys, subscriber: &Keys, relay: &MockNostrRelay, discord: &MockDiscordHttp) -> Config {
+    let dir = temp_dir(name);
+    let subscribers_file = dir.join("subscribers.txt");
+    std::fs::write(&subscribers_file, format!("{}\n", subscriber.public_key().to_bech32().unwrap())).unwrap();
+
+    let mut config = Config::default_for_testing();
+    config.discord_token = None;
+    config.discord_webhook_url = Some(discord.url.clone());
+    config.nostr_private_key = bridge.secret_key().to_secret_hex();
+    config.nostr_read_relays = vec![relay.url.clone()];
+    config.nostr_write_relays = vec![relay.url.clone()];
+    config.subscribers_file = Some(subscribers_file.to_string_lossy().to_string());
+    config.metadata_cache_file = Some(dir.join("metadata_cache.json").to_string_lossy().to_string());
+    config
+}
+
+/// Starts the bridge the same way `main` wires it, minus the Discord gateway
+async fn start_bridge(config: &Config) -> PrioritySender<BridgeMessage> {
+    let (nostr_to_discord_tx, mut nostr_to_discord_rx) = priority_channel::<BridgeMessage>(100);
+
+    let discord_bot = DiscordBot::new(config);
+    tokio::spawn(async move {
+        while let Some(message) = nostr_to_discord_rx.recv().await {
+            discord_bot.send_message(&message).await.unwrap();
+        }
+    });
+
+    let mut nostr_client = NostrClient::new(config).unwrap();
+    nostr_client.start(nostr_to_discord_tx).await.unwrap()
+}
+
+#[tokio::test]
+async fn discord_message_is_delivered_as_nip17_dm() {
+    let relay = MockNostrRelay::start().await;
+    let discord = MockDiscordHttp::start().await;
+    let bridge = Keys::generate();
+    let subscriber = Keys::generate();
+
+    let config = test_config("discord-to-nostr", &bridge, &subscriber, &relay, &discord);
+    let discord_to_nostr = start_bridge(&config).await;
+
+    discord_to_nostr.send(BridgeMessage::Discord {
+        author: "alice".to_string(),
+        content: "hello from discord".to_string(),
+        image: None,
+        image_url: None,
+        federation_source: None,
+        guild_name: None,
+        guild_icon_url: None,
+        author_id: None,
+        message_id: None,
+        span: tracing::Span::none(),
+    }).await.unwrap();
+
+    let gift_wrap = relay.wait_for_giftwrap_to(&subscriber).await;
+    let UnwrappedGift { rumor, sender } = UnwrappedGift::from_gift_wrap(&subscriber, &gift_wrap).await.unwrap();
+
+    assert_eq!(sender, bridge.public_key());
+    assert_eq!(rumor.kind, Kind::PrivateDirectMessage);
+    assert_eq!(rumor.content, "[Discord] alice: hello from discord");
+}
+
+#[tokio::test]
+async fn nostr_dm_is_posted_to_discord() {
+    let relay = MockNostrRelay::start().await;
+    let discord = MockDiscordHttp::start().await;
+    let bridge = Keys::generate();
+    let subscriber = Keys::generate();
+
+    let config = test_config("nostr-to-discord", &bridge, &subscriber, &relay, &discord);
+    let _discord_to_nostr = start_bridge(&config).await;
+
+    let rumor = EventBuilder::private_msg_rumor(bridge.public_key(), "hello from nostr")
+        .build(subscriber.public_key());
+    let gift_wrap = EventBuilder::gift_wrap(&subscriber, &bridge.public_key(), rumor, []).await.unwrap();
+    relay.inject.send(gift_wrap).unwrap();
+
+    let body = discord.wait_for_message().await;
+    assert_eq!(body["content"], "hello from nostr");
+    assert!(body["username"].as_str().unwrap().starts_with("Nostr: npub"));
+}
+
+#[tokio::test]
+async fn nostr_reply_mentions_the_discord_author() {
+    let relay = MockNostrRelay::start().await;
+    let discord = MockDiscordHttp::start().await;
+    let bridge = Keys::generate();
+    let subscriber = Keys::generate();
+
+    let mut config = test_config("nostr-reply", &bridge, &subscriber, &relay, &discord);
+    config.discord_mention_on_nostr_reply = true;
+    let discord_to_nostr = start_bridge(&config).await;
+
+    discord_to_nostr.send(BridgeMessage::Discord {
+        author: "alice".to_string(),
+        content: "anyone around?".to_string(),
+        image: None,
+        image_url: None,
+        federation_source: None,
+        guild_name: None,
+        guild_icon_url: None,
+        author_id: Some(80351110224678912),
+        message_id: None,
+        span: tracing::Span::none(),
+    }).await.unwrap();
+
+    let gift_wrap = relay.wait_for_giftwrap_to(&subscriber).await;
+    let UnwrappedGift { mut rumor, .. } = UnwrappedGift::from_gift_wrap(&subscriber, &gift_wrap).await.unwrap();
+
+    let reply = EventBuilder::private_msg_rumor(bridge.public_key(), "I am")
+        .tag(Tag::event(rumor.id()))
+        .build(subscriber.public_key());
+    let gift_wrap = EventBuilder::gift_wrap(&subscriber, &bridge.public_key(), reply, []).await.unwrap();
+    relay.inject.send(gift_wrap).unwrap();
+
+    let body = discord.wait_for_message().await;
+    assert_eq!(body["content"], "<@80351110224678912> I am");
+}
+
+#[tokio::test]
+async fn delivered_discord_message_gets_a_receipt() {
+    let relay = MockNostrRelay::start().await;
+    let discord = MockDiscordHttp::start().await;
+    let bridge = Keys::generate();
+    let subscriber = Keys::generate();
+
+    let mut config = test_config("delivery-receipt", &bridge, &subscriber, &relay, &discord);
+    config.delivery_receipt_reactions = true;
+    let (nostr_to_discord_tx, mut nostr_to_discord_rx) = priority_channel::<BridgeMessage>(100);
+    let discord_to_nostr = NostrClient::new(&config).unwrap().start(nostr_to_discord_tx).await.unwrap();
+
+    discord_to_nostr.send(BridgeMessage::Discord {
+        author: "alice".to_string(),
+        content: "did this arrive?".to_string(),
+        image: None,
+        image_url: None,
+        federation_source: None,
+        guild_name: None,
+        guild_icon_url: None,
+        author_id: None,
+        message_id: Some(1234),
+        span: tracing::Span::none(),
+    }).await.unwrap();
+
+    let receipt = tokio::time::timeout(TIMEOUT, nostr_to_discord_rx.recv()).await.unwrap().unwrap();
+    let BridgeMessage::DeliveryReceipt { discord_message_id, emoji, .. } = receipt else {
+        panic!("expected a delivery receipt, got {:?}", receipt);
+    };
+    assert_eq!(discord_message_id, 1234);
+    assert_eq!(emoji, "✅");
+}
@@ -0,0 +1,13 @@
+//! How Discord messages are rendered for Nostr subscribers.
+
+use vecord::nostr::{MessageFormat, UserPreferences};
+
+#[test]
+fn the_prefix_names_the_discord_server() {
+    let preferences = UserPreferences::default();
+    assert_eq!(preferences.format_discord_message("alice", "hi", Some("Vector")), "[Vector / Discord] alice: hi");
+    assert_eq!(preferences.format_discord_message("alice", "hi", None), "[Discord] alice: hi");
+
+    let plain = UserPreferences { message_format: MessageFormat::Plain, ..UserPreferences::default() };
+    assert_eq!(plain.format_discord_message("alice", "hi", Some("Vector")), "alice: hi");
+}
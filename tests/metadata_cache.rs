@@ -0,0 +1,150 @@
+//! Persistence and bulk access of the metadata cache.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use vecord::metadata::{MetadataCache, UserMetadata};
+use vecord::nostr::SubscriberList;
+use vector_sdk::nostr::Keys;
+
+#[tokio::test]
+async fn put_saves_the_cache_in_the_background() {
+    let dir = std::env::temp_dir().join(format!("vecord-metadata-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("metadata_cache.json");
+    let _ = std::fs::remove_file(&path);
+
+    let cache = MetadataCache::new(Some(path.to_string_lossy().to_string())).unwrap();
+    let pubkey = Keys::generate().public_key();
+    let mut metadata = UserMetadata::new(&pubkey).unwrap();
+    metadata.name = Some("alice".to_string());
+    cache.put(metadata);
+
+    // `put` returns before the write, so wait for the spawned save to land
+    let saved = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(contents) = tokio::fs::read_to_string(&path).await {
+                if contents.contains("alice") {
+                    return contents;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("metadata cache was never written");
+
+    // A fresh cache loads what the background save wrote
+    assert!(saved.contains("alice"));
+    let reloaded = MetadataCache::new(Some(path.to_string_lossy().to_string())).unwrap();
+    assert_eq!(reloaded.get(&pubkey).and_then(|m| m.name), Some("alice".to_string()));
+}
+
+#[tokio::test]
+async fn get_all_tracks_puts_and_invalidations() {
+    let cache = MetadataCache::new(None).unwrap();
+    assert!(cache.is_empty());
+
+    let alice = Keys::generate().public_key();
+    let bob = Keys::generate().public_key();
+    for (pubkey, name) in [(&alice, "alice"), (&bob, "bob")] {
+        let mut metadata = UserMetadata::new(pubkey).unwrap();
+        metadata.name = Some(name.to_string());
+        cache.put(metadata);
+    }
+
+    // Replacing an entry keeps a single copy of it
+    let mut renamed = UserMetadata::new(&alice).unwrap();
+    renamed.name = Some("alice2".to_string());
+    cache.put(renamed);
+    cache.invalidate(&bob);
+
+    let all = cache.get_all();
+    assert_eq!(cache.len(), 1);
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].name.as_deref(), Some("alice2"));
+
+    cache.invalidate(&alice);
+    assert!(cache.is_empty());
+    assert!(cache.get_all().is_empty());
+}
+
+#[tokio::test]
+async fn import_keeps_the_newer_profile() {
+    let path = std::env::temp_dir().join(format!("vecord-metadata-import-{}.json", std::process::id()));
+    let cache = MetadataCache::new(None).unwrap();
+
+    let fresh = Keys::generate().public_key();
+    let stale = Keys::generate().public_key();
+    let added = Keys::generate().public_key();
+    let profile = |pubkey, name: &str, last_updated| {
+        let mut metadata = UserMetadata::new(pubkey).unwrap();
+        metadata.name = Some(name.to_string());
+        metadata.last_updated = last_updated;
+        metadata
+    };
+    cache.put(profile(&fresh, "cached", 100));
+    cache.put(profile(&stale, "cached", 100));
+
+    let export = vec![
+        profile(&fresh, "imported", 50),
+        profile(&stale, "imported", 200),
+        profile(&added, "imported", 1),
+    ];
+    std::fs::write(&path, serde_json::to_string(&export).unwrap()).unwrap();
+
+    assert_eq!(cache.import_from_json(&path).await.unwrap(), 2);
+    assert_eq!(cache.get(&fresh).and_then(|m| m.name).as_deref(), Some("cached"));
+    assert_eq!(cache.get(&stale).and_then(|m| m.name).as_deref(), Some("imported"));
+    assert_eq!(cache.get(&added).and_then(|m| m.name).as_deref(), Some("imported"));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unversioned_cache_files_are_migrated() {
+    let dir = std::env::temp_dir().join(format!("vecord-metadata-migrate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("metadata_cache.json");
+
+    // Version 1 stored the entries as a bare map, before the banner, Lightning and website fields
+    let pubkey = Keys::generate().public_key();
+    let npub = UserMetadata::new(&pubkey).unwrap().pubkey;
+    let legacy = serde_json::json!({
+        npub.clone(): {
+            "pubkey": npub, "name": "alice", "display_name": null, "picture": null,
+            "nip05": null, "about": null, "last_updated": 1,
+        }
+    });
+    std::fs::write(&path, legacy.to_string()).unwrap();
+
+    let cache = MetadataCache::new(Some(path.to_string_lossy().to_string())).unwrap();
+    let metadata = cache.get(&pubkey).unwrap();
+    assert_eq!(metadata.name, Some("alice".to_string()));
+    assert_eq!(metadata.website, None);
+
+    // Saving writes the current, versioned schema
+    cache.save_to_file_async().await;
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["version"], 2);
+    assert_eq!(saved["entries"][npub.as_str()]["name"], "alice");
+}
+
+#[tokio::test]
+async fn subscribers_are_paired_with_their_cached_profiles() {
+    let cache = MetadataCache::new(None).unwrap();
+    let subscribers = SubscriberList::new(None).unwrap();
+    let alice = Keys::generate().public_key();
+    let bob = Keys::generate().public_key();
+    subscribers.add(alice).await;
+    subscribers.add(bob).await;
+
+    let mut metadata = UserMetadata::new(&alice).unwrap();
+    metadata.name = Some("alice".to_string());
+    cache.put(metadata);
+    assert_eq!(cache.get_many(&[bob, alice])[1].as_ref().and_then(|m| m.name.as_deref()), Some("alice"));
+
+    let snapshot: HashMap<_, _> = subscribers.with_metadata_snapshot(&cache).await.into_iter().collect();
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot[&alice].as_ref().and_then(|m| m.name.as_deref()), Some("alice"));
+    assert!(snapshot[&bob].is_none());
+}
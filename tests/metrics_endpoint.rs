@@ -0,0 +1,19 @@
+//! The Prometheus metrics listener, which runs without the admin API.
+
+use tokio::net::TcpListener;
+use vecord::metrics;
+
+#[tokio::test]
+async fn metrics_are_served_in_the_prometheus_format() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, metrics::router()).await.unwrap();
+    });
+
+    let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/plain; version=0.0.4");
+    let body = response.text().await.unwrap();
+    assert!(body.contains("# TYPE nostr_event_verification_failures_total counter"));
+}
@@ -0,0 +1,39 @@
+//! Crash-safe replacement of persisted files.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use vecord::persist::atomic_write;
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vecord-persist-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("subscribers.txt")
+}
+
+#[test]
+fn atomic_write_replaces_the_file() {
+    let path = temp_file("replace");
+    std::fs::write(&path, "old\n").unwrap();
+    let path_str = path.to_string_lossy().to_string();
+
+    atomic_write(&path_str, |file| file.write_all(b"new\n")).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+    assert!(!PathBuf::from(format!("{}.tmp", path_str)).exists());
+}
+
+#[test]
+fn failed_atomic_write_keeps_the_old_contents() {
+    let path = temp_file("failed");
+    std::fs::write(&path, "old\n").unwrap();
+    let path_str = path.to_string_lossy().to_string();
+
+    let result = atomic_write(&path_str, |file| {
+        file.write_all(b"partial")?;
+        Err(io::Error::other("interrupted"))
+    });
+
+    assert!(result.is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+    assert!(!PathBuf::from(format!("{}.tmp", path_str)).exists());
+}
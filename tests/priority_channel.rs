@@ -0,0 +1,39 @@
+//! Ordering of messages on a priority channel.
+
+use tracing::Span;
+use vecord::message::{BridgeMessage, SystemEventType};
+use vecord::priority::priority_channel;
+
+fn user_message(content: &str) -> BridgeMessage {
+    BridgeMessage::Discord {
+        author: "alice".to_string(),
+        content: content.to_string(),
+        image: None,
+        image_url: None,
+        federation_source: None,
+        guild_name: None,
+        guild_icon_url: None,
+        author_id: None,
+        message_id: None,
+        span: Span::none(),
+    }
+}
+
+#[tokio::test]
+async fn system_messages_overtake_queued_user_messages() {
+    let (tx, mut rx) = priority_channel::<BridgeMessage>(10);
+    tx.send(user_message("first")).await.unwrap();
+    tx.send(user_message("second")).await.unwrap();
+    tx.send(BridgeMessage::System {
+        event_type: SystemEventType::ChannelUnavailable,
+        message: "channel unavailable".to_string(),
+        delivered: None,
+        span: Span::none(),
+    }).await.unwrap();
+    drop(tx);
+
+    assert!(matches!(rx.recv().await, Some(BridgeMessage::System { .. })));
+    assert!(matches!(rx.recv().await, Some(BridgeMessage::Discord { content, .. }) if content == "first"));
+    assert!(matches!(rx.recv().await, Some(BridgeMessage::Discord { content, .. }) if content == "second"));
+    assert!(rx.recv().await.is_none());
+}
@@ -0,0 +1,38 @@
+//! Per-relay publishing rates from `[[relays]]`.
+
+use std::time::{Duration, Instant};
+use vecord::config::RelayConfig;
+use vecord::nostr::{RelayRateLimits, TokenBucket};
+
+#[tokio::test]
+async fn bucket_allows_a_burst_then_waits() {
+    let bucket = TokenBucket::new(20.0);
+
+    let started = Instant::now();
+    for _ in 0..20 {
+        bucket.acquire().await;
+    }
+    assert!(started.elapsed() < Duration::from_millis(40));
+
+    // The burst is spent, so the next token takes 1/20th of a second to refill
+    bucket.acquire().await;
+    assert!(started.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn only_write_relays_with_a_rate_get_their_own_limit() {
+    let relays: Vec<RelayConfig> = serde_json::from_str(r#"[
+        {"url":"wss://fast.example","max_msgs_per_sec":10},
+        {"url":"wss://unlimited.example"},
+        {"url":"wss://read.example","max_msgs_per_sec":1,"read_only":true}
+    ]"#).unwrap();
+    let write_relays = ["wss://fast.example".to_string(), "wss://unlimited.example".to_string()];
+
+    let limits = RelayRateLimits::new(&relays, &write_relays, None);
+    assert!(limits.is_active());
+    assert_eq!(limits.relays().collect::<Vec<_>>(), ["wss://fast.example"]);
+
+    // Without any rate, events are sent the usual way
+    assert!(!RelayRateLimits::new(&[], &write_relays, None).is_active());
+    assert!(RelayRateLimits::new(&[], &write_relays, Some(5.0)).is_active());
+}
@@ -0,0 +1,49 @@
+//! Relay URLs are normalized and deduplicated when the config is parsed.
+
+use vecord::config::{normalize_relays, Config, ConfigError};
+
+fn relays(urls: &[&str]) -> Vec<String> {
+    urls.iter().map(|url| url.to_string()).collect()
+}
+
+#[test]
+fn duplicate_relays_are_removed() {
+    let (normalized, duplicates) = normalize_relays("NOSTR_RELAYS", relays(&[
+        "wss://relay.example.com/",
+        " wss://relay.example.com",
+        "wss://RELAY.example.com",
+        "wss://jskitty.cat/nostr/",
+        "ws://127.0.0.1:7777",
+    ])).unwrap();
+
+    assert_eq!(normalized, relays(&["ws://127.0.0.1:7777", "wss://jskitty.cat/nostr", "wss://relay.example.com"]));
+    assert_eq!(duplicates, relays(&["wss://relay.example.com", "wss://relay.example.com"]));
+}
+
+#[test]
+fn non_websocket_relays_are_rejected() {
+    let error = normalize_relays("NOSTR_RELAYS", relays(&["https://relay.example.com"])).unwrap_err();
+    assert!(matches!(error, ConfigError::InvalidValue { value, .. } if value == "https://relay.example.com"));
+}
+
+#[test]
+fn at_least_one_relay_is_required() {
+    assert!(normalize_relays("NOSTR_RELAYS", Vec::new()).is_err());
+}
+
+#[test]
+fn relay_count_guards() {
+    let mut config = Config::default_for_testing();
+    config.nostr_read_relays = relays(&["wss://relay.example.com", "wss://jskitty.cat/nostr"]);
+    config.nostr_write_relays = relays(&["wss://relay.example.com"]);
+
+    config.min_relay_count = 3;
+    let error = config.validate().unwrap_err();
+    assert!(matches!(error.downcast_ref::<ConfigError>(), Some(ConfigError::InvalidValue { name, .. }) if name == "NOSTR_RELAYS"));
+
+    // Too many relays only warns
+    config.min_relay_count = 1;
+    config.max_relay_count = Some(1);
+    let warnings = config.validate().unwrap();
+    assert!(warnings.iter().any(|warning| warning.contains("MAX_RELAY_COUNT")));
+}
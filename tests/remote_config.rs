@@ -0,0 +1,69 @@
+//! Loading the bridge config from `VECORD_CONFIG_URL`.
+//!
+//! Everything runs in one test, as it sets the environment the other tests would read in parallel.
+
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use vecord::config::{Config, ConfigError};
+
+/// Serves `remote` at the returned URL, only to requests bearing `token` when given
+async fn serve(remote: Value, token: Option<&'static str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/config.json", listener.local_addr().unwrap());
+    let app = Router::new().route("/config.json", get(move |headers: HeaderMap| async move {
+        let expected = token.map(|token| format!("Bearer {}", token));
+        let authorized = expected.is_none_or(|expected| headers.get("authorization").is_some_and(|value| *value == *expected));
+        if authorized { Ok(Json(remote)) } else { Err(StatusCode::UNAUTHORIZED) }
+    }));
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    url
+}
+
+#[tokio::test]
+async fn remote_config_fills_in_unset_environment_variables() {
+    // The local environment wins over the remote config
+    std::env::set_var("DISCORD_CHANNEL_ID", "7");
+
+    let remote = json!({
+        "DISCORD_TOKEN": "remote-token",
+        "DISCORD_CHANNEL_ID": 42,
+        "NOSTR_PRIVATE_KEY": "0000000000000000000000000000000000000000000000000000000000000001",
+        "NOSTR_RELAYS": ["wss://one.example", "wss://two.example"],
+        "BRIDGE_REACTIONS": true,
+    });
+    let url = serve(remote.clone(), None).await;
+
+    let config = Config::from_url(&url, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(config.discord_token.as_deref(), Some("remote-token"));
+    assert_eq!(config.discord_channel_id, 7);
+    assert_eq!(config.nostr_relays(), ["wss://one.example", "wss://two.example"]);
+    assert!(config.bridge_reactions);
+
+    // Remote values are never written to the process environment
+    assert!(std::env::var("DISCORD_TOKEN").is_err());
+    assert!(std::env::var("NOSTR_PRIVATE_KEY").is_err());
+
+    // A protected config is only served with the bearer token
+    let url = serve(remote.clone(), Some("fleet-secret")).await;
+    assert!(Config::from_url(&url, Duration::from_secs(5)).await.is_err());
+    std::env::set_var("VECORD_CONFIG_TOKEN", "fleet-secret");
+    assert!(Config::from_url(&url, Duration::from_secs(5)).await.is_ok());
+
+    // Flags only accept true or false, anything else is reported instead of read as false
+    let mut misspelled = remote.clone();
+    misspelled["BRIDGE_REACTIONS"] = json!("yes");
+    let url = serve(misspelled, Some("fleet-secret")).await;
+    let Err(error) = Config::from_url(&url, Duration::from_secs(5)).await else {
+        panic!("BRIDGE_REACTIONS=yes was accepted");
+    };
+    assert!(matches!(
+        error.downcast::<ConfigError>(),
+        Ok(ConfigError::ParseError { field, value, .. }) if field == "BRIDGE_REACTIONS" && value == "yes"
+    ));
+}
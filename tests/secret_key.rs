@@ -0,0 +1,31 @@
+use vecord::config::parse_secret_key;
+use vecord::nostr::pubkey_to_bech32_or_fail;
+use vector_sdk::nostr::{Keys, ToBech32};
+
+// NIP-06 test vector, the same key in both encodings
+const NSEC: &str = "nsec10allq0gjx7fddtzef0ax00mdps9t2kmtrldkyjfs8l5xruwvh2dq0lhhkp";
+const HEX: &str = "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a";
+const NPUB: &str = "npub1zutzeysacnf9rru6zqwmxd54mud0k44tst6l70ja5mhv8jjumytsd2x7nu";
+
+#[test]
+fn nsec_key_derives_the_expected_npub() {
+    let keys = Keys::new(parse_secret_key(NSEC).unwrap());
+    assert_eq!(keys.public_key().to_bech32().unwrap(), NPUB);
+}
+
+#[test]
+fn hex_key_matches_its_nsec() {
+    assert_eq!(parse_secret_key(HEX).unwrap(), parse_secret_key(NSEC).unwrap());
+}
+
+#[test]
+fn malformed_nsec_is_rejected() {
+    let err = parse_secret_key("nsec1notakey").unwrap_err();
+    assert!(err.to_string().starts_with("Invalid nsec key"));
+}
+
+#[test]
+fn pubkey_is_encoded_as_the_expected_npub() {
+    let keys = Keys::new(parse_secret_key(HEX).unwrap());
+    assert_eq!(pubkey_to_bech32_or_fail(&keys.public_key()).unwrap(), NPUB);
+}
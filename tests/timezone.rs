@@ -0,0 +1,18 @@
+use vecord::time::{format_local, parse_timezone};
+
+// British Summer Time began at 01:00 UTC on 2024-03-31
+const BST_START: u64 = 1_711_846_800;
+
+#[test]
+fn europe_london_follows_the_dst_transition() {
+    let london = parse_timezone("Europe/London").unwrap();
+
+    assert_eq!(format_local(BST_START - 1, london), "2024-03-31 00:59:59 GMT");
+    assert_eq!(format_local(BST_START, london), "2024-03-31 02:00:00 BST");
+}
+
+#[test]
+fn invalid_timezone_is_rejected() {
+    let err = parse_timezone("Mars/Olympus_Mons").unwrap_err();
+    assert!(err.to_string().contains("Mars/Olympus_Mons"));
+}
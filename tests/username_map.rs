@@ -0,0 +1,31 @@
+//! Discord usernames Nostr users link with `!register-discord`.
+
+use vecord::usernames::UsernameMap;
+use vector_sdk::nostr::Keys;
+
+#[tokio::test]
+async fn usernames_are_case_insensitive_and_one_per_user() {
+    let usernames = UsernameMap::default();
+    let pubkey = Keys::generate().public_key();
+
+    usernames.register("Satoshi", pubkey).await;
+    assert_eq!(usernames.get("satoshi"), Some(pubkey));
+    assert_eq!(usernames.get("SATOSHI"), Some(pubkey));
+
+    // Registering again moves the link to the new username
+    usernames.register("hal", pubkey).await;
+    assert_eq!(usernames.get("satoshi"), None);
+    assert_eq!(usernames.get("hal"), Some(pubkey));
+}
+
+#[tokio::test]
+async fn registrations_are_reloaded_from_the_file() {
+    let path = std::env::temp_dir().join(format!("vecord-usernames-{}.json", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    let pubkey = Keys::generate().public_key();
+
+    UsernameMap::new(Some(path.clone())).register("satoshi", pubkey).await;
+    assert_eq!(UsernameMap::new(Some(path.clone())).get("Satoshi"), Some(pubkey));
+
+    let _ = std::fs::remove_file(&path);
+}